@@ -0,0 +1,138 @@
+use std::process::{Command, ExitStatus};
+
+use crate::errors::ExecutionError;
+
+/// Spawns the command and waits for it. Signals craft receives meanwhile are
+/// forwarded to the process group of the child, a failing child becomes
+/// `ExecutionError::ChildProcessFailed`.
+pub(crate) fn run_child(name: &str, command: &mut Command) -> Result<(), ExecutionError> {
+    let status = spawn_and_wait(command)
+        .map_err(|e| ExecutionError::JobExecutionFailed(name.to_string(), e.to_string()))?;
+
+    if !status.success() {
+        return Err(ExecutionError::ChildProcessFailed(name.to_string(), status));
+    }
+
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn spawn_and_wait(command: &mut Command) -> std::io::Result<ExitStatus> {
+    command.spawn()?.wait()
+}
+
+#[cfg(unix)]
+fn spawn_and_wait(command: &mut Command) -> std::io::Result<ExitStatus> {
+    use std::os::unix::process::CommandExt;
+
+    // Its own group, so the signals reach everything the shell starts
+    let mut child = command.process_group(0).spawn()?;
+    let pgid = child.id() as libc::pid_t;
+
+    let terminal = signals::Terminal::hand_to(pgid);
+    signals::forward_to(pgid);
+    let status = child.wait();
+    signals::restore();
+    terminal.take_back();
+
+    status
+}
+
+#[cfg(unix)]
+mod signals {
+    use std::sync::atomic::{AtomicI32, Ordering};
+
+    const FORWARDED: [libc::c_int; 4] = [libc::SIGINT, libc::SIGTERM, libc::SIGHUP, libc::SIGQUIT];
+
+    static CHILD_GROUP: AtomicI32 = AtomicI32::new(0);
+
+    extern "C" fn forward(signal: libc::c_int) {
+        let pgid = CHILD_GROUP.load(Ordering::SeqCst);
+        if pgid > 0 {
+            // Only async-signal-safe calls in here
+            unsafe {
+                libc::kill(-pgid, signal);
+            }
+        }
+    }
+
+    pub fn forward_to(pgid: libc::pid_t) {
+        CHILD_GROUP.store(pgid, Ordering::SeqCst);
+        for signal in FORWARDED {
+            unsafe {
+                libc::signal(signal, forward as *const () as libc::sighandler_t);
+            }
+        }
+    }
+
+    pub fn restore() {
+        CHILD_GROUP.store(0, Ordering::SeqCst);
+        for signal in FORWARDED {
+            unsafe {
+                libc::signal(signal, libc::SIG_DFL);
+            }
+        }
+    }
+
+    /// Interactive children need to be the foreground group of the terminal,
+    /// otherwise reading from it stops them
+    pub struct Terminal {
+        craft_group: Option<libc::pid_t>,
+    }
+
+    impl Terminal {
+        pub fn hand_to(pgid: libc::pid_t) -> Self {
+            unsafe {
+                let craft_group = libc::getpgrp();
+                if libc::isatty(libc::STDIN_FILENO) == 1
+                    && libc::tcgetpgrp(libc::STDIN_FILENO) == craft_group
+                    && libc::tcsetpgrp(libc::STDIN_FILENO, pgid) == 0
+                {
+                    return Self {
+                        craft_group: Some(craft_group),
+                    };
+                }
+            }
+
+            Self { craft_group: None }
+        }
+
+        pub fn take_back(self) {
+            if let Some(craft_group) = self.craft_group {
+                // Craft is in the background now, which would stop it on tcsetpgrp
+                unsafe {
+                    let previous = libc::signal(libc::SIGTTOU, libc::SIG_IGN);
+                    libc::tcsetpgrp(libc::STDIN_FILENO, craft_group);
+                    libc::signal(libc::SIGTTOU, previous);
+                }
+            }
+        }
+    }
+}
+
+// ─── Tests ───────────────────────────────────────────────────────────────────
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_propagates_exit_code() {
+        let result = run_child("fail", Command::new("sh").args(["-c", "exit 7"]));
+
+        match result {
+            Err(error @ ExecutionError::ChildProcessFailed(..)) => {
+                assert_eq!(error.exit_code(), 7)
+            }
+            other => panic!("Unexpected result {:?}", other),
+        }
+        assert!(run_child("succeed", Command::new("sh").args(["-c", "exit 0"])).is_ok());
+    }
+
+    #[test]
+    fn test_killed_child_exits_with_signal() {
+        let result = run_child("killed", Command::new("sh").args(["-c", "kill -TERM $$"]));
+
+        assert_eq!(result.unwrap_err().exit_code(), 128 + libc::SIGTERM);
+    }
+}
@@ -1,3 +1,4 @@
+use crate::actors::child_process::run_child;
 use crate::actors::install::PipeResult;
 use crate::contracts::Actor;
 use crate::errors::ExecutionError;
@@ -132,12 +133,6 @@ impl Actor<PipeResult> for ExecActor {
             command_to_execute.args(args);
         }
 
-        let mut child = command_to_execute
-            .spawn()
-            .expect("failed to execute process");
-
-        let _ = child.wait().expect("child process wasn't running");
-
-        Ok(())
+        run_child(&self.script, &mut command_to_execute)
     }
 }
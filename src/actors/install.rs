@@ -1,5 +1,8 @@
 use std::{
-    sync::mpsc::Receiver,
+    collections::HashSet,
+    env,
+    path::{Path, PathBuf},
+    sync::mpsc::{Receiver, Sender},
     thread::{self, JoinHandle},
 };
 
@@ -7,13 +10,25 @@ use async_trait::async_trait;
 
 use crate::actors::peer_resolver::PeerResolver;
 use crate::cache::PackagesCache;
-use crate::contracts::{Lockfile, PersistentCache};
+use crate::cache::RegistryKey;
+use crate::command::ProgramDesire;
+use crate::config::NPMRC;
+use crate::contracts::{Lockfile, Logger, PersistentCache};
+use crate::fs::ImportMethod;
+use crate::lockfile::constants::CURRENT_IMPORTER;
 use crate::lockfile::lock_file_actor::LockFileActor;
+use crate::lockfile::lockfile_structure::LockfileResolution;
+use crate::network::NetworkMode;
+use crate::package::{Engines, Overrides, Package, PackageJsonFile, PackageRecorder, Platform};
+use crate::pipeline::{Dedupe, ExtractArtifactsMap, ResolvedItem};
 use crate::{
     contracts::{Actor, Pipe, PipeArtifact, Progress, ProgressAction},
     errors::ExecutionError,
     logger::CraftLogger,
-    pipeline::{DownloaderPipe, ExtractorPipe, LinkerPipe, ResolverPipe},
+    pipeline::{
+        hoist, BuildPolicy, DownloaderPipe, ExtractorPipe, LifecycleScriptsPipe, LinkerPipe,
+        LockfileResolverPipe, NodeLinker, PeerCheck, ResolverPipe,
+    },
     ui::UIProgress,
 };
 
@@ -29,33 +44,13 @@ pub enum PackageType {
 impl PackageType {
     pub fn get_parts(&self) -> (String, String) {
         fn split_name(key: &str) -> (String, String) {
-            let nums_of_ats = key.chars().filter(|c| *c == '@').count();
-            let is_scoped_package = key.starts_with("@");
-
-            // Normal case like is-even@1
-            if !is_scoped_package && nums_of_ats == 1 {
-                let parts = key.rsplitn(2, '@').collect::<Vec<_>>();
-                return (parts[1].to_string(), parts[0].to_string());
-            }
-
-            if is_scoped_package && nums_of_ats == 2 {
-                let parts = key.rsplitn(2, '@').collect::<Vec<_>>();
-                return (parts[1].to_string(), parts[0].to_string());
-            }
-
-            // Normal case like is-even => no version
-            if nums_of_ats == 0 {
-                return (key.to_string(), "*".to_string());
-            }
-
-            // Normal case like @babel/transform@1
-            if is_scoped_package && nums_of_ats == 1 {
-                // There is no version available
-                return (key.to_string(), "*".to_string());
+            // The name ends at the first @ that is not the scope, everything
+            // after it is the version, which may contain further @s like
+            // git+ssh://git@github.com/owner/repo.git
+            match key.get(1..).and_then(|rest| rest.find('@')) {
+                Some(index) => (key[..index + 1].to_string(), key[index + 2..].to_string()),
+                None => (key.to_string(), "*".to_string()),
             }
-
-            // We can't have like 3 @s
-            panic!("Too many @s")
         }
         match self {
             PackageType::Dev(d) => split_name(d),
@@ -65,15 +60,248 @@ impl PackageType {
             PackageType::Global(g) => split_name(g),
         }
     }
+
+    /// The section of package.json the package is saved to
+    pub fn section(&self) -> Option<&'static str> {
+        match self {
+            PackageType::Dev(_) => Some("devDependencies"),
+            PackageType::Optional(_) => Some("optionalDependencies"),
+            PackageType::Prod(_) => Some("dependencies"),
+            PackageType::Peer(_) | PackageType::Global(_) => None,
+        }
+    }
+}
+
+/// A project whose dependencies are installed into its own node_modules
+#[derive(Debug, Clone)]
+pub struct Importer {
+    /// The importer id of the lockfile
+    pub id: String,
+    pub directory: PathBuf,
+    pub packages: Vec<PackageType>,
+}
+
+impl Importer {
+    pub fn new(id: &str, directory: &Path, packages: Vec<PackageType>) -> Self {
+        Self {
+            id: id.to_string(),
+            directory: directory.to_path_buf(),
+            packages,
+        }
+    }
 }
 
 pub struct InstallActor {
-    packages: Vec<PackageType>,
+    importers: Vec<Importer>,
+    /// The root of the workspace, where the lockfile lives
+    root: PathBuf,
+    program_desire: ProgramDesire,
+    save: bool,
+    report_dedupe: bool,
 }
 
 impl InstallActor {
-    pub fn new(packages: Vec<PackageType>) -> Self {
-        Self { packages }
+    pub fn new(packages: Vec<PackageType>, program_desire: ProgramDesire) -> Self {
+        let importer = Importer::new(CURRENT_IMPORTER, &env::current_dir().unwrap(), packages);
+        Self::with_importers(vec![importer], program_desire)
+    }
+
+    /// Installs every project of a workspace
+    pub fn with_importers(importers: Vec<Importer>, program_desire: ProgramDesire) -> Self {
+        let root = importers
+            .iter()
+            .find(|importer| importer.id == CURRENT_IMPORTER)
+            .map(|importer| importer.directory.clone())
+            .unwrap_or_else(|| env::current_dir().unwrap());
+        Self {
+            importers,
+            root,
+            program_desire,
+            save: false,
+            report_dedupe: false,
+        }
+    }
+
+    /// Writes the installed packages to package.json
+    pub fn saving(mut self) -> Self {
+        self.save = true;
+        self
+    }
+
+    /// Reports the deduplicated versions, e.g. for `craft dedupe`
+    pub fn deduping(mut self) -> Self {
+        self.report_dedupe = true;
+        self
+    }
+
+    /// `--save-exact` or `save-exact` of .npmrc save the exact version,
+    /// otherwise `save-prefix` is put in front
+    fn save_prefix(&self) -> String {
+        if self.program_desire.save_exact || NPMRC.get("save-exact") == Some("true") {
+            return "".to_string();
+        }
+
+        NPMRC.get("save-prefix").unwrap_or("^").to_string()
+    }
+
+    /// Saves the directly installed packages into package.json, the lockfile
+    /// gets the same specifiers
+    fn save_to_package_json(
+        &self,
+        importer: &Importer,
+        resolved: &mut [ResolvedItem],
+    ) -> Result<(), ExecutionError> {
+        let prefix = self.save_prefix();
+        let mut package_json = PackageJsonFile::read(&importer.directory)?;
+
+        for item in resolved.iter_mut().filter(|item| item.parent.is_none()) {
+            let Some(section) = item.package_type.section() else {
+                continue;
+            };
+            let package = Package::new(item.package_type.clone());
+            item.specifier = package.save_specifier(&item.package.version, &prefix);
+
+            // A package lives in one section, e.g. moving it to devDependencies
+            package_json.remove_dependency(&package.name);
+            package_json.add_dependency(section, &package.name, &item.specifier);
+        }
+
+        package_json.save()
+    }
+
+    /// The current platform unless `--os` or `--cpu` ask for another one
+    fn platform(&self) -> Platform {
+        Platform::current()
+            .with_os(self.program_desire.os.clone())
+            .with_cpu(self.program_desire.cpu.clone())
+    }
+
+    /// `--offline` and `--prefer-offline`, or `offline` and `prefer-offline`
+    /// of .npmrc
+    fn network_mode(&self) -> NetworkMode {
+        let is_set = |key: &str| NPMRC.get(key) == Some("true");
+        if self.program_desire.offline || is_set("offline") {
+            NetworkMode::Offline
+        } else if self.program_desire.prefer_offline || is_set("prefer-offline") {
+            NetworkMode::PreferOffline
+        } else {
+            NetworkMode::Online
+        }
+    }
+
+    /// `--no-peers` turns it off, otherwise `auto-install-peers` of .npmrc or
+    /// the setting of the lockfile decide, like pnpm it is on by default
+    fn auto_install_peers(&self, lockfile: &Path) -> bool {
+        if self.program_desire.no_peers {
+            return false;
+        }
+        if let Some(value) = NPMRC.get("auto-install-peers") {
+            return value == "true";
+        }
+
+        LockFileActor::read_lock_file(lockfile)
+            .ok()
+            .and_then(|lockfile| lockfile.settings)
+            .and_then(|settings| settings.auto_install_peers)
+            .unwrap_or(true)
+    }
+
+    /// Keeps as few versions of every package as the ranges allow, preferring
+    /// the versions of the lockfile
+    fn dedupe(
+        &self,
+        importer: &Importer,
+        lockfile: &Path,
+        resolved: &mut Vec<ResolvedItem>,
+        recorder: &mut PackageRecorder,
+    ) {
+        let locked = LockFileActor::read_lock_file(lockfile)
+            .ok()
+            .and_then(|lockfile| lockfile.packages)
+            .map(|packages| packages.into_keys().collect::<Vec<_>>())
+            .unwrap_or_default();
+
+        let changes = Dedupe::new(Overrides::load(&self.root))
+            .with_locked(locked)
+            .run(resolved, recorder);
+
+        if self.report_dedupe && changes.is_empty() {
+            CraftLogger::info(format!("Nothing to dedupe in {}", importer.id));
+        }
+        for change in changes {
+            match self.report_dedupe {
+                true => CraftLogger::info(format!("Deduped {}", change)),
+                false => CraftLogger::verbose(format!("Deduped {}", change)),
+            }
+        }
+    }
+
+    /// Warns about every package that wants other engines, `--engine-strict`
+    /// or `engine-strict` of .npmrc fail the install instead
+    fn check_engines(&self, resolved: &[ResolvedItem]) -> Result<(), ExecutionError> {
+        let engines = Engines::current();
+        let mut mismatches = resolved
+            .iter()
+            .flat_map(|item| engines.check(&item.package))
+            .map(|mismatch| mismatch.to_string())
+            .collect::<Vec<_>>();
+        mismatches.sort();
+        mismatches.dedup();
+
+        let engine_strict =
+            self.program_desire.engine_strict || NPMRC.get("engine-strict") == Some("true");
+        if engine_strict && !mismatches.is_empty() {
+            return Err(ExecutionError::UnsupportedEngine(mismatches.join("\n")));
+        }
+
+        for mismatch in mismatches {
+            CraftLogger::warn(format!("Unsupported engine: {}", mismatch));
+        }
+
+        Ok(())
+    }
+
+    /// Optional packages that could not be downloaded or extracted are left
+    /// out together with everything below them, any other package fails
+    fn drop_failed_optional(
+        resolved: Vec<ResolvedItem>,
+        extracted: &ExtractArtifactsMap,
+        recorder: &mut PackageRecorder,
+    ) -> Result<Vec<ResolvedItem>, ExecutionError> {
+        let mut failed = HashSet::new();
+
+        for item in &resolved {
+            let is_linked_directly = matches!(
+                item.package.resolution,
+                Some(LockfileResolution::Directory(_))
+            );
+            if is_linked_directly || extracted.contains_key(&item.package.target().to_string()) {
+                continue;
+            }
+
+            if !item.is_optional() {
+                return Err(ExecutionError::JobExecutionFailed(
+                    item.package.to_string(),
+                    "the package could not be downloaded".to_string(),
+                ));
+            }
+
+            CraftLogger::warn(format!(
+                "Skipping optional dependency {}, it could not be downloaded",
+                item.package
+            ));
+            let key: RegistryKey = item.package.clone().into();
+            recorder.skip_optional(&key);
+            failed.insert(key);
+        }
+
+        Ok(resolved
+            .into_iter()
+            .filter(|item| {
+                !failed.contains(&item.package.clone().into())
+                    && !item.parent.iter().flatten().any(|p| failed.contains(p))
+            })
+            .collect())
     }
 
     fn start_progress(&self, rx: Receiver<ProgressAction>) -> JoinHandle<()> {
@@ -87,32 +315,60 @@ impl InstallActor {
 
 pub(crate) type PipeResult = Result<(), ExecutionError>;
 
-#[async_trait]
-impl Actor<PipeResult> for InstallActor {
-    async fn start(&mut self) -> PipeResult {
-        let (tx, rx) = std::sync::mpsc::channel();
-        let mut cache = PackagesCache::default();
-        cache.init().await.unwrap();
-        let ui_thread = self.start_progress(rx);
-
+impl InstallActor {
+    async fn install_importer(
+        &self,
+        importer: &Importer,
+        tx: Sender<ProgressAction>,
+    ) -> Result<(Vec<ResolvedItem>, PackageRecorder), ExecutionError> {
         // ─── Start Resolving ─────────────────────────
 
-        CraftLogger::verbose("Resolving dependencies");
-        let resolve_artifacts = ResolverPipe::new(self.packages.clone(), tx.clone())
-            .run()
-            .await?;
+        let frozen_lockfile = self.program_desire.frozen_lockfile;
+        let lockfile = self.root.join("pnpm-lock.yaml");
+        let (mut resolve_artifacts, mut recorder) = if frozen_lockfile {
+            CraftLogger::verbose(format!(
+                "Resolving dependencies of {} from lockfile",
+                importer.id
+            ));
+            LockfileResolverPipe::new(importer.packages.clone(), tx.clone())
+                .for_importer(&importer.id, &importer.directory)
+                .with_platform(self.platform())
+                .run()
+                .await?
+        } else {
+            CraftLogger::verbose(format!("Resolving dependencies of {}", importer.id));
+            ResolverPipe::new(importer.packages.clone(), tx.clone())
+                .for_importer(&importer.id)
+                .with_platform(self.platform())
+                .with_auto_install_peers(self.auto_install_peers(&lockfile))
+                .with_network_mode(self.network_mode())
+                .run()
+                .await?
+        };
         CraftLogger::verbose(format!(
             "Resolved: {:?}",
-            resolve_artifacts.0.get_artifacts().len()
+            resolve_artifacts.get_artifacts().len()
         ));
 
-        // ─── Start Mutating ───────────────────────
-        let recorder = PeerResolver::new(resolve_artifacts.1).run().await?;
+        // The lockfile was deduplicated when it was written
+        if !frozen_lockfile {
+            let mut resolved = resolve_artifacts.get_artifacts();
+            self.dedupe(importer, &lockfile, &mut resolved, &mut recorder);
+            resolve_artifacts = resolved.into_iter().collect();
+        }
+
+        let peers = PeerCheck::run(&resolve_artifacts.get_artifacts());
+        if !peers.issues.is_empty() {
+            CraftLogger::warn(peers.report());
+        }
+
+        self.check_engines(&resolve_artifacts.get_artifacts())?;
 
         // ─── Start Downloading ──────────────────────
 
         CraftLogger::verbose("Downloading dependencies");
-        let download_artifacts = DownloaderPipe::new(&resolve_artifacts.0, tx.clone())
+        let download_artifacts = DownloaderPipe::new(&resolve_artifacts, tx.clone())
+            .with_network_mode(self.network_mode())
             .run()
             .await?;
 
@@ -133,28 +389,92 @@ impl Actor<PipeResult> for InstallActor {
             extracted_artifacts.get_artifacts().len()
         ));
 
+        let resolved = Self::drop_failed_optional(
+            resolve_artifacts.get_artifacts(),
+            &extracted_artifacts.get_artifacts(),
+            &mut recorder,
+        )?;
+
+        // ─── Start Mutating ───────────────────────
+
+        if frozen_lockfile {
+            LockfileResolverPipe::attach_binaries(
+                &mut recorder,
+                &extracted_artifacts.get_artifacts(),
+            );
+        }
+        let recorder = PeerResolver::new(recorder).run().await?;
+
         // ─── Start Linking ──────────────────────────
 
         CraftLogger::verbose("Linking dependencies");
+        let node_modules = importer.directory.join("node_modules");
+        let node_linker = NodeLinker::current();
+        // The hoisted layout places packages elsewhere than they were resolved,
+        // the lockfile still records them below what depends on them
+        let placed = match node_linker {
+            NodeLinker::Hoisted => hoist(&resolved, &recorder),
+            _ => resolved.clone(),
+        };
         LinkerPipe::new(
             tx.clone(),
-            resolve_artifacts.0.get_artifacts(),
+            placed.clone(),
             extracted_artifacts.get_artifacts(),
             recorder.clone(),
         )
+        .with_node_modules(node_modules.clone())
+        .with_node_linker(node_linker)
+        .with_import_method(ImportMethod::current())
         .run()
         .await?;
 
-        // ─── Sync Lock File ────────────────────────
-        LockFileActor::new(resolve_artifacts.0.get_artifacts(), recorder)
-            .run()
-            .expect("Error writing lockfile");
-
         // ─── Cleanup ────────────────────────────────
 
-        ExtractorPipe::cleanup(resolve_artifacts.0.get_artifacts()).await?;
+        ExtractorPipe::cleanup(placed.clone(), &node_modules).await?;
 
-        // ─── Link binaries ────────────────────────────────
+        // ─── Run Lifecycle Scripts ──────────────────
+
+        if !self.program_desire.ignore_scripts {
+            CraftLogger::verbose("Running lifecycle scripts");
+            let policy = BuildPolicy::load(&self.root);
+            LifecycleScriptsPipe::new(placed, recorder.clone(), &importer.directory, policy)
+                .with_node_linker(node_linker)
+                .run()
+                .await?;
+        }
+
+        Ok((resolved, recorder))
+    }
+}
+
+#[async_trait]
+impl Actor<PipeResult> for InstallActor {
+    async fn start(&mut self) -> PipeResult {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let mut cache = PackagesCache::default();
+        cache.init().await.unwrap();
+        let ui_thread = self.start_progress(rx);
+
+        let mut resolved_importers = vec![];
+        let mut recorder = PackageRecorder::default();
+
+        for importer in &self.importers {
+            let (mut resolved, importer_recorder) =
+                self.install_importer(importer, tx.clone()).await?;
+            if self.save {
+                self.save_to_package_json(importer, &mut resolved)?;
+            }
+            resolved_importers.push((importer.id.clone(), resolved));
+            recorder.merge(importer_recorder);
+        }
+
+        // ─── Sync Lock File ────────────────────────
+
+        if !self.program_desire.frozen_lockfile {
+            LockFileActor::new(resolved_importers, recorder)
+                .run()
+                .expect("Error writing lockfile");
+        }
 
         drop(tx);
         ui_thread.join().unwrap();
@@ -164,7 +484,11 @@ impl Actor<PipeResult> for InstallActor {
 
 #[cfg(test)]
 mod tests {
+    use super::InstallActor;
     use crate::actors::PackageType;
+    use crate::errors::ExecutionError;
+    use crate::package::PackageRecorder;
+    use crate::pipeline::fixtures::{extracted, item, key};
     use std::collections::HashMap;
 
     #[test]
@@ -175,6 +499,14 @@ mod tests {
         mappings.insert("is-even@~1", ("is-even", "~1"));
         mappings.insert("is-even@~1.2.0", ("is-even", "~1.2.0"));
         mappings.insert("is-even", ("is-even", "*"));
+        mappings.insert(
+            "repo@git+ssh://git@github.com/owner/repo.git#v1",
+            ("repo", "git+ssh://git@github.com/owner/repo.git#v1"),
+        );
+        mappings.insert(
+            "@scope/repo@github:owner/repo#semver:^1.2",
+            ("@scope/repo", "github:owner/repo#semver:^1.2"),
+        );
 
         mappings.iter().for_each(|(k, v)| {
             let pkg_type = PackageType::Dev(k.to_string());
@@ -184,4 +516,35 @@ mod tests {
             assert_eq!(parts.1, v.1);
         })
     }
+
+    #[test]
+    fn test_drop_failed_optional_tolerates_optional_downloads() {
+        let esbuild = item("esbuild", "1.0.0", &[]);
+        let binary = item("@esbuild/linux-x64", "1.0.0", &[key("esbuild", "1.0.0")]).with_type(
+            PackageType::Optional("@esbuild/linux-x64@1.0.0".to_string()),
+        );
+        let extracted = extracted(std::slice::from_ref(&esbuild));
+        let mut recorder = PackageRecorder::default();
+
+        let kept =
+            InstallActor::drop_failed_optional(vec![esbuild, binary], &extracted, &mut recorder)
+                .unwrap();
+
+        assert_eq!(kept.len(), 1);
+        assert_eq!(kept[0].package.name, "esbuild");
+        assert!(recorder.skipped_optional.contains("@esbuild/linux-x64"));
+    }
+
+    #[test]
+    fn test_drop_failed_optional_rejects_required_downloads() {
+        let resolved = vec![item("esbuild", "1.0.0", &[])];
+
+        let result =
+            InstallActor::drop_failed_optional(resolved, &extracted(&[]), &mut Default::default());
+
+        assert!(matches!(
+            result,
+            Err(ExecutionError::JobExecutionFailed(..))
+        ));
+    }
 }
@@ -1,13 +1,17 @@
 mod cache_clean;
+mod child_process;
 mod exec_actor;
 mod install;
 mod peer_resolver;
 mod preprocesse_dependency_install;
+mod remove;
 mod run;
 
 pub use cache_clean::CacheCleanActor;
 pub use exec_actor::ExecActor;
+pub use install::Importer;
 pub use install::InstallActor;
 pub use install::PackageType;
 pub use preprocesse_dependency_install::PreprocessDependencyInstall;
+pub use remove::RemoveActor;
 pub use run::RunActor;
@@ -2,19 +2,33 @@ use crate::actors::PackageType;
 use crate::command::ProgramDesire;
 use crate::errors::ExecutionError;
 use crate::package::PackageJson;
+use crate::workspace::Workspace;
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use super::Importer;
 
 pub struct PreprocessDependencyInstall {
     pub program_desire: ProgramDesire,
+    directory: PathBuf,
 }
 
 impl PreprocessDependencyInstall {
     pub fn new(program_desire: ProgramDesire) -> PreprocessDependencyInstall {
-        PreprocessDependencyInstall { program_desire }
+        PreprocessDependencyInstall {
+            program_desire,
+            directory: PathBuf::from("."),
+        }
+    }
+
+    /// Reads the package.json of another project, e.g. of the workspace
+    pub fn in_directory(mut self, directory: &Path) -> Self {
+        self.directory = directory.to_path_buf();
+        self
     }
 
-    pub(crate) fn read_package_json() -> Result<PackageJson, ExecutionError> {
-        std::fs::read_to_string("package.json")
+    pub(crate) fn read_package_json_in(directory: &Path) -> Result<PackageJson, ExecutionError> {
+        std::fs::read_to_string(directory.join("package.json"))
             .map(|e| e.into())
             .map_err(|_| ExecutionError::PackageJsonNotFound)
     }
@@ -30,7 +44,7 @@ impl PreprocessDependencyInstall {
     /// Calculates the main dependencies to use
     fn calculate_dependencies(&self) -> Result<Vec<PackageType>, ExecutionError> {
         let mut dependencies: Vec<PackageType> = vec![];
-        let package_json = Self::read_package_json()?;
+        let package_json = Self::read_package_json_in(&self.directory)?;
         if self.program_desire.dev_install {
             if let Some(dev_deps) = package_json.dev_dependencies {
                 let mut dev_dependencies = self
@@ -67,14 +81,28 @@ impl PreprocessDependencyInstall {
         Ok(dependencies)
     }
 
-    pub fn get_script() -> Result<HashMap<String, String>, ExecutionError> {
-        let package_json = Self::read_package_json()?;
-        Ok(package_json.scripts.unwrap_or(HashMap::new()))
-    }
-
     pub async fn run(&self) -> Result<Vec<PackageType>, ExecutionError> {
         let dependency_to_install = self.calculate_dependencies()?;
 
         Ok(dependency_to_install)
     }
+
+    /// The dependencies of every project of the workspace, each project is
+    /// an importer of the lockfile
+    pub async fn run_workspace(
+        &self,
+        workspace: &Workspace,
+    ) -> Result<Vec<Importer>, ExecutionError> {
+        let mut importers = vec![];
+
+        for project in &workspace.projects {
+            let packages = PreprocessDependencyInstall::new(self.program_desire.clone())
+                .in_directory(&project.directory)
+                .run()
+                .await?;
+            importers.push(Importer::new(&project.id, &project.directory, packages));
+        }
+
+        Ok(importers)
+    }
 }
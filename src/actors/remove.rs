@@ -0,0 +1,304 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use async_trait::async_trait;
+
+use crate::actors::install::PipeResult;
+use crate::cache::RegistryKey;
+use crate::contracts::{Actor, Logger};
+use crate::errors::ExecutionError;
+use crate::lockfile::constants::CURRENT_IMPORTER;
+use crate::lockfile::lock_file_actor::LockFileActor;
+use crate::logger::CraftLogger;
+use crate::package::{BinType, NpmPackage, PackageJsonFile};
+use crate::pipeline::NodeLinker;
+
+/// Removes dependencies from package.json, node_modules and the lockfile.
+/// Fails only if none of the names is a dependency of the project.
+pub struct RemoveActor {
+    packages: Vec<String>,
+    directory: PathBuf,
+}
+
+impl RemoveActor {
+    pub fn new(packages: Vec<String>) -> Self {
+        Self::with_directory(packages, env::current_dir().unwrap())
+    }
+
+    pub fn with_directory(packages: Vec<String>, directory: PathBuf) -> Self {
+        Self {
+            packages,
+            directory,
+        }
+    }
+
+    /// The names of the binaries the package linked into `.bin`
+    fn binaries(package_directory: &Path) -> Vec<String> {
+        let package = fs::read_to_string(package_directory.join("package.json"))
+            .ok()
+            .and_then(|c| serde_json::from_str::<NpmPackage>(&c).ok());
+
+        match package.and_then(|p| p.bin) {
+            Some(BinType::Bin(s)) => vec![s.rsplit('/').next().unwrap().replace(".js", "")],
+            Some(BinType::BinMappings(mappings)) => mappings.into_keys().collect(),
+            None => vec![],
+        }
+    }
+
+    fn remove_from_node_modules(&self, name: &str) -> Result<(), ExecutionError> {
+        let node_modules = self.directory.join("node_modules");
+        let package_directory = node_modules.join(name);
+        if !package_directory.exists() {
+            return Ok(());
+        }
+
+        let bin = node_modules.join(".bin");
+        for binary in Self::binaries(&package_directory) {
+            for file in [
+                binary.clone(),
+                format!("{}.CMD", binary),
+                format!("{}.ps1", binary),
+            ] {
+                let _ = fs::remove_file(bin.join(file));
+            }
+        }
+
+        Self::remove_folder(&node_modules, name)
+            .map_err(|e| ExecutionError::JobExecutionFailed(name.to_string(), e.to_string()))
+    }
+
+    fn remove_folder(node_modules: &Path, name: &str) -> std::io::Result<()> {
+        let package_directory = node_modules.join(name);
+        fs::remove_dir_all(&package_directory)?;
+
+        // Drop the scope folder with its last package
+        if let Some(scope) = package_directory.parent().filter(|p| *p != node_modules) {
+            if fs::read_dir(scope).is_ok_and(|mut entries| entries.next().is_none()) {
+                let _ = fs::remove_dir(scope);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Removes the packages nothing depends on anymore. Nested ones went with
+    /// the removed dependencies, hoisted ones sit at the top and isolated
+    /// ones in the virtual store.
+    fn remove_unreachable(&self, unreachable: &[RegistryKey]) {
+        let node_modules = self.directory.join("node_modules");
+
+        for key in unreachable {
+            let store = NodeLinker::store_node_modules(&node_modules, key);
+            if let Some(entry) = store.parent().filter(|e| e.exists()) {
+                CraftLogger::verbose(format!("Removing {} from the store", key));
+                let _ = fs::remove_dir_all(entry);
+            }
+
+            let hoisted = node_modules.join(&key.name);
+            let version = fs::read_to_string(hoisted.join("package.json"))
+                .ok()
+                .and_then(|c| serde_json::from_str::<NpmPackage>(&c).ok())
+                .map(|p| p.version);
+            if !hoisted.is_symlink() && version.as_ref() == Some(&key.version) {
+                CraftLogger::verbose(format!("Removing {}", key));
+                if let Err(e) = Self::remove_folder(&node_modules, &key.name) {
+                    CraftLogger::warn(format!("Failed to remove {}: {}", key, e));
+                }
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Actor<PipeResult> for RemoveActor {
+    async fn start(&mut self) -> PipeResult {
+        let mut package_json = PackageJsonFile::read(&self.directory)?;
+
+        // Like npm, names that aren't dependencies are only warned about
+        let mut removed = vec![];
+        for name in &self.packages {
+            match package_json.remove_dependency(name) {
+                true => removed.push(name.clone()),
+                false => CraftLogger::warn(format!("{} is not a dependency", name)),
+            }
+        }
+        if removed.is_empty() {
+            return Err(ExecutionError::DependencyNotFound(self.packages.join(", ")));
+        }
+        package_json.save()?;
+
+        for name in &removed {
+            CraftLogger::verbose(format!("Removing {}", name));
+            self.remove_from_node_modules(name)?;
+        }
+
+        let unreachable =
+            LockFileActor::remove_dependencies(&self.directory, CURRENT_IMPORTER, &removed)?;
+        self.remove_unreachable(&unreachable);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::fixtures::TempDir;
+
+    fn create_package(directory: &Path, manifest: &str) {
+        fs::create_dir_all(directory).unwrap();
+        fs::write(directory.join("package.json"), manifest).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_remove_package() {
+        let project = TempDir::new("remove");
+        create_package(
+            &project,
+            r#"{"dependencies": {"@scope/tool": "^1.0.0", "lodash": "^4.0.0"}}"#,
+        );
+        create_package(
+            &project.join("node_modules/@scope/tool"),
+            r#"{"name": "@scope/tool", "version": "1.0.0", "bin": {"tool": "cli.js"}}"#,
+        );
+        create_package(
+            &project.join("node_modules/lodash"),
+            r#"{"name": "lodash", "version": "4.0.0"}"#,
+        );
+        fs::create_dir_all(project.join("node_modules/.bin")).unwrap();
+        fs::write(project.join("node_modules/.bin/tool"), "").unwrap();
+
+        RemoveActor::with_directory(vec!["@scope/tool".to_string()], project.to_path_buf())
+            .start()
+            .await
+            .unwrap();
+
+        let manifest = fs::read_to_string(project.join("package.json")).unwrap();
+        assert!(!manifest.contains("@scope/tool"));
+        assert!(manifest.contains("lodash"));
+        assert!(!project.join("node_modules/@scope").exists());
+        assert!(!project.join("node_modules/.bin/tool").exists());
+        assert!(project.join("node_modules/lodash").exists());
+
+        let result = RemoveActor::with_directory(vec!["react".to_string()], project.to_path_buf())
+            .start()
+            .await;
+        assert!(matches!(result, Err(ExecutionError::DependencyNotFound(_))));
+    }
+
+    const LOCKFILE: &str = r#"
+lockfileVersion: '9.0'
+
+importers:
+  .:
+    dependencies:
+      express:
+        specifier: ^4.0.0
+        version: 4.21.0
+      ms:
+        specifier: ^2.0.0
+        version: 2.1.3
+
+packages:
+  express@4.21.0:
+    resolution: {integrity: sha512-express}
+  debug@2.6.9:
+    resolution: {integrity: sha512-debug}
+  ms@2.0.0:
+    resolution: {integrity: sha512-ms-old}
+  ms@2.1.3:
+    resolution: {integrity: sha512-ms}
+
+snapshots:
+  express@4.21.0:
+    dependencies:
+      debug: 2.6.9
+  debug@2.6.9:
+    dependencies:
+      ms: 2.0.0
+  ms@2.0.0: {}
+  ms@2.1.3: {}
+"#;
+
+    fn create_project(name: &str) -> TempDir {
+        let project = TempDir::new(name);
+        create_package(
+            &project,
+            r#"{"dependencies": {"express": "^4.0.0", "ms": "^2.0.0"}}"#,
+        );
+        fs::write(project.join("pnpm-lock.yaml"), LOCKFILE).unwrap();
+        project
+    }
+
+    #[tokio::test]
+    async fn test_remove_prunes_hoisted_packages() {
+        let project = create_project("remove-hoisted");
+        for (name, version) in [("express", "4.21.0"), ("debug", "2.6.9"), ("ms", "2.1.3")] {
+            create_package(
+                &project.join("node_modules").join(name),
+                &format!(r#"{{"name": "{}", "version": "{}"}}"#, name, version),
+            );
+        }
+        create_package(
+            &project.join("node_modules/debug/node_modules/ms"),
+            r#"{"name": "ms", "version": "2.0.0"}"#,
+        );
+
+        RemoveActor::with_directory(vec!["express".to_string()], project.to_path_buf())
+            .start()
+            .await
+            .unwrap();
+
+        assert!(!project.join("node_modules/express").exists());
+        assert!(!project.join("node_modules/debug").exists());
+        // Another version of an unreachable package is kept
+        assert!(project.join("node_modules/ms").exists());
+    }
+
+    #[tokio::test]
+    async fn test_remove_prunes_the_store() {
+        let project = create_project("remove-isolated");
+        let node_modules = project.join("node_modules");
+        for (name, version) in [("express", "4.21.0"), ("debug", "2.6.9"), ("ms", "2.1.3")] {
+            let key = RegistryKey {
+                name: name.to_string(),
+                version: version.to_string(),
+            };
+            create_package(
+                &NodeLinker::store_path(&node_modules, &key),
+                &format!(r#"{{"name": "{}", "version": "{}"}}"#, name, version),
+            );
+        }
+
+        RemoveActor::with_directory(vec!["express".to_string()], project.to_path_buf())
+            .start()
+            .await
+            .unwrap();
+
+        let mut store = fs::read_dir(node_modules.join(".craft"))
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+            .collect::<Vec<_>>();
+        store.sort();
+        assert_eq!(store, vec!["ms@2.1.3"]);
+    }
+
+    #[tokio::test]
+    async fn test_remove_skips_names_that_are_no_dependencies() {
+        let project = create_project("remove-missing");
+
+        RemoveActor::with_directory(
+            vec!["express".to_string(), "react".to_string()],
+            project.to_path_buf(),
+        )
+        .start()
+        .await
+        .unwrap();
+
+        let manifest = fs::read_to_string(project.join("package.json")).unwrap();
+        assert!(!manifest.contains("express"));
+        assert!(manifest.contains("ms"));
+    }
+}
@@ -1,52 +1,171 @@
-use crate::contracts::Actor;
+use crate::actors::child_process::run_child;
+use crate::config::NPMRC;
+use crate::contracts::{Actor, Logger};
+use crate::errors::ExecutionError;
+use crate::logger::CraftLogger;
+use crate::package::PackageJson;
+use crate::pipeline::path_with_bins;
 use async_trait::async_trait;
-use std::env;
+use std::path::PathBuf;
 use std::process::{Command, Stdio};
 
 pub struct RunActor {
     pub script: String,
-    pub cwd: Option<String>,
+    /// The folder of the package whose script runs
+    pub directory: PathBuf,
+    /// Arguments after `--`, appended to the script
+    pub args: Vec<String>,
+    package_json: PackageJson,
 }
 
 impl RunActor {
-    pub fn new(script: String, cwd: Option<String>) -> Self {
-        Self { script, cwd }
+    pub fn new(
+        script: String,
+        directory: PathBuf,
+        args: Vec<String>,
+        package_json: PackageJson,
+    ) -> Self {
+        Self {
+            script,
+            directory,
+            args,
+            package_json,
+        }
+    }
+
+    /// Quotes an argument for the shell if it contains anything special
+    fn quote(arg: &str) -> String {
+        let is_plain = !arg.is_empty()
+            && arg
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "-_./=:,@%+".contains(c));
+
+        if is_plain {
+            arg.to_string()
+        } else if cfg!(target_os = "windows") {
+            format!("\"{}\"", arg.replace('"', "\\\""))
+        } else {
+            format!("'{}'", arg.replace('\'', "'\\''"))
+        }
+    }
+
+    fn command_line(script: &str, args: &[String]) -> String {
+        args.iter().fold(script.to_string(), |line, arg| {
+            format!("{} {}", line, Self::quote(arg))
+        })
+    }
+
+    fn run_script(&self, event: &str, command_line: &str) -> Result<(), ExecutionError> {
+        CraftLogger::info(format!("Running script: {}", event));
+        CraftLogger::info(format!("Command: {}", command_line));
+
+        let mut command = if cfg!(target_os = "windows") {
+            let mut command = Command::new("cmd");
+            command.args(["/C", command_line]);
+            command
+        } else {
+            let mut command = Command::new("sh");
+            command.args(["-c", command_line]);
+            command
+        };
+
+        command
+            .current_dir(&self.directory)
+            .envs(NPMRC.env_vars())
+            .env("PATH", path_with_bins(&self.directory))
+            .env("npm_lifecycle_event", event)
+            .env("npm_lifecycle_script", command_line)
+            .env(
+                "npm_package_name",
+                self.package_json.name.as_deref().unwrap_or_default(),
+            )
+            .env(
+                "npm_package_version",
+                self.package_json.version.as_deref().unwrap_or_default(),
+            )
+            .env("npm_package_json", self.directory.join("package.json"))
+            .env("npm_command", "run-script")
+            .stdout(Stdio::inherit())
+            .stderr(Stdio::inherit());
+
+        run_child(event, &mut command)?;
+
+        Ok(())
     }
 }
 
 #[async_trait]
 impl Actor<crate::actors::install::PipeResult> for RunActor {
     async fn start(&mut self) -> crate::actors::install::PipeResult {
-        let mut exec_path = env::current_dir().expect("Error getting cwd");
+        let scripts = self.package_json.scripts.clone().unwrap_or_default();
+        let script = scripts.get(&self.script).ok_or_else(|| {
+            ExecutionError::ScriptNotFound(format!("Script {} not found", self.script))
+        })?;
 
-        if let Some(c) = self.cwd.clone() {
-            exec_path = exec_path.join(c);
+        // `pre<name>` and `post<name>` run around the script, without the arguments
+        let pre = format!("pre{}", self.script);
+        if let Some(pre_script) = scripts.get(&pre) {
+            self.run_script(&pre, pre_script)?;
         }
 
-        let mut child = if cfg!(target_os = "windows") {
-            Command::new("cmd")
-                .args(["/C", self.script.as_str()])
-                .current_dir(exec_path)
-                .stdout(Stdio::inherit())
-                // creating a pipe to capture the standard error (stderr) of the child process.
-                .stderr(Stdio::inherit())
-                // output() executes the child process synchronously and captures its output.
-                //It returns a std::process::Output struct containing information about the process's exit status, stdout, and stderr.
-                .spawn()
-                .expect("failed to execute process")
-        } else {
-            Command::new("sh")
-                .arg("-c")
-                .stdout(Stdio::inherit())
-                .current_dir(exec_path)
-                // creating a pipe to capture the standard error (stderr) of the child process.
-                .stderr(Stdio::inherit())
-                .arg(self.script.as_str())
-                .spawn()
-                .expect("failed to execute process")
-        };
-        let _ = child.wait().expect("child process wasn't running");
+        let command_line = Self::command_line(script, &self.args);
+        self.run_script(&self.script, &command_line)?;
+
+        let post = format!("post{}", self.script);
+        if let Some(post_script) = scripts.get(&post) {
+            self.run_script(&post, post_script)?;
+        }
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::fixtures::TempDir;
+
+    #[test]
+    fn test_command_line() {
+        let args = vec!["--watch".to_string(), "--name=a b".to_string()];
+
+        if cfg!(target_os = "windows") {
+            assert_eq!(
+                RunActor::command_line("tsc", &args),
+                "tsc --watch \"--name=a b\""
+            );
+        } else {
+            assert_eq!(
+                RunActor::command_line("tsc", &args),
+                "tsc --watch '--name=a b'"
+            );
+            assert_eq!(RunActor::quote("it's"), "'it'\\''s'");
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_script_runs_in_the_package_directory() {
+        let directory = TempDir::new("run");
+        let package_json = serde_json::from_str::<PackageJson>(
+            r#"{"scripts": {"where": "echo \"$PWD $npm_package_json\" > where"}}"#,
+        )
+        .unwrap();
+
+        RunActor::new(
+            "where".to_string(),
+            directory.to_path_buf(),
+            vec![],
+            package_json,
+        )
+        .start()
+        .await
+        .unwrap();
+
+        let directory = directory.canonicalize().unwrap();
+        assert_eq!(
+            std::fs::read_to_string(directory.join("where")).unwrap(),
+            format!("{0} {0}/package.json\n", directory.display())
+        );
+    }
+}
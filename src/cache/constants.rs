@@ -6,6 +6,8 @@ use std::path::PathBuf;
 lazy_static! {
     pub static ref PACKAGES_CACHE_FOLDER: PathBuf = PathBuf::from(".craft/cache/packages");
     pub static ref REGISTRY_CACHE_FOLDER: PathBuf = PathBuf::from(".craft/cache/registry");
+    pub static ref PACKUMENT_CACHE_FOLDER: PathBuf = PathBuf::from(".craft/cache/packuments");
+    pub static ref GIT_CACHE_FOLDER: PathBuf = PathBuf::from(".craft/cache/git");
     pub static ref DEP_CACHE_FOLDER: PathBuf = PathBuf::from(".craft/cache/node_modules");
 }
 
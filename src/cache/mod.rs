@@ -1,8 +1,11 @@
 mod constants;
 mod packages;
+mod packuments;
 mod registry;
 
 pub use constants::DEP_CACHE_FOLDER;
+pub use constants::GIT_CACHE_FOLDER;
 pub use packages::PackagesCache;
+pub use packuments::{CachedPackument, PackumentCache};
 pub use registry::RegistryCache;
 pub use registry::RegistryKey;
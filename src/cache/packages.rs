@@ -46,6 +46,10 @@ impl PackagesCache {
                     .file_name()
                     .into_string()
                     .map_err(|_| CacheError::CacheError)?;
+                // Downloads that were interrupted before they were verified
+                if filename.ends_with(".part") {
+                    continue;
+                }
                 let reg_key = convert_to_registry_key(&filename);
 
                 cache.insert(reg_key);
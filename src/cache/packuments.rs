@@ -0,0 +1,116 @@
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+use super::constants::PACKUMENT_CACHE_FOLDER;
+use crate::errors::CacheError;
+use crate::fs::get_config_dir;
+use crate::package::FullPackage;
+
+// ─── PackumentCache ──────────────────────────────────────────────────────────
+
+/// The packuments of the registries with what is needed to revalidate them,
+/// one file per packument url
+#[derive(Debug, Clone)]
+pub struct PackumentCache {
+    directory: PathBuf,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedPackument {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    /// When the registry last confirmed the packument, in seconds since the epoch
+    pub fetched_at: i64,
+    pub packument: FullPackage,
+}
+
+// ─────────────────────────────────────────────────────────────────────────────
+
+static WRITES: AtomicUsize = AtomicUsize::new(0);
+
+impl Default for PackumentCache {
+    fn default() -> Self {
+        Self::new(get_config_dir(PACKUMENT_CACHE_FOLDER.clone()))
+    }
+}
+
+impl PackumentCache {
+    pub fn new(directory: PathBuf) -> Self {
+        Self { directory }
+    }
+
+    /// `https://registry.npmjs.org/@types%2fnode` is stored at
+    /// `registry.npmjs.org/@types%2fnode.json`
+    fn path(&self, url: &str) -> PathBuf {
+        let location = url
+            .split_once("://")
+            .map_or(url, |(_, location)| location)
+            .trim_end_matches('/')
+            .replace([':', '?', '*', '"', '<', '>', '|', '\\'], "+");
+
+        let mut path = self.directory.clone();
+        // Names like `lodash.merge` have dots, so no `set_extension`
+        path.extend(
+            format!("{}.json", location)
+                .split('/')
+                .filter(|p| !p.is_empty()),
+        );
+        path
+    }
+
+    pub async fn get(&self, url: &str) -> Option<CachedPackument> {
+        let content = tokio::fs::read(self.path(url)).await.ok()?;
+        serde_json::from_slice(&content).ok()
+    }
+
+    /// Writes to a temporary file first, so concurrent installs never read a
+    /// half written packument
+    pub async fn set(&self, url: &str, packument: &CachedPackument) -> Result<(), CacheError> {
+        let path = self.path(url);
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+
+        let tmp = path.with_extension(format!(
+            "{}-{}.tmp",
+            std::process::id(),
+            WRITES.fetch_add(1, Ordering::Relaxed)
+        ));
+        let content = serde_json::to_vec(packument).map_err(|_| CacheError::CacheError)?;
+        tokio::fs::write(&tmp, content).await?;
+        tokio::fs::rename(&tmp, &path).await?;
+
+        Ok(())
+    }
+
+    pub async fn clean(&self) -> Result<(), CacheError> {
+        if self.directory.exists() {
+            tokio::fs::remove_dir_all(&self.directory).await?;
+        }
+
+        Ok(())
+    }
+}
+
+impl CachedPackument {
+    pub fn new(
+        packument: FullPackage,
+        etag: Option<String>,
+        last_modified: Option<String>,
+    ) -> Self {
+        Self {
+            etag,
+            last_modified,
+            fetched_at: Utc::now().timestamp(),
+            packument,
+        }
+    }
+
+    /// Whether it was confirmed by the registry less than `max_age` seconds ago
+    pub fn is_fresh(&self, max_age: i64) -> bool {
+        Utc::now().timestamp() - self.fetched_at < max_age
+    }
+}
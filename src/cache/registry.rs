@@ -221,8 +221,8 @@ impl PersistentCache<NpmPackage> for RegistryCache {
 
         log::info!("Getting key: {}", key);
 
-        // We have a range
-        let range: Range = key.version.parse().unwrap();
+        // We have a range, dist-tags are only known to the registry
+        let range: Range = key.version.parse().ok()?;
         let mut selected_version: Option<NpmPackage> = None;
         for (_, v) in self.cache.get(&key.name)?.iter() {
             let v_package: Version = v.version.parse().unwrap();
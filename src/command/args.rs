@@ -36,6 +36,15 @@ impl From<Install> for ProgramDesire {
             optional_install: true,
             package_json_available: false,
             pnpm_lock_yaml_available: false,
+            frozen_lockfile: val.frozen_lockfile,
+            ignore_scripts: val.ignore_scripts,
+            save_exact: val.save_exact,
+            os: val.os,
+            cpu: val.cpu,
+            no_peers: val.no_peers,
+            engine_strict: val.engine_strict,
+            offline: val.offline,
+            prefer_offline: val.prefer_offline,
         };
 
         // This needs to be done before all the other checks
@@ -81,12 +90,31 @@ impl From<Install> for ProgramDesire {
 /// -> optional_install => optional, dev, prod
 /// -> global_install => global
 ///
+#[derive(Debug, Clone)]
 pub struct ProgramDesire {
     pub dev_install: bool,
     pub prod_install: bool,
     pub optional_install: bool,
     pub package_json_available: bool,
     pub pnpm_lock_yaml_available: bool,
+    /// Install exactly what pnpm-lock.yaml describes without touching the registry
+    pub frozen_lockfile: bool,
+    /// Don't run the lifecycle scripts of the dependencies and the project
+    pub ignore_scripts: bool,
+    /// Save the exact version instead of a range to package.json
+    pub save_exact: bool,
+    /// Install optional dependencies for this os instead of the current one
+    pub os: Option<String>,
+    /// Install optional dependencies for this cpu instead of the current one
+    pub cpu: Option<String>,
+    /// Don't install missing peer dependencies
+    pub no_peers: bool,
+    /// Fail instead of warning if a package doesn't support the node version
+    pub engine_strict: bool,
+    /// Only install from the caches
+    pub offline: bool,
+    /// Use the caches however old they are, only fetch what is missing
+    pub prefer_offline: bool,
 }
 
 /// Sub commands
@@ -104,6 +132,8 @@ pub struct ProgramDesire {
 pub enum SubCommand {
     #[clap(name = "install", alias = "add")]
     Install(Install),
+    #[clap(name = "remove", aliases = ["rm", "uninstall"])]
+    Remove(Remove),
     #[clap(name = "run")]
     Run(Run),
     #[clap(name = "cache")]
@@ -111,6 +141,9 @@ pub enum SubCommand {
     Cache(CacheAction),
     #[clap(name = "exec")]
     Exec(Exec),
+    /// Installs with as few versions of every package as possible
+    #[clap(name = "dedupe")]
+    Dedupe,
 }
 
 /// Install sub command
@@ -131,7 +164,7 @@ pub enum SubCommand {
 ///  SubCommand::Install(install) => install,
 /// _ => panic!("Invalid command")
 /// };
-#[derive(Debug, Parser, Clone)]
+#[derive(Debug, Parser, Clone, Default)]
 pub struct Install {
     #[arg(name = "global", long, short, alias = "g")]
     pub save_global: bool,
@@ -139,16 +172,34 @@ pub struct Install {
     #[arg(long)]
     pub save_dev: bool,
 
-    #[arg(long)]
+    /// Only install from the caches, without the registry
+    #[arg(long, conflicts_with = "prefer_offline")]
     pub offline: bool,
+    /// Use cached metadata however old it is, only fetch what is missing
+    #[arg(long)]
+    pub prefer_offline: bool,
     #[arg(long)]
     pub prod: bool,
     #[arg(long)]
     pub dev: bool,
     #[arg(long)]
     pub no_optional: bool,
+    /// Don't install missing peer dependencies
     #[arg(long)]
     pub no_peers: bool,
+    /// Install from pnpm-lock.yaml and fail if it is out of date
+    #[arg(long)]
+    pub frozen_lockfile: bool,
+    /// Don't run lifecycle scripts like postinstall
+    ///
+    /// Otherwise every dependency runs its scripts, unless
+    /// `pnpm.onlyBuiltDependencies` or `pnpm.neverBuiltDependencies` of
+    /// package.json limit which ones may
+    #[arg(long)]
+    pub ignore_scripts: bool,
+    /// Fail if a package doesn't support the installed node version
+    #[arg(long)]
+    pub engine_strict: bool,
     /// Save as production dependency
     #[arg(long)]
     pub save_prod: bool,
@@ -157,17 +208,40 @@ pub struct Install {
     #[arg(long)]
     pub save_optional: bool,
 
+    /// Save the exact version instead of a range
+    #[arg(long, short = 'E')]
+    pub save_exact: bool,
+
+    /// Install for another os, e.g. darwin
+    #[arg(long)]
+    pub os: Option<String>,
+
+    /// Install for another cpu, e.g. arm64
+    #[arg(long)]
+    pub cpu: Option<String>,
+
     /// List of packages to install
     #[arg(required = false)]
     pub packages: Option<Vec<String>>,
 }
 
+/// Remove sub command
+#[derive(Debug, Parser, Clone)]
+pub struct Remove {
+    /// List of packages to remove, names that are no dependencies are skipped
+    #[arg(required = true)]
+    pub packages: Vec<String>,
+}
+
 #[derive(Debug, Parser, Clone)]
 pub struct Run {
     #[clap(name = "dir", alias = "C", required = false, index = 2)]
     pub directory: Option<String>,
     #[arg(required = true, name = "--script", index = 1)]
     pub script: String,
+    /// Arguments after `--` are passed to the script
+    #[arg(last = true, index = 3)]
+    pub args: Vec<String>,
 }
 
 #[derive(clap::Args, Debug, Clone)]
@@ -188,3 +262,28 @@ pub enum CacheAction {
     #[clap(name = "clean")]
     Clean,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn test_command_definition() {
+        Command::command().debug_assert();
+    }
+
+    #[test]
+    fn test_run_forwards_args() {
+        let command = Command::parse_from(["craft", "run", "build", "--", "--watch", "a b"]);
+
+        match command.command {
+            SubCommand::Run(run) => {
+                assert_eq!(run.script, "build");
+                assert_eq!(run.directory, None);
+                assert_eq!(run.args, vec!["--watch", "a b"]);
+            }
+            other => panic!("Unexpected command {:?}", other),
+        }
+    }
+}
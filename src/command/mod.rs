@@ -1,4 +1,4 @@
 mod args;
 
 pub use args::ProgramDesire;
-pub use args::{CacheAction, Command, Install, SubCommand};
+pub use args::{CacheAction, Command, Install, Remove, SubCommand};
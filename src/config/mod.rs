@@ -0,0 +1,5 @@
+mod npmrc;
+
+pub use npmrc::Npmrc;
+pub use npmrc::NPMRC;
+pub use npmrc::NPM_REGISTRY_URL;
@@ -0,0 +1,501 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use homedir::my_home;
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+pub const NPM_REGISTRY_URL: &str = "https://registry.npmjs.org";
+
+const NPMRC_FILE: &str = ".npmrc";
+const REGISTRY: &str = "registry";
+const REPLACE_REGISTRY_HOST: &str = "replace-registry-host";
+const ALWAYS_AUTH: &str = "always-auth";
+const AUTH_TOKEN: &str = "_authToken";
+const AUTH: &str = "_auth";
+const USERNAME: &str = "username";
+const PASSWORD: &str = "_password";
+/// Settings that hold credentials, also when they belong to a registry like
+/// `//host/:_authToken`
+const CREDENTIALS: [&str; 9] = [
+    AUTH_TOKEN, AUTH, USERNAME, PASSWORD, "password", "email", "otp", "key", "certfile",
+];
+
+lazy_static! {
+    pub static ref NPMRC: Npmrc = Npmrc::load();
+}
+
+// ─── Npmrc ───────────────────────────────────────────────────────────────────
+
+/// Settings of the user and project `.npmrc` files. Values of the project
+/// file take precedence over the ones of the user.
+#[derive(Debug, Clone, Default)]
+pub struct Npmrc {
+    values: HashMap<String, String>,
+}
+
+// ─────────────────────────────────────────────────────────────────────────────
+
+impl Npmrc {
+    pub fn parse(content: &str) -> Self {
+        Self::parse_with_env(content, |name| std::env::var(name).ok())
+    }
+
+    /// Parses the file and replaces `${VAR}` in values with the result of
+    /// `env`. Unknown variables are replaced by an empty string.
+    pub fn parse_with_env<F: Fn(&str) -> Option<String>>(content: &str, env: F) -> Self {
+        let mut values = HashMap::new();
+
+        for line in content.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+
+            if let Some((key, value)) = line.split_once('=') {
+                let value = value.trim();
+                let value = value
+                    .strip_prefix('"')
+                    .and_then(|v| v.strip_suffix('"'))
+                    .unwrap_or(value);
+                values.insert(key.trim().to_string(), Self::interpolate(value, &env));
+            }
+        }
+
+        Self { values }
+    }
+
+    fn interpolate<F: Fn(&str) -> Option<String>>(value: &str, env: &F) -> String {
+        let mut result = String::new();
+        let mut rest = value;
+
+        while let Some(start) = rest.find("${") {
+            let Some(end) = rest[start..].find('}') else {
+                break;
+            };
+            result.push_str(&rest[..start]);
+            result.push_str(&env(&rest[start + 2..start + end]).unwrap_or_default());
+            rest = &rest[start + end + 1..];
+        }
+        result.push_str(rest);
+
+        result
+    }
+
+    fn read_file(path: &Path) -> Self {
+        fs::read_to_string(path)
+            .map(|c| Self::parse(&c))
+            .unwrap_or_default()
+    }
+
+    pub fn merge(&mut self, other: Npmrc) {
+        self.values.extend(other.values);
+    }
+
+    pub fn load() -> Self {
+        let mut npmrc = Npmrc::default();
+
+        if let Ok(Some(home)) = my_home() {
+            npmrc.merge(Self::read_file(&home.join(NPMRC_FILE)));
+        }
+        npmrc.merge(Self::read_file(Path::new(NPMRC_FILE)));
+
+        npmrc
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.values.get(key).map(|v| v.as_str())
+    }
+
+    /// The default registry, without a trailing slash
+    pub fn registry(&self) -> String {
+        self.get(REGISTRY)
+            .unwrap_or(NPM_REGISTRY_URL)
+            .trim_end_matches('/')
+            .to_string()
+    }
+
+    /// The registry of a package, scoped packages can have their own registry
+    /// configured with `@scope:registry=`
+    pub fn registry_for(&self, package_name: &str) -> String {
+        if let Some((scope, _)) = package_name.split_once('/') {
+            if let Some(registry) = self.get(&format!("{}:{}", scope, REGISTRY)) {
+                return registry.trim_end_matches('/').to_string();
+            }
+        }
+
+        self.registry()
+    }
+
+    /// Packuments of mirrors often still point to registry.npmjs.org, so
+    /// those tarballs are fetched from the configured registry instead.
+    pub fn tarball_url_for(&self, package_name: &str, tarball: &str) -> String {
+        if self.get(REPLACE_REGISTRY_HOST) == Some("never") {
+            return tarball.to_string();
+        }
+
+        match tarball.strip_prefix(NPM_REGISTRY_URL) {
+            Some(path) => format!("{}{}", self.registry_for(package_name), path),
+            None => tarball.to_string(),
+        }
+    }
+
+    /// The `Authorization` header for a request to `url`. Credentials of the
+    /// form `//host/path/:_authToken` are used for every url below that path,
+    /// the most specific one wins. Credentials without a host only belong to
+    /// the default registry unless `always-auth` is set.
+    pub fn auth_header_for(&self, url: &str) -> Option<String> {
+        let target = Self::nerf_dart(url);
+
+        // Both sides end with a slash, so `//host` doesn't match `//host.evil`
+        let mut scopes = self
+            .values
+            .keys()
+            .filter_map(|key| key.rsplit_once(':').map(|(scope, _)| scope))
+            .filter(|scope| scope.starts_with("//") && target.starts_with(&Self::nerf_dart(scope)))
+            .collect::<Vec<_>>();
+        scopes.sort_by_key(|scope| std::cmp::Reverse(Self::nerf_dart(scope).len()));
+        scopes.dedup();
+
+        for scope in scopes {
+            if let Some(header) = self.credentials(&format!("{}:", scope)) {
+                return Some(header);
+            }
+        }
+
+        let is_default_registry = target.starts_with(&Self::nerf_dart(&self.registry()));
+        if is_default_registry || self.get(ALWAYS_AUTH) == Some("true") {
+            return self.credentials("");
+        }
+
+        None
+    }
+
+    /// The settings as `npm_config_*` variables for scripts, like npm passes
+    /// them. Credentials are left out.
+    pub fn env_vars(&self) -> Vec<(String, String)> {
+        let is_credential = |key: &str| {
+            let setting = key.rsplit(':').next().unwrap_or(key);
+            CREDENTIALS.iter().any(|c| c.eq_ignore_ascii_case(setting))
+        };
+        let mut vars = self
+            .values
+            .iter()
+            .filter(|(key, _)| !key.starts_with("//") && !key.starts_with('_'))
+            .filter(|(key, _)| !is_credential(key))
+            .map(|(key, value)| {
+                let name = key.to_lowercase().replace(['-', ':', '@', '/'], "_");
+                (
+                    format!("npm_config_{}", name.trim_start_matches('_')),
+                    value.clone(),
+                )
+            })
+            .collect::<Vec<_>>();
+        if self.get(REGISTRY).is_none() {
+            vars.push((
+                "npm_config_registry".to_string(),
+                format!("{}/", self.registry()),
+            ));
+        }
+        vars.sort();
+
+        vars
+    }
+
+    /// All configured tokens and passwords, so they can be kept out of logs
+    pub fn secrets(&self) -> Vec<String> {
+        let mut secrets = self
+            .values
+            .iter()
+            .filter(|(key, _)| {
+                key.ends_with(AUTH_TOKEN) || key.ends_with(AUTH) || key.ends_with(PASSWORD)
+            })
+            .filter(|(_, value)| !value.is_empty())
+            .flat_map(|(key, value)| {
+                let mut secrets = vec![value.clone()];
+                if !key.ends_with(AUTH_TOKEN) {
+                    if let Some(decoded) = STANDARD
+                        .decode(value)
+                        .ok()
+                        .and_then(|d| String::from_utf8(d).ok())
+                    {
+                        secrets.push(decoded);
+                    }
+                }
+                secrets
+            })
+            .collect::<Vec<_>>();
+        secrets.sort();
+        secrets.dedup();
+
+        secrets
+    }
+
+    fn credentials(&self, prefix: &str) -> Option<String> {
+        let value = |key: &str| {
+            self.get(&format!("{}{}", prefix, key))
+                .filter(|v| !v.is_empty())
+        };
+
+        if let Some(token) = value(AUTH_TOKEN) {
+            return Some(format!("Bearer {}", token));
+        }
+
+        if let Some(auth) = value(AUTH) {
+            return Some(format!("Basic {}", auth));
+        }
+
+        if let (Some(username), Some(password)) = (value(USERNAME), value(PASSWORD)) {
+            let password = STANDARD
+                .decode(password)
+                .ok()
+                .and_then(|p| String::from_utf8(p).ok())?;
+            let auth = STANDARD.encode(format!("{}:{}", username, password));
+            return Some(format!("Basic {}", auth));
+        }
+
+        None
+    }
+
+    /// Strips the protocol, query and fragment, so `https://Host:8080/path?q`
+    /// becomes `//host:8080/path/`
+    fn nerf_dart(url: &str) -> String {
+        let without_protocol = match url.split_once("://") {
+            Some((_, rest)) => rest,
+            None => url.trim_start_matches('/'),
+        };
+        let without_query = without_protocol
+            .split(['?', '#'])
+            .next()
+            .unwrap_or_default();
+        let (host, path) = match without_query.split_once('/') {
+            Some((host, path)) => (host, path),
+            None => (without_query, ""),
+        };
+        let nerfed = format!("//{}/{}", host.to_lowercase(), path);
+
+        if nerfed.ends_with('/') {
+            nerfed
+        } else {
+            format!("{}/", nerfed)
+        }
+    }
+}
+
+// ─── Tests ───────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let npmrc = Npmrc::parse(
+            r#"
+            # comment
+            ; another comment
+            registry = "https://mirror.company.com/npm/"
+            @company:registry=https://npm.company.com
+            "#,
+        );
+
+        assert_eq!(
+            npmrc.get("registry"),
+            Some("https://mirror.company.com/npm/")
+        );
+        assert_eq!(npmrc.registry(), "https://mirror.company.com/npm");
+        assert_eq!(npmrc.get("# comment"), None);
+    }
+
+    #[test]
+    fn test_env_vars() {
+        let npmrc = Npmrc::parse(
+            "save-exact=true\n@company:registry=https://npm.company.com/\n//npm.company.com/:_authToken=secret\n_auth=c2VjcmV0\nusername=user\npassword=pass\nemail=me@example.com\n@company:_authToken=secret\nkey=pem",
+        );
+
+        assert_eq!(
+            npmrc.env_vars(),
+            vec![
+                (
+                    "npm_config_company_registry".to_string(),
+                    "https://npm.company.com/".to_string()
+                ),
+                (
+                    "npm_config_registry".to_string(),
+                    format!("{}/", NPM_REGISTRY_URL)
+                ),
+                ("npm_config_save_exact".to_string(), "true".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_registry_for() {
+        let npmrc = Npmrc::parse("@company:registry=https://npm.company.com/");
+
+        assert_eq!(
+            npmrc.registry_for("@company/utils"),
+            "https://npm.company.com"
+        );
+        assert_eq!(npmrc.registry_for("@babel/core"), NPM_REGISTRY_URL);
+        assert_eq!(npmrc.registry_for("lodash"), NPM_REGISTRY_URL);
+    }
+
+    #[test]
+    fn test_merge_prefers_later_files() {
+        let mut npmrc = Npmrc::parse("registry=https://user.example.com\nfoo=bar");
+        npmrc.merge(Npmrc::parse("registry=https://project.example.com"));
+
+        assert_eq!(npmrc.registry(), "https://project.example.com");
+        assert_eq!(npmrc.get("foo"), Some("bar"));
+    }
+
+    #[test]
+    fn test_tarball_url_for() {
+        let npmrc = Npmrc::parse(
+            "registry=https://mirror.example.com/\n@company:registry=https://npm.company.com",
+        );
+
+        assert_eq!(
+            npmrc.tarball_url_for(
+                "lodash",
+                "https://registry.npmjs.org/lodash/-/lodash-4.17.21.tgz"
+            ),
+            "https://mirror.example.com/lodash/-/lodash-4.17.21.tgz"
+        );
+        assert_eq!(
+            npmrc.tarball_url_for(
+                "@company/utils",
+                "https://npm.company.com/@company/utils/-/utils-1.0.0.tgz"
+            ),
+            "https://npm.company.com/@company/utils/-/utils-1.0.0.tgz"
+        );
+
+        let npmrc =
+            Npmrc::parse("registry=https://mirror.example.com/\nreplace-registry-host=never");
+        assert_eq!(
+            npmrc.tarball_url_for(
+                "lodash",
+                "https://registry.npmjs.org/lodash/-/lodash-4.17.21.tgz"
+            ),
+            "https://registry.npmjs.org/lodash/-/lodash-4.17.21.tgz"
+        );
+    }
+
+    #[test]
+    fn test_env_interpolation() {
+        let npmrc = Npmrc::parse_with_env(
+            "//npm.company.com/:_authToken=${NPM_TOKEN}\nother=${MISSING}-x",
+            |name| (name == "NPM_TOKEN").then(|| "s3cr3t".to_string()),
+        );
+
+        assert_eq!(npmrc.get("//npm.company.com/:_authToken"), Some("s3cr3t"));
+        assert_eq!(npmrc.get("other"), Some("-x"));
+    }
+
+    #[test]
+    fn test_auth_header_for() {
+        let npmrc = Npmrc::parse(
+            r#"
+            @company:registry=https://npm.company.com/private/
+            //npm.company.com/:_authToken=host-token
+            //npm.company.com/private/:_authToken=private-token
+            //basic.example.com/:_auth=dXNlcjpwYXNz
+            //login.example.com/:username=user
+            //login.example.com/:_password=cGFzcw==
+            "#,
+        );
+
+        assert_eq!(
+            npmrc.auth_header_for("https://npm.company.com/private/@company%2futils"),
+            Some("Bearer private-token".to_string())
+        );
+        assert_eq!(
+            npmrc.auth_header_for("https://npm.company.com/other/utils-1.0.0.tgz"),
+            Some("Bearer host-token".to_string())
+        );
+        assert_eq!(
+            npmrc.auth_header_for("https://basic.example.com/lodash"),
+            Some("Basic dXNlcjpwYXNz".to_string())
+        );
+        assert_eq!(
+            npmrc.auth_header_for("https://login.example.com/lodash"),
+            Some("Basic dXNlcjpwYXNz".to_string())
+        );
+        assert_eq!(
+            npmrc.auth_header_for("https://registry.npmjs.org/lodash"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_auth_header_for_lookalike_hosts() {
+        let npmrc = Npmrc::parse(
+            r#"
+            //npm.company.com:_authToken=host-token
+            //npm.company.com:8443/private:_authToken=port-token
+            "#,
+        );
+
+        assert_eq!(
+            npmrc.auth_header_for("https://NPM.company.com/lodash"),
+            Some("Bearer host-token".to_string())
+        );
+        assert_eq!(
+            npmrc.auth_header_for("https://npm.company.com.evil.com/lodash"),
+            None
+        );
+        assert_eq!(
+            npmrc.auth_header_for("https://npm.company.com:8443/private/lodash"),
+            Some("Bearer port-token".to_string())
+        );
+        assert_eq!(
+            npmrc.auth_header_for("https://npm.company.com:8443/private-evil/lodash"),
+            None
+        );
+        assert_eq!(
+            npmrc.auth_header_for("https://npm.company.com:84430/lodash"),
+            None
+        );
+        assert_eq!(
+            npmrc.auth_header_for("https://evil.com/?//npm.company.com/"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_global_auth_needs_always_auth_for_other_hosts() {
+        let npmrc = Npmrc::parse("registry=https://mirror.example.com\n_auth=dXNlcjpwYXNz");
+
+        assert_eq!(
+            npmrc.auth_header_for("https://mirror.example.com/lodash"),
+            Some("Basic dXNlcjpwYXNz".to_string())
+        );
+        assert_eq!(
+            npmrc.auth_header_for("https://cdn.example.com/lodash.tgz"),
+            None
+        );
+
+        let npmrc = Npmrc::parse(
+            "registry=https://mirror.example.com\n_auth=dXNlcjpwYXNz\nalways-auth=true",
+        );
+        assert_eq!(
+            npmrc.auth_header_for("https://cdn.example.com/lodash.tgz"),
+            Some("Basic dXNlcjpwYXNz".to_string())
+        );
+    }
+
+    #[test]
+    fn test_secrets() {
+        let npmrc = Npmrc::parse("//npm.company.com/:_authToken=tok\n_auth=dXNlcjpwYXNz\nfoo=bar");
+
+        assert_eq!(
+            npmrc.secrets(),
+            vec![
+                "dXNlcjpwYXNz".to_string(),
+                "tok".to_string(),
+                "user:pass".to_string()
+            ]
+        );
+    }
+}
@@ -1,5 +1,9 @@
+use std::process::ExitStatus;
+
 use thiserror::Error;
 
+use super::{LockfileError, NetworkError};
+
 #[derive(Debug, Error)]
 pub enum ExecutionError {
     #[error("Failed to execute job {0}: Reason: {1}")]
@@ -10,4 +14,43 @@ pub enum ExecutionError {
     ScriptNotFound(String),
     #[error("Failed to find a script in package.json")]
     NoScriptsFound,
+    #[error("Script {1} of {0} failed:\n{2}")]
+    LifecycleScriptFailed(String, String, String),
+    #[error("Cannot remove {0}: no such dependency found")]
+    DependencyNotFound(String),
+    #[error("Unsupported engine:\n{0}")]
+    UnsupportedEngine(String),
+    #[error("Command {0} failed: {1}")]
+    ChildProcessFailed(String, ExitStatus),
+    #[error("{0}")]
+    Lockfile(#[from] LockfileError),
+    #[error("{0}")]
+    Network(#[from] NetworkError),
+}
+
+impl ExecutionError {
+    /// The exit code craft should end with, the one of the child if it failed
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            ExecutionError::ChildProcessFailed(_, status) => exit_code_of(status),
+            _ => 1,
+        }
+    }
+}
+
+/// Like a shell, a child killed by a signal results in 128 + the signal
+fn exit_code_of(status: &ExitStatus) -> i32 {
+    if let Some(code) = status.code() {
+        return code;
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return 128 + signal;
+        }
+    }
+
+    1
 }
@@ -8,4 +8,6 @@ pub enum LockfileError {
     FileWriteError(String),
     #[error("Error file contains invalid structure {0}")]
     InvalidStructure(String),
+    #[error("Lockfile is not up to date with package.json: {0}")]
+    OutdatedLockfile(String),
 }
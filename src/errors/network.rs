@@ -1,3 +1,5 @@
+use std::time::Duration;
+
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -12,4 +14,41 @@ pub enum NetworkError {
     FailedToFetchVersion(String),
     #[error("Checksum mismatch while downloading {0}")]
     CheckSum(String),
+    #[error("Git failed for {0}: {1}")]
+    Git(String, String),
+    #[error("Offline, but missing from the cache:\n  {}", .0.join("\n  "))]
+    Offline(Vec<String>),
+    #[error("Not found: {0}")]
+    NotFound(String),
+    #[error("Not authorized ({1}) for {0}, check the auth token of the registry")]
+    Unauthorized(String, u16),
+    #[error("Too many requests to {0}")]
+    RateLimited(String, Option<Duration>),
+    #[error("Server error ({1}) for {0}")]
+    ServerError(String, u16),
+    #[error("Unexpected status {1} for {0}")]
+    UnexpectedStatus(String, u16),
+    #[error("Timed out fetching {0}")]
+    Timeout(String),
+}
+
+impl NetworkError {
+    /// Whether trying again may help, a flaky network or a busy registry
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            NetworkError::RateLimited(..)
+            | NetworkError::ServerError(..)
+            | NetworkError::Timeout(_) => true,
+            NetworkError::FetchFailure(e) => e.is_connect() || e.is_request() || e.is_body(),
+            _ => false,
+        }
+    }
+
+    /// How long the registry asked to wait with `Retry-After`
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            NetworkError::RateLimited(_, retry_after) => *retry_after,
+            _ => None,
+        }
+    }
 }
@@ -1,37 +1,69 @@
-use fs_extra::dir::CopyOptions;
 use std::fs;
+use std::io;
 use std::path::Path;
 
-pub fn copy_dir(from: &Path, to: &Path) -> Result<(), Box<dyn std::error::Error>> {
-    let mut from = from.to_path_buf();
-    if to.exists() {
-        fs::remove_dir_all(to).unwrap();
-    }
+use crate::config::NPMRC;
 
-    if !from.exists() {
-        from = from.join("..");
-    }
+// ─── ImportMethod ────────────────────────────────────────────────────────────
 
-    fs::create_dir_all(to).unwrap();
+/// How the files of the store get into node_modules, `package-import-method`
+/// of .npmrc. Every method falls back to copying a file it can't import, e.g.
+/// hard links across file systems.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ImportMethod {
+    /// Clones if the file system supports it, hard links otherwise
+    #[default]
+    Auto,
+    Hardlink,
+    /// Copy on write, e.g. reflinks on btrfs, XFS or APFS
+    Clone,
+    Copy,
+}
 
-    let options = CopyOptions::new().overwrite(true);
+// ─────────────────────────────────────────────────────────────────────────────
 
-    copy_recursive(from.as_path(), to, &options)
-}
+impl ImportMethod {
+    pub fn current() -> Self {
+        match NPMRC.get("package-import-method") {
+            Some("hardlink") => ImportMethod::Hardlink,
+            Some("clone") | Some("clone-or-copy") => ImportMethod::Clone,
+            Some("copy") => ImportMethod::Copy,
+            _ => ImportMethod::Auto,
+        }
+    }
 
-fn copy_recursive(
-    from: &Path,
-    to: &Path,
-    options: &CopyOptions,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let from_meta = fs::metadata(from)?;
+    fn import_file(&self, from: &Path, to: &Path) -> io::Result<()> {
+        let imported = match self {
+            ImportMethod::Auto => {
+                reflink_copy::reflink(from, to).or_else(|_| fs::hard_link(from, to))
+            }
+            ImportMethod::Hardlink => fs::hard_link(from, to),
+            ImportMethod::Clone => reflink_copy::reflink(from, to),
+            ImportMethod::Copy => Err(io::ErrorKind::Unsupported.into()),
+        };
 
-    if options.overwrite && to.exists() {
-        fs::remove_dir_all(to)?;
+        if imported.is_err() {
+            fs::copy(from, to)?;
+        }
+        Ok(())
     }
+}
+
+/// Recreates the directory `from` at `to` file by file, so the package has
+/// its real location at `to` and writing to it leaves the store alone.
+/// Files that already exist are kept.
+pub fn import_dir(from: &Path, to: &Path, method: ImportMethod) -> io::Result<()> {
+    fs::create_dir_all(to)?;
 
-    if from_meta.is_dir() {
-        symlink_dir(from, to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+
+        if entry.file_type()?.is_dir() {
+            import_dir(&entry.path(), &target, method)?;
+        } else if !target.exists() {
+            method.import_file(&entry.path(), &target)?;
+        }
     }
 
     Ok(())
@@ -48,3 +80,44 @@ pub fn symlink_dir<P: AsRef<Path>, U: AsRef<Path>>(from: P, to: U) -> std::io::R
     junction::create(from, to)?;
     Ok(())
 }
+
+// ─── Tests ───────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::fixtures::TempDir;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_import_dir() {
+        use std::os::unix::fs::MetadataExt;
+
+        let dir = TempDir::new("import");
+        let store = dir.join("store");
+        fs::create_dir_all(store.join("lib")).unwrap();
+        fs::write(store.join("package.json"), "{}").unwrap();
+        fs::write(store.join("lib/index.js"), "module.exports = 1").unwrap();
+
+        let inode = |path: &Path| fs::metadata(path).unwrap().ino();
+
+        import_dir(&store, &dir.join("hardlink"), ImportMethod::Hardlink).unwrap();
+        let linked = dir.join("hardlink/lib/index.js");
+        assert!(!fs::symlink_metadata(dir.join("hardlink/lib"))
+            .unwrap()
+            .is_symlink());
+        assert_eq!(inode(&linked), inode(&store.join("lib/index.js")));
+
+        import_dir(&store, &dir.join("copy"), ImportMethod::Copy).unwrap();
+        let copied = dir.join("copy/lib/index.js");
+        assert_ne!(inode(&copied), inode(&store.join("lib/index.js")));
+        fs::write(&copied, "module.exports = 2").unwrap();
+        assert_eq!(
+            fs::read_to_string(store.join("lib/index.js")).unwrap(),
+            "module.exports = 1"
+        );
+
+        import_dir(&store, &dir.join("auto"), ImportMethod::Auto).unwrap();
+        assert!(dir.join("auto/package.json").is_file());
+    }
+}
@@ -1,5 +1,6 @@
 mod actors;
 mod cache;
+mod config;
 mod contracts;
 mod errors;
 mod fs;
@@ -10,6 +11,7 @@ mod perf;
 mod registry;
 mod tar;
 mod ui;
+mod workspace;
 
 mod pipeline;
 
@@ -5,6 +5,8 @@ pub const SETTINGS: &str = "settings";
 pub const AUTO_INSTALL_PEERS: &str = "autoInstallPeers";
 pub const EXCLUDE_LINKS_FROM_LOCKFILE: &str = "excludeLinksFromLockfile";
 pub const PEER_SUFFIX_MAX_LENGTH: &str = "peerSuffixMaxLength";
+pub const OVERRIDES: &str = "overrides";
+pub const IGNORED_OPTIONAL_DEPENDENCIES: &str = "ignoredOptionalDependencies";
 
 // Importers dependencies
 pub const SPECIFIER: &str = "specifier";
@@ -13,7 +15,7 @@ pub const VERSION: &str = "version";
 pub const DEPENDENCIES: &str = "dependencies";
 pub const DEV_DEPENDENCIES: &str = "devDependencies";
 pub const PEER_DEPENDENCIES: &str = "peerDependencies";
-pub const OPT_DEPENDENCIES: &str = "optDependencies";
+pub const OPT_DEPENDENCIES: &str = "optionalDependencies";
 pub const PEER_DEPENDENCIES_META: &str = "peerDependenciesMeta";
 
 // packages
@@ -24,6 +26,7 @@ pub const ENGINES: &str = "engines";
 pub const OPTIONAL: &str = "optional";
 pub const OS: &str = "os";
 pub const CPU: &str = "cpu";
+pub const LIBC: &str = "libc";
 
 pub const HAS_BIN: &str = "hasBin";
 
@@ -1,38 +1,47 @@
 use crate::actors::PackageType;
+use crate::cache::RegistryKey;
+use crate::config::NPMRC;
 use crate::contracts::Lockfile;
 use crate::errors::LockfileError;
-use crate::lockfile::constants::CURRENT_IMPORTER;
 use crate::lockfile::lockfile_structure::{
     ImporterSections, LockfileStructure, ResolvedDependency,
 };
-use crate::package::{PackageMetaHandler, PackageRecorder};
+use crate::package::{NpmPackage, Overrides, PackageJson, PackageMetaHandler, PackageRecorder};
 use crate::pipeline::ResolvedItem;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::path::Path;
 
 pub struct LockFileActor {
-    resolved_items: Vec<ResolvedItem>,
+    /// The resolved items of every importer, keyed by the importer id
+    importers: Vec<(String, Vec<ResolvedItem>)>,
     recorder: PackageRecorder,
 }
 
 impl LockFileActor {
     pub(crate) fn new(
-        resolved_items: Vec<ResolvedItem>,
+        importers: Vec<(String, Vec<ResolvedItem>)>,
         recorder: PackageRecorder,
     ) -> LockFileActor {
         LockFileActor {
-            resolved_items,
+            importers,
             recorder,
         }
     }
 
-    fn persist_lockfile_structure(content: &str) -> Result<(), LockfileError> {
-        fs::write("pnpm-lock.yaml", content)
-            .map_err(|e| LockfileError::FileWriteError(e.to_string()))?;
+    fn persist_lockfile_structure(path: &Path, content: &str) -> Result<(), LockfileError> {
+        fs::write(path, content).map_err(|e| LockfileError::FileWriteError(e.to_string()))?;
         Ok(())
     }
 
+    /// Aliases are locked as the package they point to, e.g. `react@18.3.1`
+    fn locked_version(package: &NpmPackage) -> String {
+        match package.alias_of {
+            Some(_) => package.target().to_string(),
+            None => package.version.clone(),
+        }
+    }
+
     fn create_importers(
         packages: Vec<ResolvedItem>,
         map: Option<&ImporterSections>,
@@ -47,7 +56,7 @@ impl LockFileActor {
                             dev_d.insert(
                                 item.package.name.clone(),
                                 ResolvedDependency {
-                                    version: item.package.version.clone(),
+                                    version: Self::locked_version(&item.package),
                                     specifier: item.specifier.clone(),
                                 },
                             );
@@ -57,7 +66,7 @@ impl LockFileActor {
                             dev_deps.insert(
                                 item.package.name.clone(),
                                 ResolvedDependency {
-                                    version: item.package.version.clone(),
+                                    version: Self::locked_version(&item.package),
                                     specifier: item.specifier.clone(),
                                 },
                             );
@@ -69,7 +78,7 @@ impl LockFileActor {
                             opt_d.insert(
                                 item.package.name.clone(),
                                 ResolvedDependency {
-                                    version: item.package.version.clone(),
+                                    version: Self::locked_version(&item.package),
                                     specifier: item.specifier.clone(),
                                 },
                             );
@@ -79,7 +88,7 @@ impl LockFileActor {
                             dev_deps.insert(
                                 item.package.name.clone(),
                                 ResolvedDependency {
-                                    version: item.package.version.clone(),
+                                    version: Self::locked_version(&item.package),
                                     specifier: item.specifier.clone(),
                                 },
                             );
@@ -91,7 +100,7 @@ impl LockFileActor {
                             opt_d.insert(
                                 item.package.name.clone(),
                                 ResolvedDependency {
-                                    version: item.package.version.clone(),
+                                    version: Self::locked_version(&item.package),
                                     specifier: item.specifier.clone(),
                                 },
                             );
@@ -101,7 +110,7 @@ impl LockFileActor {
                             prod_deps.insert(
                                 item.package.name.clone(),
                                 ResolvedDependency {
-                                    version: item.package.version.clone(),
+                                    version: Self::locked_version(&item.package),
                                     specifier: item.specifier.clone(),
                                 },
                             );
@@ -113,7 +122,7 @@ impl LockFileActor {
                             opt_d.insert(
                                 item.package.name.clone(),
                                 ResolvedDependency {
-                                    version: item.package.version.clone(),
+                                    version: Self::locked_version(&item.package),
                                     specifier: item.specifier.clone(),
                                 },
                             );
@@ -123,7 +132,7 @@ impl LockFileActor {
                             prod_deps.insert(
                                 item.package.name.clone(),
                                 ResolvedDependency {
-                                    version: item.package.version.clone(),
+                                    version: Self::locked_version(&item.package),
                                     specifier: item.specifier.clone(),
                                 },
                             );
@@ -141,52 +150,215 @@ impl LockFileActor {
         &self,
         lockfile_structure: &mut LockfileStructure,
     ) -> Result<(), LockfileError> {
-        match &mut lockfile_structure.importers {
-            Some(e) => {
-                let current_importer = e.get(CURRENT_IMPORTER);
-                match current_importer {
-                    Some(i) => {
-                        e.insert(
-                            CURRENT_IMPORTER.to_string(),
-                            Self::create_importers(self.resolved_items.clone(), Some(&i.clone())),
-                        );
-                        Ok(())
-                    }
-                    None => {
-                        let resolved_deps =
-                            Self::create_importers(self.resolved_items.clone(), None);
-                        e.insert(CURRENT_IMPORTER.to_string(), resolved_deps);
-                        Ok(())
-                    }
+        let importers = lockfile_structure
+            .importers
+            .get_or_insert_with(HashMap::new);
+
+        for (id, resolved_items) in &self.importers {
+            let resolved_deps = Self::create_importers(resolved_items.clone(), importers.get(id));
+            importers.insert(id.clone(), resolved_deps);
+        }
+
+        Ok(())
+    }
+
+    /// Keeps the overrides and which dependencies may be built in sync with
+    /// package.json
+    fn handle_settings(lockfile_structure: &mut LockfileStructure) {
+        if let Some(value) = NPMRC.get("auto-install-peers") {
+            lockfile_structure
+                .settings
+                .get_or_insert_with(Default::default)
+                .auto_install_peers = Some(value == "true");
+        }
+
+        let overrides = Overrides::load(Path::new("."));
+        lockfile_structure.overrides = (!overrides.is_empty()).then(|| overrides.to_map());
+
+        let settings = fs::read_to_string("package.json")
+            .ok()
+            .and_then(|c| serde_json::from_str::<PackageJson>(&c).ok())
+            .and_then(|p| p.pnpm);
+
+        if let Some(settings) = settings {
+            lockfile_structure.only_built_dependencies = settings.only_built_dependencies;
+            lockfile_structure.never_built_dependencies = settings.never_built_dependencies;
+        }
+    }
+
+    /// Removes the dependencies from the importer and drops all packages that
+    /// are not reachable from any importer anymore, which are returned
+    pub(crate) fn remove_dependencies(
+        directory: &Path,
+        importer: &str,
+        names: &[String],
+    ) -> Result<Vec<RegistryKey>, LockfileError> {
+        let path = directory.join("pnpm-lock.yaml");
+        if !path.exists() {
+            return Ok(vec![]);
+        }
+
+        let mut lockfile_structure = Self::read_lock_file(&path)?;
+        Self::remove_from_importer(&mut lockfile_structure, importer, names);
+        let unreachable = Self::prune_packages(&mut lockfile_structure);
+        Self::persist_lockfile_structure(&path, &lockfile_structure.write_to_string())?;
+        Ok(unreachable)
+    }
+
+    fn remove_from_importer(
+        lockfile_structure: &mut LockfileStructure,
+        importer: &str,
+        names: &[String],
+    ) {
+        let Some(sections) = lockfile_structure
+            .importers
+            .as_mut()
+            .and_then(|i| i.get_mut(importer))
+        else {
+            return;
+        };
+
+        for section in [
+            &mut sections.dependencies,
+            &mut sections.dev_dependencies,
+            &mut sections.optional_dependencies,
+            &mut sections.peer_dependencies,
+        ] {
+            if let Some(dependencies) = section {
+                dependencies.retain(|name, _| !names.contains(name));
+                if dependencies.is_empty() {
+                    *section = None;
                 }
             }
-            None => {
-                let mut new_importers = HashMap::new();
-                new_importers.insert(
-                    CURRENT_IMPORTER.to_string(),
-                    Self::create_importers(self.resolved_items.clone(), None),
+        }
+    }
+
+    /// The key of a package without the peer dependencies suffix, aliases
+    /// point to the key of another package
+    fn package_key(name: &str, version: &str) -> String {
+        let version = version.split_once('(').map_or(version, |(v, _)| v);
+        match Self::alias_target(version) {
+            Some(target) => target.to_string(),
+            None => format!("{}@{}", name, version),
+        }
+    }
+
+    /// The package an alias is locked as, its version is `react@18.3.1`
+    /// instead of `18.3.1`
+    pub(crate) fn alias_target(version: &str) -> Option<RegistryKey> {
+        let key = Self::registry_key(version)?;
+        key.version.parse::<nodejs_semver::Version>().ok()?;
+        Some(key)
+    }
+
+    /// Splits `name@version` behind the scope of the name
+    fn registry_key(key: &str) -> Option<RegistryKey> {
+        let (name, version) = key.get(1..)?.split_once('@')?;
+        Some(RegistryKey {
+            name: format!("{}{}", &key[..1], name),
+            version: version.to_string(),
+        })
+    }
+
+    fn prune_packages(lockfile_structure: &mut LockfileStructure) -> Vec<RegistryKey> {
+        let snapshots = lockfile_structure
+            .snapshots
+            .take()
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|(key, snapshot)| {
+                let key = Self::registry_key(&key)?;
+                Some((Self::package_key(&key.name, &key.version), snapshot))
+            })
+            .collect::<HashMap<_, _>>();
+
+        let mut queue = lockfile_structure
+            .importers
+            .iter()
+            .flat_map(|importers| importers.values())
+            .flat_map(|sections| {
+                [
+                    &sections.dependencies,
+                    &sections.dev_dependencies,
+                    &sections.optional_dependencies,
+                    &sections.peer_dependencies,
+                ]
+            })
+            .flatten()
+            .flatten()
+            .map(|(name, dependency)| Self::package_key(name, &dependency.version))
+            .collect::<Vec<_>>();
+
+        let mut reachable = HashSet::new();
+        while let Some(key) = queue.pop() {
+            if !reachable.insert(key.clone()) {
+                continue;
+            }
+            if let Some(snapshot) = snapshots.get(&key) {
+                queue.extend(
+                    [&snapshot.dependencies, &snapshot.optional_dependencies]
+                        .into_iter()
+                        .flatten()
+                        .flatten()
+                        .map(|(name, version)| Self::package_key(name, version)),
                 );
+            }
+        }
 
-                lockfile_structure.importers = Some(new_importers);
-                Ok(())
+        let mut unreachable = vec![];
+        if let Some(packages) = &mut lockfile_structure.packages {
+            packages.retain(|key, _| {
+                let keep = reachable.contains(key);
+                if !keep {
+                    unreachable.extend(Self::registry_key(key));
+                }
+                keep
+            });
+            // The snapshots are written from the resolved dependencies
+            for (key, package) in packages.iter_mut() {
+                let Some(snapshot) = snapshots.get(key) else {
+                    continue;
+                };
+                let mut dependencies = snapshot.dependencies.clone().unwrap_or_default();
+                dependencies.extend(snapshot.optional_dependencies.clone().unwrap_or_default());
+                package.resolved_dependencies = Some(dependencies).filter(|d| !d.is_empty());
+                package.optional_dependencies = snapshot.optional_dependencies.clone();
             }
         }
+        unreachable
     }
 
     fn handle_packages(&self, lockfile_structure: &mut LockfileStructure) {
-        let mut hashmap: HashMap<String, PackageMetaHandler> = HashMap::new();
+        let recorded = self
+            .recorder
+            .main_packages
+            .iter()
+            .chain(self.recorder.sub_dependencies.iter())
+            .collect::<HashMap<_, _>>();
 
-        self.recorder.main_packages.iter().for_each(|p| {
-            let pm_handler: PackageMetaHandler = p.1.clone().into();
-            hashmap.insert(p.0.to_string(), pm_handler);
-        });
+        // Aliases are only known to what depends on them, the package itself
+        // is locked under the name it is published with
+        let mut hashmap: HashMap<String, PackageMetaHandler> = HashMap::new();
+        for meta in recorded.values() {
+            let mut pm_handler: PackageMetaHandler = (*meta).clone().into();
+            for (name, version) in pm_handler.resolved_dependencies.iter_mut().flatten() {
+                let key = RegistryKey {
+                    name: name.clone(),
+                    version: version.clone(),
+                };
+                if let Some(target) = recorded.get(&key).filter(|m| m.alias_of.is_some()) {
+                    *version = target.target().to_string();
+                }
+            }
+            hashmap.insert(meta.target().to_string(), pm_handler);
+        }
 
-        self.recorder.sub_dependencies.iter().for_each(|p| {
-            let pm_handler: PackageMetaHandler = p.1.clone().into();
-            hashmap.insert(p.0.to_string(), pm_handler);
-        });
+        lockfile_structure.packages = Some(hashmap);
 
-        lockfile_structure.packages = Some(hashmap)
+        // Optional packages left out on this platform
+        let skipped = &self.recorder.skipped_optional;
+        lockfile_structure.ignored_optional_dependencies =
+            (!skipped.is_empty()).then(|| skipped.iter().cloned().collect());
     }
 }
 
@@ -204,14 +376,172 @@ impl Lockfile<LockfileStructure> for LockFileActor {
             let mut lockfile_structure = Self::read_lock_file(Path::new("pnpm-lock.yaml"))?;
             self.handle_importers(&mut lockfile_structure)?;
             self.handle_packages(&mut lockfile_structure);
-            Self::persist_lockfile_structure(&lockfile_structure.write_to_string())?;
+            Self::handle_settings(&mut lockfile_structure);
+            Self::persist_lockfile_structure(
+                Path::new("pnpm-lock.yaml"),
+                &lockfile_structure.write_to_string(),
+            )?;
             Ok(())
         } else {
             let mut lockfile_structure = LockfileStructure::default();
             self.handle_importers(&mut lockfile_structure)?;
             self.handle_packages(&mut lockfile_structure);
-            Self::persist_lockfile_structure(&lockfile_structure.write_to_string())?;
+            Self::handle_settings(&mut lockfile_structure);
+            Self::persist_lockfile_structure(
+                Path::new("pnpm-lock.yaml"),
+                &lockfile_structure.write_to_string(),
+            )?;
             Ok(())
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lockfile::constants::CURRENT_IMPORTER;
+    use crate::pipeline::fixtures::{item, key, recorded};
+
+    const LOCKFILE: &str = r#"
+lockfileVersion: '9.0'
+
+importers:
+  .:
+    dependencies:
+      express:
+        specifier: ^4.0.0
+        version: 4.21.0
+      ms:
+        specifier: ^2.0.0
+        version: 2.1.3
+
+packages:
+  express@4.21.0:
+    resolution: {integrity: sha512-express}
+  debug@2.6.9:
+    resolution: {integrity: sha512-debug}
+  ms@2.0.0:
+    resolution: {integrity: sha512-ms-old}
+  ms@2.1.3:
+    resolution: {integrity: sha512-ms}
+
+snapshots:
+  express@4.21.0:
+    dependencies:
+      debug: 2.6.9
+  debug@2.6.9:
+    dependencies:
+      ms: 2.0.0
+  ms@2.0.0: {}
+  ms@2.1.3: {}
+"#;
+
+    #[test]
+    fn test_remove_prunes_unreachable_packages() {
+        let mut lockfile = serde_yaml_ng::from_str::<LockfileStructure>(LOCKFILE).unwrap();
+
+        LockFileActor::remove_from_importer(
+            &mut lockfile,
+            CURRENT_IMPORTER,
+            &["express".to_string()],
+        );
+        let unreachable = LockFileActor::prune_packages(&mut lockfile);
+
+        let importer = &lockfile.importers.as_ref().unwrap()[CURRENT_IMPORTER];
+        let dependencies = importer.dependencies.as_ref().unwrap();
+        assert!(!dependencies.contains_key("express"));
+        assert!(dependencies.contains_key("ms"));
+
+        let mut packages = lockfile.packages.unwrap().into_keys().collect::<Vec<_>>();
+        packages.sort();
+        assert_eq!(packages, vec!["ms@2.1.3"]);
+
+        let mut unreachable = unreachable
+            .iter()
+            .map(|k| k.to_string())
+            .collect::<Vec<_>>();
+        unreachable.sort();
+        assert_eq!(
+            unreachable,
+            vec!["debug@2.6.9", "express@4.21.0", "ms@2.0.0"]
+        );
+    }
+
+    /// `my-react` and `your-react` both install react 18.3.1, `app` depends
+    /// on it as `my-react`
+    fn aliased_react() -> LockFileActor {
+        let alias = |name: &str, parent: &[RegistryKey]| {
+            let mut item = item(name, "18.3.1", parent);
+            item.package.alias_of = Some("react".to_string());
+            item.specifier = "npm:react@18.3.1".to_string();
+            item
+        };
+        let app = item("app", "1.0.0", &[]);
+        let resolved = vec![
+            alias("my-react", &[]),
+            alias("your-react", &[]),
+            app.clone(),
+            alias("my-react", &[key("app", "1.0.0")]),
+        ];
+
+        let mut recorder = recorded(&resolved);
+        recorder
+            .main_packages
+            .get_mut(&key("app", "1.0.0"))
+            .unwrap()
+            .resolved_dependencies = Some(HashMap::from([(
+            "my-react".to_string(),
+            "18.3.1".to_string(),
+        )]));
+
+        LockFileActor::new(vec![(CURRENT_IMPORTER.to_string(), resolved)], recorder)
+    }
+
+    #[test]
+    fn test_aliases_are_locked_as_their_target() {
+        let actor = aliased_react();
+        let mut lockfile = LockfileStructure::default();
+
+        actor.handle_importers(&mut lockfile).unwrap();
+        actor.handle_packages(&mut lockfile);
+
+        let importer = &lockfile.importers.as_ref().unwrap()[CURRENT_IMPORTER];
+        let dependencies = importer.dependencies.as_ref().unwrap();
+        assert_eq!(dependencies["my-react"].version, "react@18.3.1");
+        assert_eq!(dependencies["my-react"].specifier, "npm:react@18.3.1");
+        assert_eq!(dependencies["your-react"].version, "react@18.3.1");
+
+        let packages = lockfile.packages.as_ref().unwrap();
+        let mut keys = packages.keys().collect::<Vec<_>>();
+        keys.sort();
+        assert_eq!(keys, vec!["app@1.0.0", "react@18.3.1"]);
+        assert_eq!(
+            packages["app@1.0.0"].resolved_dependencies,
+            Some(HashMap::from([(
+                "my-react".to_string(),
+                "react@18.3.1".to_string()
+            )]))
+        );
+    }
+
+    #[test]
+    fn test_prune_follows_aliases() {
+        let actor = aliased_react();
+        let mut lockfile = LockfileStructure::default();
+        actor.handle_importers(&mut lockfile).unwrap();
+        actor.handle_packages(&mut lockfile);
+        let lockfile = lockfile.write_to_string();
+        let mut lockfile = serde_yaml_ng::from_str::<LockfileStructure>(&lockfile).unwrap();
+
+        LockFileActor::remove_from_importer(
+            &mut lockfile,
+            CURRENT_IMPORTER,
+            &["app".to_string(), "your-react".to_string()],
+        );
+
+        let unreachable = LockFileActor::prune_packages(&mut lockfile);
+
+        assert_eq!(unreachable, vec![key("app", "1.0.0")]);
+        assert!(lockfile.packages.unwrap().contains_key("react@18.3.1"));
+    }
+}
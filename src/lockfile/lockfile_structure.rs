@@ -1,8 +1,8 @@
 use crate::lockfile::constants::{
     AUTO_INSTALL_PEERS, CPU, DEPENDENCIES, DEV_DEPENDENCIES, ENGINES, EXCLUDE_LINKS_FROM_LOCKFILE,
-    HAS_BIN, LOCKFILE_VERSION, OPTIONAL, OPT_DEPENDENCIES, OS, PACKAGES, PEER_DEPENDENCIES,
-    PEER_DEPENDENCIES_META, PEER_SUFFIX_MAX_LENGTH, RESOLUTION, SETTINGS, SNAPSHOTS, SPECIFIER,
-    VERSION,
+    HAS_BIN, IGNORED_OPTIONAL_DEPENDENCIES, LIBC, LOCKFILE_VERSION, OPTIONAL, OPT_DEPENDENCIES, OS,
+    OVERRIDES, PACKAGES, PEER_DEPENDENCIES, PEER_DEPENDENCIES_META, PEER_SUFFIX_MAX_LENGTH,
+    RESOLUTION, SETTINGS, SNAPSHOTS, SPECIFIER, VERSION,
 };
 use crate::package::{EnginesType, PackageMetaHandler};
 use serde::{Deserialize, Serialize};
@@ -19,7 +19,9 @@ pub type ResolvedDependencies = HashMap<String, ResolvedDependency>;
 type CatalogName = String;
 type DependencyName = String;
 
-#[derive(Serialize, Deserialize)]
+/// The order of the variants matters: the structures are untagged, so the
+/// most specific ones have to be tried first.
+#[derive(Serialize, Deserialize, Clone, Debug)]
 #[serde(untagged)]
 pub enum LockfileResolution {
     Directory(DirectoryResolution),
@@ -31,35 +33,66 @@ pub enum LockfileResolution {
 /**
  * tarball hosted remotely
  */
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct TarballResolution {
-    pub r#type: Option<String>,
-    pub tarball: Option<String>,
+    pub tarball: String,
     pub integrity: Option<String>,
-    pub path: Option<String>,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct DirectoryResolution {
-    directory: String,
+    pub directory: String,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct GitRepositoryResolution {
-    repo: String,
-    commit: String,
-    path: Option<String>,
+    pub repo: String,
+    pub commit: String,
+    pub path: Option<String>,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct IntegrityResolution {
     pub integrity: String,
 }
 
+impl LockfileResolution {
+    fn format_inline(&self) -> String {
+        let value = LockfileStructure::format_flow_string;
+        match self {
+            LockfileResolution::Integrity(i) => format!("{{integrity: {}}}", value(&i.integrity)),
+            LockfileResolution::Tarball(t) => match &t.integrity {
+                Some(integrity) => format!(
+                    "{{integrity: {}, tarball: {}}}",
+                    value(integrity),
+                    value(&t.tarball)
+                ),
+                None => format!("{{tarball: {}}}", value(&t.tarball)),
+            },
+            LockfileResolution::Directory(d) => {
+                format!("{{directory: {}, type: directory}}", value(&d.directory))
+            }
+            LockfileResolution::GitRepository(g) => match &g.path {
+                Some(path) => format!(
+                    "{{commit: {}, path: {}, repo: {}, type: git}}",
+                    value(&g.commit),
+                    value(path),
+                    value(&g.repo)
+                ),
+                None => format!(
+                    "{{commit: {}, repo: {}, type: git}}",
+                    value(&g.commit),
+                    value(&g.repo)
+                ),
+            },
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ResolvedCatalogEntry {
@@ -67,18 +100,6 @@ pub struct ResolvedCatalogEntry {
     pub version: String,
 }
 
-#[derive(Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct PackageSnapshot {
-    pub id: Option<String>,
-    pub optional: Option<bool>,
-    pub patched: Option<bool>,
-    pub has_bin: Option<bool>,
-    pub name: Option<String>,
-    pub version: Option<String>,
-    pub resolution: LockfileResolution,
-}
-
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct PatchFile {
@@ -86,14 +107,7 @@ pub struct PatchFile {
     pub hash: String,
 }
 
-#[derive(Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct PatchInfo {
-    strict: bool,
-    file: PatchFile,
-}
-
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct LockfileSettings {
     pub auto_install_peers: Option<bool>,
@@ -136,6 +150,8 @@ pub struct LockfileStructure {
         serialize_with = "ordered_map"
     )]
     pub packages: Option<HashMap<String, PackageMetaHandler>>,
+    #[serde(skip_serializing)]
+    pub snapshots: Option<HashMap<String, PackageMetaHandler>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub never_built_dependencies: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -154,6 +170,8 @@ pub struct LockfileStructure {
 
 impl LockfileStructure {
     const ESCAPE_CHARS: [char; 4] = ['@', '<', '>', '*'];
+    const FLOW_CHARS: [char; 5] = [',', '[', ']', '{', '}'];
+    const FLOW_START_CHARS: [char; 11] = ['#', '&', '!', '|', '\'', '"', '%', '`', '-', '?', ':'];
 
     fn starts_with_illegal_character(str: &str) -> bool {
         if let Some(c) = str.chars().next() {
@@ -171,6 +189,23 @@ impl LockfileStructure {
         str.to_string()
     }
 
+    /// Quotes values of inline mappings like `{tarball: ...}` that would
+    /// otherwise end the value or the mapping early
+    fn format_flow_string(str: &str) -> String {
+        let is_plain = !Self::starts_with_illegal_character(str)
+            && !str.starts_with(Self::FLOW_START_CHARS)
+            && !str.contains(Self::FLOW_CHARS)
+            && !str.contains(": ")
+            && !str.contains(" #")
+            && !str.ends_with(':')
+            && str.trim() == str
+            && !str.is_empty();
+        if is_plain {
+            return str.to_string();
+        }
+        format!("'{}'", str.replace('\'', "''"))
+    }
+
     fn format_line(key: &str, value: Option<&str>, indent: i32) -> String {
         let mut str = "".to_string();
         for _ in 0..indent * 2 {
@@ -217,6 +252,37 @@ impl LockfileStructure {
         settings_str
     }
 
+    fn format_overrides(&self) -> String {
+        let mut overrides_str = Self::format_line(OVERRIDES, None, 0);
+        let overrides: BTreeMap<_, _> = self.overrides.iter().flatten().collect();
+
+        for (selector, specifier) in overrides {
+            // A plain `-` would be a list item
+            let specifier = match specifier.as_str() {
+                "-" => "'-'",
+                specifier => specifier,
+            };
+            overrides_str.push_str(&Self::format_line(selector, Some(specifier), 1));
+        }
+
+        overrides_str
+    }
+
+    fn format_ignored_optional_dependencies(&self) -> String {
+        let mut ignored_str = Self::format_line(IGNORED_OPTIONAL_DEPENDENCIES, None, 0);
+        let mut ignored = self
+            .ignored_optional_dependencies
+            .clone()
+            .unwrap_or_default();
+        ignored.sort();
+
+        for name in ignored {
+            ignored_str.push_str(&format!("  - {}\n", Self::format_string(&name)));
+        }
+
+        ignored_str
+    }
+
     fn format_lockfile_version(&self) -> String {
         Self::format_line(
             LOCKFILE_VERSION,
@@ -294,7 +360,7 @@ impl LockfileStructure {
     ) {
         packages_serialized.push('\n');
         if snapshot
-            && p.1.dependencies.is_none()
+            && p.1.resolved_dependencies.is_none()
             && p.1.peer_dependencies.is_none()
             && p.1.has_bin.is_none()
         {
@@ -305,10 +371,9 @@ impl LockfileStructure {
 
         if !snapshot {
             if let Some(res) = &p.1.resolution {
-                let integrity = format!("{{integrity: {}}}", res.integrity);
                 packages_serialized.push_str(&Self::format_line(
                     RESOLUTION,
-                    Some(&integrity),
+                    Some(&res.format_inline()),
                     index + 1,
                 ));
             }
@@ -316,10 +381,19 @@ impl LockfileStructure {
 
         if snapshot {
             if let Some(deps) = &p.1.resolved_dependencies {
-                packages_serialized.push_str(&Self::format_line(DEPENDENCIES, None, index + 1));
-                deps.iter().for_each(|(k, v)| {
-                    packages_serialized.push_str(&Self::format_line(k, Some(v), index + 2));
-                })
+                let optional = p.1.optional_dependencies.clone().unwrap_or_default();
+                let (optional, required): (BTreeMap<_, _>, BTreeMap<_, _>) =
+                    deps.iter().partition(|(k, _)| optional.contains_key(*k));
+
+                for (section, deps) in [(DEPENDENCIES, required), (OPT_DEPENDENCIES, optional)] {
+                    if deps.is_empty() {
+                        continue;
+                    }
+                    packages_serialized.push_str(&Self::format_line(section, None, index + 1));
+                    deps.iter().for_each(|(k, v)| {
+                        packages_serialized.push_str(&Self::format_line(k, Some(v), index + 2));
+                    })
+                }
             }
         }
 
@@ -409,6 +483,14 @@ impl LockfileStructure {
             ));
         }
 
+        if let Some(libc) = &p.1.libc {
+            packages_serialized.push_str(&Self::format_line(
+                LIBC,
+                Some(&Self::format_inline_vector(libc)),
+                index + 1,
+            ));
+        }
+
         if let Some(bin) = &p.1.has_bin {
             packages_serialized.push_str(&Self::format_line(
                 HAS_BIN,
@@ -458,6 +540,20 @@ impl LockfileStructure {
             serialized_content.push_str(&self.format_settings())
         }
 
+        if self.overrides.as_ref().is_some_and(|o| !o.is_empty()) {
+            serialized_content.push('\n');
+            serialized_content.push_str(&self.format_overrides())
+        }
+
+        if self
+            .ignored_optional_dependencies
+            .as_ref()
+            .is_some_and(|i| !i.is_empty())
+        {
+            serialized_content.push('\n');
+            serialized_content.push_str(&self.format_ignored_optional_dependencies())
+        }
+
         if self.importers.is_some() {
             serialized_content.push('\n');
             serialized_content.push_str(&self.format_importers())
@@ -515,26 +611,46 @@ impl Default for LockfileStructure {
             pnpmfile_checksum: None,
             never_built_dependencies: None,
             packages: None,
+            snapshots: None,
         }
     }
 }
 
-#[derive(Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-struct ProjectSnapshot {
-    specifiers: ResolvedDependencies,
-    dependencies: Option<ResolvedDependencies>,
-    optional_dependencies: Option<ResolvedDependencies>,
-    dev_dependencies: Option<ResolvedDependencies>,
-    dependencies_meta: Option<DependenciesMeta>,
-}
+// ─── Tests ───────────────────────────────────────────────────────────────────
 
-pub type DependenciesMeta = HashMap<DependencyName, DependencyMeta>;
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-#[derive(Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct DependencyMeta {
-    injected: Option<bool>,
-    node: Option<String>,
-    patch: Option<String>,
+    fn resolution(lockfile: &LockfileStructure, key: &str) -> String {
+        let resolution = lockfile.packages.as_ref().unwrap()[key].resolution.clone();
+        serde_json::to_string(&resolution).unwrap()
+    }
+
+    #[test]
+    fn test_resolutions_survive_a_round_trip() {
+        let lockfile = serde_yaml_ng::from_str::<LockfileStructure>(
+            r#"
+lockfileVersion: '9.0'
+
+packages:
+  remote@1.0.0:
+    resolution: {integrity: sha512-remote, tarball: 'https://example.com/remote.tgz?a=1,2'}
+  local@file:vendor:
+    resolution: {directory: '../my dir, v2: {x} #1', type: directory}
+  repo@1.0.0:
+    resolution: {commit: abc, path: 'it''s', repo: 'git+ssh://host/a#b', type: git}
+"#,
+        )
+        .unwrap();
+
+        let written = lockfile.write_to_string();
+        let read = serde_yaml_ng::from_str::<LockfileStructure>(&written).unwrap();
+
+        for key in ["remote@1.0.0", "local@file:vendor", "repo@1.0.0"] {
+            assert_eq!(resolution(&read, key), resolution(&lockfile, key));
+        }
+        assert!(written.contains("tarball: 'https://example.com/remote.tgz?a=1,2'"));
+        assert!(written.contains("{integrity: sha512-remote, "));
+    }
 }
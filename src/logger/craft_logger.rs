@@ -1,4 +1,12 @@
+use crate::config::NPMRC;
 use crate::contracts::Logger;
+use lazy_static::lazy_static;
+
+lazy_static! {
+    static ref SECRETS: Vec<String> = NPMRC.secrets();
+}
+
+const REDACTED: &str = "***";
 
 // ─── CraftLogger ─────────────────────────────────────────────────────────────────
 
@@ -9,7 +17,22 @@ pub struct CraftLogger;
 
 impl CraftLogger {
     pub fn verbose<S: AsRef<str> + std::fmt::Display>(message: S) {
-        log::debug!("{}", message)
+        log::debug!("{}", Self::redact(message.as_ref(), &SECRETS))
+    }
+
+    /// Replaces every occurrence of the secrets in the message
+    fn redact(message: &str, secrets: &[String]) -> String {
+        let mut secrets = secrets.iter().collect::<Vec<_>>();
+        // Longer secrets first, so a secret containing another one is
+        // replaced as a whole
+        secrets.sort_by_key(|s| std::cmp::Reverse(s.len()));
+
+        secrets
+            .into_iter()
+            .filter(|s| !s.is_empty())
+            .fold(message.to_string(), |message, secret| {
+                message.replace(secret.as_str(), REDACTED)
+            })
     }
 }
 
@@ -17,14 +40,32 @@ impl CraftLogger {
 
 impl Logger for CraftLogger {
     fn info<S: AsRef<str> + std::fmt::Display>(message: S) {
-        log::info!("{}", message)
+        log::info!("{}", Self::redact(message.as_ref(), &SECRETS))
     }
 
     fn error<S: AsRef<str> + std::fmt::Display>(message: S) {
-        log::error!("{}", message)
+        log::error!("{}", Self::redact(message.as_ref(), &SECRETS))
     }
 
     fn warn<S: AsRef<str> + std::fmt::Display>(message: S) {
-        log::warn!("{}", message)
+        log::warn!("{}", Self::redact(message.as_ref(), &SECRETS))
+    }
+}
+
+// ─── Tests ───────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact() {
+        let secrets = vec!["tok".to_string(), "token-123".to_string()];
+
+        assert_eq!(
+            CraftLogger::redact("Authorization: Bearer token-123, tok", &secrets),
+            "Authorization: Bearer ***, ***"
+        );
+        assert_eq!(CraftLogger::redact("nothing here", &[]), "nothing here");
     }
 }
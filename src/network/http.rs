@@ -1,39 +1,307 @@
 use std::path::PathBuf;
 
+use crate::config::{Npmrc, NPMRC};
+use crate::contracts::Logger;
 use crate::errors::NetworkError;
+use crate::logger::CraftLogger;
+use crate::network::{Integrity, RetryPolicy};
+use chrono::{DateTime, Utc};
+use lazy_static::lazy_static;
+use reqwest::header::{ACCEPT_ENCODING, AUTHORIZATION, RETRY_AFTER};
+use reqwest::{Client, Response, StatusCode};
 use sha1::{Digest, Sha1};
+use std::time::Duration;
 use tokio::fs::File;
 use tokio::io::AsyncWriteExt;
+use tokio::sync::{Semaphore, SemaphorePermit};
 
 pub struct Http;
 
+/// Requests at the same time, unless `network-concurrency` says otherwise
+const NETWORK_CONCURRENCY: usize = 16;
+
+lazy_static! {
+    /// One client for all requests, so connections to the registries are
+    /// kept alive and reused. Packuments may come gzip or brotli compressed.
+    static ref CLIENT: Client = Client::builder()
+        .gzip(true)
+        .brotli(true)
+        .tcp_keepalive(Duration::from_secs(60))
+        .pool_idle_timeout(Duration::from_secs(90))
+        .build()
+        .expect("Failed to create the HTTP client");
+    static ref REQUESTS: Semaphore = Semaphore::new(Http::concurrency(&NPMRC));
+}
+
 impl Http {
+    pub fn client() -> &'static Client {
+        &CLIENT
+    }
+
+    /// Waits until fewer than `network-concurrency` requests are running, the
+    /// request may start while the permit is held
+    pub async fn permit() -> SemaphorePermit<'static> {
+        REQUESTS
+            .acquire()
+            .await
+            .expect("The request semaphore is never closed")
+    }
+
+    pub fn concurrency(config: &Npmrc) -> usize {
+        config
+            .get("network-concurrency")
+            .and_then(|value| value.parse::<usize>().ok())
+            .filter(|concurrency| *concurrency > 0)
+            .unwrap_or(NETWORK_CONCURRENCY)
+    }
+
+    /// Downloads the file and verifies it against the strongest supported hash
+    /// of the subresource integrity (e.g. `sha512-...`), or the shasum of the
+    /// registry if there is none. Failed attempts are retried as a whole.
     pub async fn download_file(
         url: &str,
         path: &PathBuf,
         sha_sum: &str,
+        integrity: Option<&str>,
+        auth: Option<&str>,
+        retry: &RetryPolicy,
     ) -> Result<(), NetworkError> {
-        log::info!("Downloading file from: {}", url);
-        let mut response = reqwest::get(url).await?;
-        let mut hasher = Sha1::new();
+        CraftLogger::info(format!("Downloading file from: {}", url));
 
-        let mut file = match File::create(path).await {
-            Ok(file) => file,
-            Err(_) => {
-                println!("Could not create file at {:?}", path);
-                panic!("Stop");
-            }
+        retry
+            .run(url, || {
+                Self::try_download(url, path, sha_sum, integrity, auth)
+            })
+            .await
+    }
+
+    /// Writes next to `path` and only moves the file there once it is
+    /// complete and verified, the cache takes every file it finds as valid
+    async fn try_download(
+        url: &str,
+        path: &PathBuf,
+        sha_sum: &str,
+        integrity: Option<&str>,
+        auth: Option<&str>,
+    ) -> Result<(), NetworkError> {
+        let mut partial = path.clone().into_os_string();
+        partial.push(format!(".{}.part", std::process::id()));
+        let partial = PathBuf::from(partial);
+
+        let result = match Self::write_verified(url, &partial, sha_sum, integrity, auth).await {
+            Ok(()) => tokio::fs::rename(&partial, path).await.map_err(Into::into),
+            Err(e) => Err(e),
         };
+        if result.is_err() {
+            let _ = tokio::fs::remove_file(&partial).await;
+        }
+        result
+    }
+
+    async fn write_verified(
+        url: &str,
+        path: &PathBuf,
+        sha_sum: &str,
+        integrity: Option<&str>,
+        auth: Option<&str>,
+    ) -> Result<(), NetworkError> {
+        // The checksums are the ones of the tarball as it is
+        let mut request = Self::client().get(url).header(ACCEPT_ENCODING, "identity");
+        if let Some(auth) = auth {
+            request = request.header(AUTHORIZATION, auth);
+        }
+        let response = request
+            .send()
+            .await
+            .map_err(|e| Self::request_error(url, e))?;
+        let mut response = Self::check_status(url, response)?;
+        let integrity = integrity.map(Integrity::parse);
+        let algorithm = integrity.as_ref().and_then(Integrity::strongest);
+        let mut integrity_hasher = algorithm.map(|a| a.hasher());
+        let mut hasher = Sha1::new();
 
-        while let Some(chunk) = response.chunk().await? {
+        let mut file = File::create(path).await?;
+
+        while let Some(chunk) = response
+            .chunk()
+            .await
+            .map_err(|e| Self::request_error(url, e))?
+        {
             file.write_all(&chunk).await?;
             hasher.update(&chunk);
+            if let Some(integrity_hasher) = &mut integrity_hasher {
+                integrity_hasher.update(&chunk);
+            }
+        }
+
+        let verified = match (integrity, algorithm, integrity_hasher) {
+            (Some(integrity), Some(algorithm), Some(integrity_hasher)) => {
+                integrity.matches(algorithm, &integrity_hasher.finalize())
+            }
+            _ if !sha_sum.is_empty() => hex::encode(hasher.finalize().0) == sha_sum,
+            // Nothing that could be verified
+            (Some(_), ..) => false,
+            _ => true,
+        };
+
+        match verified {
+            true => Ok(()),
+            false => Err(NetworkError::CheckSum(url.to_string())),
+        }
+    }
+
+    /// The response if it is a success or not modified, otherwise the error of
+    /// its status
+    pub fn check_status(url: &str, response: Response) -> Result<Response, NetworkError> {
+        let status = response.status();
+        if status.is_success() || status == StatusCode::NOT_MODIFIED {
+            return Ok(response);
+        }
+
+        let url = url.to_string();
+        Err(match status {
+            StatusCode::NOT_FOUND => NetworkError::NotFound(url),
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => {
+                NetworkError::Unauthorized(url, status.as_u16())
+            }
+            StatusCode::TOO_MANY_REQUESTS => {
+                NetworkError::RateLimited(url, Self::retry_after(&response))
+            }
+            _ if status.is_server_error() => NetworkError::ServerError(url, status.as_u16()),
+            _ => NetworkError::UnexpectedStatus(url, status.as_u16()),
+        })
+    }
+
+    /// `Retry-After` as seconds or as a date
+    fn retry_after(response: &Response) -> Option<Duration> {
+        let value = response.headers().get(RETRY_AFTER)?.to_str().ok()?;
+        if let Ok(seconds) = value.trim().parse::<u64>() {
+            return Some(Duration::from_secs(seconds));
         }
-        let calculated_sha = hasher.finalize().0;
-        let calculated_sha_hex = hex::encode(calculated_sha);
-        if calculated_sha_hex != sha_sum {
-            return Err(NetworkError::CheckSum(url.to_string()));
+
+        let date = DateTime::parse_from_rfc2822(value).ok()?;
+        (date.with_timezone(&Utc) - Utc::now()).to_std().ok()
+    }
+
+    /// Timeouts of reqwest become a `Timeout` of the url
+    pub fn request_error(url: &str, error: reqwest::Error) -> NetworkError {
+        match error.is_timeout() {
+            true => NetworkError::Timeout(url.to_string()),
+            false => NetworkError::FetchFailure(error),
+        }
+    }
+}
+
+// ─── Tests ───────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::mock_server::{MockResponse, MockServer};
+    use crate::pipeline::fixtures::TempDir;
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use std::path::Path;
+
+    const TARBALL: &[u8] = b"a tarball that is long enough to be cut off";
+
+    fn no_retries() -> RetryPolicy {
+        RetryPolicy {
+            retries: 0,
+            ..Default::default()
+        }
+    }
+
+    /// The files of the folder, partial downloads included
+    fn files(folder: &Path) -> Vec<String> {
+        std::fs::read_dir(folder)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+            .collect()
+    }
+
+    async fn download(server: &MockServer, folder: &Path) -> Result<(), NetworkError> {
+        let sha_sum = hex::encode(Sha1::digest(TARBALL));
+
+        Http::download_file(
+            &format!("{}/pkg/-/pkg-1.0.0.tgz", server.url()),
+            &folder.join("pkg-1.0.0.tgz"),
+            &sha_sum,
+            None,
+            None,
+            &no_retries(),
+        )
+        .await
+    }
+
+    #[test]
+    fn test_concurrency() {
+        assert_eq!(Http::concurrency(&Npmrc::parse("")), NETWORK_CONCURRENCY);
+        assert_eq!(Http::concurrency(&Npmrc::parse("network-concurrency=4")), 4);
+        assert_eq!(
+            Http::concurrency(&Npmrc::parse("network-concurrency=0")),
+            NETWORK_CONCURRENCY
+        );
+    }
+
+    #[tokio::test]
+    async fn test_download_moves_verified_files_into_place() {
+        let server = MockServer::start(|_| MockResponse::new(200, TARBALL)).await;
+        let folder = TempDir::new("download");
+
+        download(&server, &folder).await.unwrap();
+
+        assert_eq!(files(&folder), vec!["pkg-1.0.0.tgz"]);
+        assert_eq!(
+            std::fs::read(folder.join("pkg-1.0.0.tgz")).unwrap(),
+            TARBALL
+        );
+    }
+
+    #[tokio::test]
+    async fn test_download_removes_corrupt_files() {
+        let server = MockServer::start(|_| MockResponse::new(200, "something else")).await;
+        let folder = TempDir::new("corrupt");
+
+        let result = download(&server, &folder).await;
+
+        assert!(matches!(result, Err(NetworkError::CheckSum(_))));
+        assert!(files(&folder).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_download_removes_truncated_files() {
+        let server = MockServer::start(|_| MockResponse::new(200, TARBALL).truncated(10)).await;
+        let folder = TempDir::new("truncated");
+
+        let result = download(&server, &folder).await;
+
+        assert!(result.is_err());
+        assert!(files(&folder).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_download_verifies_the_strongest_integrity() {
+        let server = MockServer::start(|_| MockResponse::new(200, TARBALL)).await;
+        let folder = TempDir::new("integrity");
+        let sha384 = STANDARD.encode(sha2::Sha384::digest(TARBALL));
+        let url = format!("{}/pkg/-/pkg-1.0.0.tgz", server.url());
+
+        for (integrity, valid) in [
+            (format!("sha384-{}", sha384), true),
+            (format!("sha1-wrong sha384-{}", sha384), true),
+            (format!("sha384-{} sha512-wrong", sha384), false),
+            ("md5-unsupported".to_string(), false),
+        ] {
+            let result = Http::download_file(
+                &url,
+                &folder.join("pkg-1.0.0.tgz"),
+                "",
+                Some(&integrity),
+                None,
+                &no_retries(),
+            )
+            .await;
+            assert_eq!(result.is_ok(), valid, "{}", integrity);
         }
-        Ok(())
     }
 }
@@ -0,0 +1,105 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use sha1::Sha1;
+use sha2::digest::DynDigest;
+use sha2::{Sha256, Sha384, Sha512};
+
+// ─── Integrity ───────────────────────────────────────────────────────────────
+
+/// A subresource integrity string like `sha512-... sha1-...`. Only the
+/// strongest algorithm that is supported is verified, like npm does.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Integrity {
+    hashes: Vec<(Algorithm, String)>,
+}
+
+/// Ordered from the weakest to the strongest
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Algorithm {
+    Sha1,
+    Sha256,
+    Sha384,
+    Sha512,
+}
+
+// ─────────────────────────────────────────────────────────────────────────────
+
+impl Algorithm {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "sha1" => Some(Self::Sha1),
+            "sha256" => Some(Self::Sha256),
+            "sha384" => Some(Self::Sha384),
+            "sha512" => Some(Self::Sha512),
+            _ => None,
+        }
+    }
+
+    pub fn hasher(&self) -> Box<dyn DynDigest + Send> {
+        match self {
+            Self::Sha1 => Box::new(Sha1::default()),
+            Self::Sha256 => Box::new(Sha256::default()),
+            Self::Sha384 => Box::new(Sha384::default()),
+            Self::Sha512 => Box::new(Sha512::default()),
+        }
+    }
+}
+
+impl Integrity {
+    /// Unknown algorithms and options after `?` are ignored
+    pub fn parse(integrity: &str) -> Self {
+        let hashes = integrity
+            .split_whitespace()
+            .filter_map(|hash| {
+                let (algorithm, digest) = hash.split_once('-')?;
+                let digest = digest.split('?').next().unwrap_or(digest);
+                Some((Algorithm::parse(algorithm)?, digest.to_string()))
+            })
+            .collect();
+
+        Self { hashes }
+    }
+
+    pub fn strongest(&self) -> Option<Algorithm> {
+        self.hashes.iter().map(|(algorithm, _)| *algorithm).max()
+    }
+
+    /// Whether the digest is one of the hashes of the algorithm
+    pub fn matches(&self, algorithm: Algorithm, digest: &[u8]) -> bool {
+        let digest = STANDARD.encode(digest);
+        self.hashes
+            .iter()
+            .any(|(a, expected)| *a == algorithm && *expected == digest)
+    }
+}
+
+// ─── Tests ───────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strongest_supported_hash() {
+        let integrity = Integrity::parse("sha1-AAAA md5-BBBB sha384-CCCC?opt sha512-DDDD");
+        assert_eq!(integrity.strongest(), Some(Algorithm::Sha512));
+
+        let integrity = Integrity::parse("sha384-CCCC sha1-AAAA");
+        assert_eq!(integrity.strongest(), Some(Algorithm::Sha384));
+
+        assert_eq!(Integrity::parse("md5-BBBB").strongest(), None);
+    }
+
+    #[test]
+    fn test_matches_digest() {
+        let mut hasher = Algorithm::Sha384.hasher();
+        hasher.update(b"tarball");
+        let digest = hasher.finalize();
+
+        let integrity = Integrity::parse(&format!(
+            "sha1-AAAA sha384-{}?opt",
+            STANDARD.encode(&digest)
+        ));
+        assert!(integrity.matches(Algorithm::Sha384, &digest));
+        assert!(!integrity.matches(Algorithm::Sha1, &digest));
+    }
+}
@@ -0,0 +1,155 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::sync::Mutex;
+
+// ─── MockServer ──────────────────────────────────────────────────────────────
+
+/// A minimal HTTP server for tests. Every request is answered by the handler
+/// and recorded, so tests can check what was asked for.
+pub struct MockServer {
+    url: String,
+    requests: Arc<Mutex<Vec<MockRequest>>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct MockRequest {
+    pub path: String,
+    pub headers: HashMap<String, String>,
+}
+
+pub struct MockResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+    /// Bytes of the body that are sent before the connection is closed
+    pub sent: Option<usize>,
+}
+
+// ─────────────────────────────────────────────────────────────────────────────
+
+impl MockRequest {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(&name.to_lowercase()).map(|h| h.as_str())
+    }
+}
+
+impl MockResponse {
+    pub fn new(status: u16, body: impl Into<Vec<u8>>) -> Self {
+        Self {
+            status,
+            headers: vec![],
+            body: body.into(),
+            sent: None,
+        }
+    }
+
+    pub fn json(body: impl Into<Vec<u8>>) -> Self {
+        Self::new(200, body).header("Content-Type", "application/json")
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Drops the connection after `sent` bytes of the announced body
+    pub fn truncated(mut self, sent: usize) -> Self {
+        self.sent = Some(sent);
+        self
+    }
+}
+
+impl MockServer {
+    pub async fn start<F>(handler: F) -> Self
+    where
+        F: Fn(&MockRequest) -> MockResponse + Send + Sync + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(vec![]));
+        let handler = Arc::new(handler);
+
+        let recorded = requests.clone();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let handler = handler.clone();
+                let recorded = recorded.clone();
+
+                tokio::spawn(async move {
+                    let mut buffer = vec![];
+                    let mut chunk = [0u8; 1024];
+                    while !buffer.windows(4).any(|w| w == b"\r\n\r\n") {
+                        match stream.read(&mut chunk).await {
+                            Ok(0) | Err(_) => return,
+                            Ok(n) => buffer.extend_from_slice(&chunk[..n]),
+                        }
+                    }
+
+                    let head = String::from_utf8_lossy(&buffer).to_string();
+                    let mut lines = head.lines();
+                    let path = lines
+                        .next()
+                        .and_then(|l| l.split_whitespace().nth(1))
+                        .unwrap_or("/")
+                        .to_string();
+                    let headers = lines
+                        .filter_map(|l| l.split_once(':'))
+                        .map(|(k, v)| (k.trim().to_lowercase(), v.trim().to_string()))
+                        .collect();
+
+                    let request = MockRequest { path, headers };
+                    let response = handler(&request);
+                    recorded.lock().await.push(request);
+
+                    let mut raw = format!(
+                        "HTTP/1.1 {} Mock\r\nContent-Length: {}\r\nConnection: close\r\n",
+                        response.status,
+                        response.body.len()
+                    );
+                    for (name, value) in &response.headers {
+                        raw.push_str(&format!("{}: {}\r\n", name, value));
+                    }
+                    raw.push_str("\r\n");
+
+                    let _ = stream.write_all(raw.as_bytes()).await;
+                    let sent = response.sent.unwrap_or(response.body.len());
+                    let _ = stream.write_all(&response.body[..sent]).await;
+                    let _ = stream.shutdown().await;
+                });
+            }
+        });
+
+        Self { url, requests }
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub async fn requests(&self) -> Vec<MockRequest> {
+        self.requests.lock().await.clone()
+    }
+}
+
+/// A packument in the format of the registry with the given versions, the
+/// last one is tagged `latest`
+pub fn packument(base_url: &str, name: &str, versions: &[&str]) -> String {
+    let file_name = name.rsplit('/').next().unwrap();
+    let latest = versions.last().unwrap_or(&"");
+    let versions = versions
+        .iter()
+        .map(|v| {
+            format!(
+                r#""{v}": {{"name": "{name}", "version": "{v}", "dist": {{"shasum": "", "tarball": "{base_url}/{name}/-/{file_name}-{v}.tgz"}}}}"#
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(",");
+
+    format!(
+        r#"{{"name": "{name}", "dist-tags": {{"latest": "{latest}"}}, "versions": {{{versions}}}}}"#
+    )
+}
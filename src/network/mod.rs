@@ -1,3 +1,11 @@
 mod http;
+mod integrity;
+#[cfg(test)]
+pub(crate) mod mock_server;
+mod mode;
+mod retry;
 
 pub use http::Http;
+pub use integrity::Integrity;
+pub use mode::NetworkMode;
+pub use retry::RetryPolicy;
@@ -0,0 +1,13 @@
+// ─── NetworkMode ─────────────────────────────────────────────────────────────
+
+/// How much an install relies on the registry
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum NetworkMode {
+    #[default]
+    Online,
+    /// Cached packuments are used however old they are, the registry is only
+    /// asked for what isn't cached
+    PreferOffline,
+    /// Everything comes from the caches, missing packages are an error
+    Offline,
+}
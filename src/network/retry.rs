@@ -0,0 +1,134 @@
+use std::collections::hash_map::RandomState;
+use std::future::Future;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+use crate::config::Npmrc;
+use crate::errors::NetworkError;
+use crate::logger::CraftLogger;
+use crate::network::Http;
+
+// ─── RetryPolicy ─────────────────────────────────────────────────────────────
+
+/// How often and how long requests to registries are tried, configured like
+/// npm with `fetch-retries`, `fetch-retry-factor`, `fetch-retry-mintimeout`,
+/// `fetch-retry-maxtimeout` and `fetch-timeout`. `fetch-total-timeout`
+/// limits all attempts and the waits between them together. Times are in
+/// milliseconds.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    pub retries: u32,
+    pub factor: u32,
+    pub min_timeout: Duration,
+    pub max_timeout: Duration,
+    /// Of a single attempt, until the whole body is read
+    pub timeout: Duration,
+    pub total_timeout: Duration,
+}
+
+// ─────────────────────────────────────────────────────────────────────────────
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            retries: 2,
+            factor: 10,
+            min_timeout: Duration::from_secs(10),
+            max_timeout: Duration::from_secs(60),
+            timeout: Duration::from_secs(60),
+            total_timeout: Duration::from_secs(300),
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn from_config(config: &Npmrc) -> Self {
+        let default = Self::default();
+        let number = |key: &str| config.get(key).and_then(|value| value.parse::<u64>().ok());
+        let millis =
+            |key: &str, default: Duration| number(key).map_or(default, Duration::from_millis);
+
+        Self {
+            retries: number("fetch-retries").map_or(default.retries, |n| n as u32),
+            factor: number("fetch-retry-factor").map_or(default.factor, |n| n as u32),
+            min_timeout: millis("fetch-retry-mintimeout", default.min_timeout),
+            max_timeout: millis("fetch-retry-maxtimeout", default.max_timeout),
+            timeout: millis("fetch-timeout", default.timeout),
+            total_timeout: millis("fetch-total-timeout", default.total_timeout),
+        }
+    }
+
+    /// Exponential with jitter, so parallel downloads don't retry in lockstep:
+    /// between half and all of `min_timeout * factor^attempt`
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let exponential = self
+            .min_timeout
+            .saturating_mul(self.factor.saturating_pow(attempt))
+            .min(self.max_timeout);
+        let jitter = RandomState::new().build_hasher().finish() % 1000;
+
+        exponential / 2 + exponential / 2 * jitter as u32 / 1000
+    }
+
+    /// Runs the attempt until it succeeds, fails for good or the retries are
+    /// used up. Each attempt gets the time that is left of `total_timeout`,
+    /// at most `timeout`, and counts against `network-concurrency`.
+    pub async fn run<T, F, Fut>(&self, url: &str, mut attempt: F) -> Result<T, NetworkError>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, NetworkError>>,
+    {
+        // Waiting for a permit doesn't count, large installs queue for long
+        let mut spent = Duration::ZERO;
+        let mut tried = 0;
+
+        loop {
+            let timeout = self.timeout.min(self.total_timeout.saturating_sub(spent));
+            let permit = Http::permit().await;
+            let started = Instant::now();
+            let error = match tokio::time::timeout(timeout, attempt()).await {
+                Ok(Ok(result)) => return Ok(result),
+                Ok(Err(error)) => error,
+                Err(_) => NetworkError::Timeout(url.to_string()),
+            };
+            // Others may use the connection while this one waits
+            drop(permit);
+            spent += started.elapsed();
+
+            let delay = error.retry_after().unwrap_or_else(|| self.backoff(tried));
+            if !error.is_retryable() || tried >= self.retries || spent + delay >= self.total_timeout
+            {
+                return Err(error);
+            }
+
+            CraftLogger::verbose(format!("Retrying {} in {:?}: {}", url, delay, error));
+            tokio::time::sleep(delay).await;
+            spent += delay;
+            tried += 1;
+        }
+    }
+}
+
+// ─── Tests ───────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff() {
+        let retry = RetryPolicy::from_config(&Npmrc::parse(
+            "fetch-retries=4\nfetch-retry-mintimeout=100\nfetch-retry-maxtimeout=5000",
+        ));
+        assert_eq!(retry.retries, 4);
+        assert_eq!(retry.timeout, RetryPolicy::default().timeout);
+
+        for (attempt, full) in [(0, 100), (1, 1000), (2, 5000), (9, 5000)] {
+            let backoff = retry.backoff(attempt);
+            let full = Duration::from_millis(full);
+            assert!(backoff >= full / 2 && backoff <= full, "{:?}", backoff);
+        }
+    }
+}
@@ -0,0 +1,164 @@
+use std::fmt::Display;
+use std::process::Command;
+use std::sync::OnceLock;
+
+use nodejs_semver::{Range, Version};
+
+use crate::package::{EnginesType, NpmPackage};
+
+// ─── Engines ─────────────────────────────────────────────────────────────────
+
+/// The versions of the engines the `engines` field of packages is checked
+/// against: the active node, npm and craft itself
+#[derive(Debug)]
+pub struct Engines {
+    node: Option<String>,
+    /// Only asked for when a package wants a npm version
+    npm: OnceLock<Option<String>>,
+    craft: String,
+}
+
+/// A package that wants another version of an engine
+#[derive(Debug, Clone, PartialEq)]
+pub struct EngineMismatch {
+    pub package: String,
+    pub engine: String,
+    pub wanted: String,
+    pub current: String,
+}
+
+// ─────────────────────────────────────────────────────────────────────────────
+
+impl Engines {
+    #[cfg(target_os = "windows")]
+    const NPM: &'static str = "npm.cmd";
+    #[cfg(not(target_os = "windows"))]
+    const NPM: &'static str = "npm";
+
+    pub fn current() -> Self {
+        Self::new(Self::version_of("node"), env!("CARGO_PKG_VERSION"))
+    }
+
+    pub fn new(node: Option<String>, craft: &str) -> Self {
+        Self {
+            node,
+            npm: OnceLock::new(),
+            craft: craft.to_string(),
+        }
+    }
+
+    /// `<program> --version`, without the leading `v` node prints
+    fn version_of(program: &str) -> Option<String> {
+        let output = Command::new(program).arg("--version").output().ok()?;
+        if !output.status.success() {
+            return None;
+        }
+
+        let version = String::from_utf8_lossy(&output.stdout);
+        Some(version.trim().trim_start_matches('v').to_string())
+    }
+
+    fn version(&self, engine: &str) -> Option<&str> {
+        match engine {
+            "node" => self.node.as_deref(),
+            "npm" => self
+                .npm
+                .get_or_init(|| Self::version_of(Self::NPM))
+                .as_deref(),
+            "craft" => Some(&self.craft),
+            _ => None,
+        }
+    }
+
+    /// The engines of the package that are installed in another version
+    /// than it wants. Engines that can't be detected are not checked.
+    pub fn check(&self, package: &NpmPackage) -> Vec<EngineMismatch> {
+        let wanted: Vec<(String, String)> = match &package.engines {
+            Some(EnginesType::EngineMap(engines)) => engines
+                .iter()
+                .map(|(engine, range)| (engine.clone(), range.clone()))
+                .collect(),
+            // The old form, like `["node >= 0.6"]`
+            Some(EnginesType::Engine(engines)) => engines
+                .iter()
+                .filter_map(|e| e.trim().split_once(' '))
+                .map(|(engine, range)| (engine.to_string(), range.trim().to_string()))
+                .collect(),
+            None => vec![],
+        };
+
+        let mut mismatches = wanted
+            .into_iter()
+            .filter_map(|(engine, range)| {
+                let current = self.version(&engine)?;
+                let version = current.parse::<Version>().ok()?;
+                let satisfied = range
+                    .parse::<Range>()
+                    .map_or(true, |r| version.satisfies(&r));
+
+                (!satisfied).then(|| EngineMismatch {
+                    package: package.to_string(),
+                    engine,
+                    wanted: range,
+                    current: current.to_string(),
+                })
+            })
+            .collect::<Vec<_>>();
+        mismatches.sort_by(|a, b| a.engine.cmp(&b.engine));
+
+        mismatches
+    }
+}
+
+impl Display for EngineMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} wants {} {} but {} is installed",
+            self.package, self.engine, self.wanted, self.current
+        )
+    }
+}
+
+// ─── Tests ───────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn package(engines: serde_json::Value) -> NpmPackage {
+        NpmPackage {
+            name: "server".to_string(),
+            version: "1.0.0".to_string(),
+            engines: serde_json::from_value(engines).ok(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_check_engines() {
+        let engines = Engines::new(Some("18.19.0".to_string()), "0.1.0");
+
+        assert!(engines
+            .check(&package(
+                serde_json::json!({"node": ">=16", "craft": "0.x"})
+            ))
+            .is_empty());
+        assert!(engines
+            .check(&package(serde_json::json!(["node >= 0.6"])))
+            .is_empty());
+        assert!(engines
+            .check(&package(serde_json::json!({"deno": "^1"})))
+            .is_empty());
+
+        let mismatches = engines.check(&package(serde_json::json!({
+            "node": "^20.9.0 || >=22",
+            "craft": ">=1"
+        })));
+        assert_eq!(mismatches.len(), 2);
+        assert_eq!(
+            mismatches[1].to_string(),
+            "server@1.0.0 wants node ^20.9.0 || >=22 but 18.19.0 is installed"
+        );
+    }
+}
@@ -9,4 +9,7 @@ use super::NpmPackage;
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct FullPackage {
     pub versions: HashMap<String, NpmPackage>,
+    /// Tags like `latest` or `next` pointing to versions
+    #[serde(rename = "dist-tags", default)]
+    pub dist_tags: HashMap<String, String>,
 }
@@ -4,17 +4,47 @@ use serde::Deserialize;
 
 // ─── PackageJson ─────────────────────────────────────────────────────────────
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct PackageJson {
+    pub name: Option<String>,
+    pub version: Option<String>,
     pub dependencies: Option<HashMap<String, String>>,
     pub dev_dependencies: Option<HashMap<String, String>>,
     pub optional_dependencies: Option<HashMap<String, String>>,
     pub scripts: Option<HashMap<String, String>>,
+    pub workspaces: Option<WorkspacesType>,
+    pub pnpm: Option<PnpmSettings>,
+}
+
+/// The `pnpm` field of package.json
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PnpmSettings {
+    pub only_built_dependencies: Option<Vec<String>>,
+    pub never_built_dependencies: Option<Vec<String>>,
+}
+
+/// `workspaces` is either a list of globs or an object like yarn's
+/// `{"packages": [...], "nohoist": [...]}`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum WorkspacesType {
+    Globs(Vec<String>),
+    Config { packages: Vec<String> },
 }
 
 // ─────────────────────────────────────────────────────────────────────────────
 
+impl WorkspacesType {
+    pub fn globs(&self) -> &[String] {
+        match self {
+            WorkspacesType::Globs(globs) => globs,
+            WorkspacesType::Config { packages } => packages,
+        }
+    }
+}
+
 impl From<String> for PackageJson {
     fn from(s: String) -> Self {
         serde_json::from_str(&s).unwrap()
@@ -0,0 +1,164 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+use serde_json::{ser::PrettyFormatter, Map, Serializer, Value};
+
+use crate::errors::ExecutionError;
+
+pub const DEPENDENCY_SECTIONS: [&str; 3] =
+    ["dependencies", "devDependencies", "optionalDependencies"];
+
+// ─── PackageJsonFile ─────────────────────────────────────────────────────────
+
+/// package.json for editing. Writing it back keeps the order of the keys,
+/// the indentation and the final newline of the file.
+#[derive(Debug)]
+pub struct PackageJsonFile {
+    path: PathBuf,
+    value: Value,
+    indent: String,
+    final_newline: bool,
+}
+
+// ─────────────────────────────────────────────────────────────────────────────
+
+impl PackageJsonFile {
+    pub fn read(directory: &Path) -> Result<Self, ExecutionError> {
+        let path = directory.join("package.json");
+        let content = fs::read_to_string(&path).map_err(|_| ExecutionError::PackageJsonNotFound)?;
+        let value = serde_json::from_str::<Value>(&content).map_err(|e| {
+            ExecutionError::JobExecutionFailed(path.display().to_string(), e.to_string())
+        })?;
+
+        Ok(Self {
+            path,
+            value,
+            indent: Self::detect_indent(&content),
+            final_newline: content.ends_with('\n'),
+        })
+    }
+
+    /// The indentation of the first indented line, two spaces if there is none
+    fn detect_indent(content: &str) -> String {
+        content
+            .lines()
+            .map(|line| &line[..line.len() - line.trim_start().len()])
+            .find(|indent| !indent.is_empty())
+            .unwrap_or("  ")
+            .to_string()
+    }
+
+    /// Removes the dependency from every section, returns whether it was found
+    pub fn remove_dependency(&mut self, name: &str) -> bool {
+        let mut removed = false;
+
+        for section in DEPENDENCY_SECTIONS {
+            if let Some(dependencies) = self.value.get_mut(section).and_then(|s| s.as_object_mut())
+            {
+                // `remove` would move the last key into the gap
+                let before = dependencies.len();
+                dependencies.retain(|key, _| key != name);
+                removed |= dependencies.len() != before;
+            }
+        }
+
+        removed
+    }
+
+    /// Sets the dependency in `section`. A new one is appended, or sorted in
+    /// if the section was sorted before, like npm keeps it.
+    pub fn add_dependency(&mut self, section: &str, name: &str, specifier: &str) {
+        let Some(object) = self.value.as_object_mut() else {
+            return;
+        };
+        let Some(dependencies) = object
+            .entry(section)
+            .or_insert_with(|| Value::Object(Map::new()))
+            .as_object_mut()
+        else {
+            return;
+        };
+
+        let was_sorted = dependencies.keys().is_sorted();
+        dependencies.insert(name.to_string(), Value::String(specifier.to_string()));
+
+        if was_sorted {
+            let mut entries = std::mem::take(dependencies).into_iter().collect::<Vec<_>>();
+            entries.sort_by(|a, b| a.0.cmp(&b.0));
+            dependencies.extend(entries);
+        }
+    }
+
+    pub fn content(&self) -> String {
+        let mut buffer = vec![];
+        let formatter = PrettyFormatter::with_indent(self.indent.as_bytes());
+        let mut serializer = Serializer::with_formatter(&mut buffer, formatter);
+        self.value
+            .serialize(&mut serializer)
+            .expect("Serializing a json value can't fail");
+
+        let mut content = String::from_utf8(buffer).expect("Serialized json is valid utf-8");
+        if self.final_newline {
+            content.push('\n');
+        }
+
+        content
+    }
+
+    pub fn save(&self) -> Result<(), ExecutionError> {
+        fs::write(&self.path, self.content()).map_err(|e| {
+            ExecutionError::JobExecutionFailed(self.path.display().to_string(), e.to_string())
+        })
+    }
+}
+
+// ─── Tests ───────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::fixtures::TempDir;
+
+    fn write_package_json(name: &str, content: &str) -> TempDir {
+        let dir = TempDir::new(&format!("json-file-{}", name));
+        fs::write(dir.join("package.json"), content).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_remove_dependency_keeps_format() {
+        let dir = write_package_json(
+            "remove",
+            "{\n    \"name\": \"app\",\n    \"dependencies\": {\n        \"zod\": \"^3.0.0\",\n        \"lodash\": \"^4.0.0\",\n        \"axios\": \"^1.0.0\"\n    },\n    \"devDependencies\": {\n        \"lodash\": \"^4.0.0\"\n    }\n}\n",
+        );
+
+        let mut file = PackageJsonFile::read(&dir).unwrap();
+        assert!(file.remove_dependency("lodash"));
+        assert!(!file.remove_dependency("react"));
+        file.save().unwrap();
+
+        assert_eq!(
+            fs::read_to_string(dir.join("package.json")).unwrap(),
+            "{\n    \"name\": \"app\",\n    \"dependencies\": {\n        \"zod\": \"^3.0.0\",\n        \"axios\": \"^1.0.0\"\n    },\n    \"devDependencies\": {}\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_add_dependency_keeps_format() {
+        let dir = write_package_json(
+            "add",
+            "{\n\t\"name\": \"app\",\n\t\"dependencies\": {\n\t\t\"axios\": \"^1.0.0\",\n\t\t\"zod\": \"^3.0.0\"\n\t},\n\t\"private\": true\n}",
+        );
+
+        let mut file = PackageJsonFile::read(&dir).unwrap();
+        file.add_dependency("dependencies", "lodash", "^4.17.21");
+        file.add_dependency("devDependencies", "typescript", "~5.6.2");
+        file.save().unwrap();
+
+        assert_eq!(
+            fs::read_to_string(dir.join("package.json")).unwrap(),
+            "{\n\t\"name\": \"app\",\n\t\"dependencies\": {\n\t\t\"axios\": \"^1.0.0\",\n\t\t\"lodash\": \"^4.17.21\",\n\t\t\"zod\": \"^3.0.0\"\n\t},\n\t\"private\": true,\n\t\"devDependencies\": {\n\t\t\"typescript\": \"~5.6.2\"\n\t}\n}"
+        );
+    }
+}
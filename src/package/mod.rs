@@ -1,18 +1,29 @@
+mod engines;
 mod full_package;
 mod git_package;
 mod json;
+mod json_file;
 mod npm_package;
+mod overrides;
 mod package_recorder;
 mod pkg;
+mod platform;
 mod registry;
 
+pub use engines::Engines;
 pub use full_package::FullPackage;
 pub use json::PackageJson;
+pub use json_file::PackageJsonFile;
 pub use npm_package::BinType;
+pub use npm_package::Distribution;
 pub use npm_package::EnginesType;
 pub use npm_package::NpmPackage;
+pub use overrides::Overrides;
 pub use package_recorder::PackageMetaHandler;
 pub use package_recorder::PackageMetaRecorder;
 pub use package_recorder::PackageRecorder;
 pub use package_recorder::ResolvedBinary;
 pub use pkg::Package;
+pub use platform::Platform;
+pub use registry::Registry;
+pub use registry::WORKSPACE_PREFIX;
@@ -1,5 +1,6 @@
 use crate::cache::RegistryKey;
-use crate::package::package_recorder::{PackageMetaRecorder, PackageResolution};
+use crate::lockfile::lockfile_structure::{IntegrityResolution, LockfileResolution};
+use crate::package::package_recorder::PackageMetaRecorder;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fmt::Display;
@@ -65,10 +66,19 @@ pub struct NpmPackage {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub cpu: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub libc: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub private: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub workspaces: Option<Vec<String>>,
+    #[serde(default)]
     pub dist: Distribution,
+    /// Where the package comes from if it is not the tarball of a registry
+    #[serde(skip)]
+    pub resolution: Option<LockfileResolution>,
+    /// The package an `npm:` alias installs under its own name
+    #[serde(skip)]
+    pub alias_of: Option<String>,
     #[serde(skip_serializing)]
     pub depth_traces: Option<Vec<Vec<RegistryKey>>>,
 }
@@ -107,16 +117,23 @@ impl From<NpmPackage> for PackageMetaRecorder {
             peer_dependencies: val.peer_dependencies,
             peer_dependencies_meta: val.peer_dependencies_meta,
             dependencies: val.dependencies,
+            optional_dependencies: val.optional_dependencies,
             cpu: val.cpu,
             engines: val.engines,
             os: val.os,
+            libc: val.libc,
             bin: val.bin.clone(),
             depth_traces: val.depth_traces,
+            alias_of: val.alias_of,
             ..Default::default()
         };
 
-        if let Some(integrity) = val.dist.integrity {
-            meta_recoder.resolution = Some(PackageResolution { integrity })
+        if let Some(resolution) = val.resolution {
+            meta_recoder.resolution = Some(resolution)
+        } else if let Some(integrity) = val.dist.integrity {
+            meta_recoder.resolution = Some(LockfileResolution::Integrity(IntegrityResolution {
+                integrity,
+            }))
         }
         if val.bin.is_some() {
             meta_recoder.has_bin = Some(true)
@@ -179,6 +196,7 @@ impl PartialEq for NpmPackage {
 #[derive(Debug, Deserialize, Serialize, Clone, Default)]
 pub struct Distribution {
     pub integrity: Option<String>,
+    #[serde(default)]
     pub shasum: String,
     pub tarball: String,
 
@@ -199,4 +217,13 @@ impl NpmPackage {
     pub fn contains_org(&self) -> bool {
         self.name.contains('/')
     }
+
+    /// The package that is downloaded, the target of an alias or otherwise
+    /// the package itself
+    pub fn target(&self) -> RegistryKey {
+        RegistryKey {
+            name: self.alias_of.clone().unwrap_or_else(|| self.name.clone()),
+            version: self.version.clone(),
+        }
+    }
 }
@@ -0,0 +1,469 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use nodejs_semver::{Range, Version};
+use serde_json::Value;
+
+use crate::cache::RegistryKey;
+
+/// `pnpm.overrides` removes a dependency with this specifier
+const REMOVE: &str = "-";
+
+// ─── Overrides ───────────────────────────────────────────────────────────────
+
+/// The override rules of the root package.json, from npm's `overrides`,
+/// `pnpm.overrides` and yarn's `resolutions`. They replace the specifier of
+/// transitive dependencies while resolving.
+#[derive(Debug, Clone, Default)]
+pub struct Overrides {
+    rules: Vec<OverrideRule>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct OverrideRule {
+    /// Packages the dependency has to be below, the outermost first
+    parents: Vec<Parent>,
+    target: Selector,
+    specifier: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Parent {
+    selector: Selector,
+    /// The next package has to be a dependency of this one, like pnpm's
+    /// `foo>bar` and yarn's `foo/bar`. Otherwise it may be anywhere below,
+    /// like npm's nested overrides and yarn's `foo/**/bar`.
+    direct: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Selector {
+    name: String,
+    range: Option<String>,
+}
+
+// ─────────────────────────────────────────────────────────────────────────────
+
+impl Selector {
+    /// `name` or `name@range`, the name may be scoped
+    fn parse(selector: &str) -> Self {
+        match selector.get(1..).and_then(|rest| rest.find('@')) {
+            Some(index) => Self {
+                name: selector[..index + 1].to_string(),
+                range: Some(selector[index + 2..].to_string()),
+            },
+            None => Self {
+                name: selector.to_string(),
+                range: None,
+            },
+        }
+    }
+
+    /// Matches a resolved package, e.g. one of the parents
+    fn matches_package(&self, key: &RegistryKey) -> bool {
+        if self.name != key.name {
+            return false;
+        }
+
+        match (&self.range, key.version.parse::<Version>()) {
+            (None, _) => true,
+            (Some(range), Ok(version)) => range
+                .parse::<Range>()
+                .is_ok_and(|range| version.satisfies(&range)),
+            (Some(_), Err(_)) => false,
+        }
+    }
+
+    /// Matches a dependency by the range it asks for
+    fn matches_dependency(&self, name: &str, specifier: &str) -> bool {
+        if self.name != name {
+            return false;
+        }
+
+        match &self.range {
+            None => true,
+            Some(range) => match (range.parse::<Range>(), specifier.parse::<Range>()) {
+                (Ok(range), Ok(requested)) => range.allows_any(&requested),
+                _ => range == specifier,
+            },
+        }
+    }
+}
+
+impl std::fmt::Display for Selector {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.range {
+            Some(range) => write!(f, "{}@{}", self.name, range),
+            None => write!(f, "{}", self.name),
+        }
+    }
+}
+
+impl Parent {
+    fn new(selector: Selector, direct: bool) -> Self {
+        Self { selector, direct }
+    }
+}
+
+impl OverrideRule {
+    fn matches(&self, ancestors: &[RegistryKey], name: &str, specifier: &str) -> bool {
+        self.target.matches_dependency(name, specifier)
+            && Self::matches_parents(&self.parents, ancestors)
+    }
+
+    /// Matches the parents from the innermost one, which has to be the last
+    /// ancestor if it is a direct parent, the outermost may be anywhere
+    fn matches_parents(parents: &[Parent], ancestors: &[RegistryKey]) -> bool {
+        let Some((parent, outer)) = parents.split_last() else {
+            return true;
+        };
+
+        let candidates = match parent.direct {
+            true => ancestors.len().saturating_sub(1)..ancestors.len(),
+            false => 0..ancestors.len(),
+        };
+
+        candidates.rev().any(|index| {
+            parent.selector.matches_package(&ancestors[index])
+                && Self::matches_parents(outer, &ancestors[..index])
+        })
+    }
+
+    /// The pnpm notation of the rule, like `foo@1>bar`
+    fn key(&self) -> String {
+        self.parents
+            .iter()
+            .map(|p| &p.selector)
+            .chain(std::iter::once(&self.target))
+            .map(|s| s.to_string())
+            .collect::<Vec<_>>()
+            .join(">")
+    }
+}
+
+impl Overrides {
+    pub fn load(directory: &Path) -> Self {
+        fs::read_to_string(directory.join("package.json"))
+            .ok()
+            .and_then(|c| serde_json::from_str::<Value>(&c).ok())
+            .map(|manifest| Self::from_manifest(&manifest))
+            .unwrap_or_default()
+    }
+
+    /// pnpm's overrides come first, then npm's and yarn's, the first rule of
+    /// the same selector wins
+    pub fn from_manifest(manifest: &Value) -> Self {
+        let mut rules = vec![];
+
+        if let Some(overrides) = manifest
+            .pointer("/pnpm/overrides")
+            .and_then(|o| o.as_object())
+        {
+            for (selector, specifier) in overrides {
+                if let Some(specifier) = specifier.as_str() {
+                    let mut selectors =
+                        selector.split('>').map(Selector::parse).collect::<Vec<_>>();
+                    let target = selectors.pop().unwrap();
+                    rules.push(OverrideRule {
+                        parents: selectors
+                            .into_iter()
+                            .map(|s| Parent::new(s, true))
+                            .collect(),
+                        target,
+                        specifier: specifier.to_string(),
+                    });
+                }
+            }
+        }
+
+        if let Some(overrides) = manifest.get("overrides") {
+            Self::parse_npm(overrides, &[], &mut rules);
+        }
+
+        if let Some(resolutions) = manifest.get("resolutions").and_then(|r| r.as_object()) {
+            for (pattern, specifier) in resolutions {
+                if let Some(specifier) = specifier.as_str() {
+                    let (parents, target) = Self::parse_yarn_pattern(pattern);
+                    rules.push(OverrideRule {
+                        parents,
+                        target,
+                        specifier: specifier.to_string(),
+                    });
+                }
+            }
+        }
+
+        let mut unique: Vec<OverrideRule> = vec![];
+        for mut rule in rules {
+            rule.specifier = Self::reference(manifest, &rule.target.name, &rule.specifier);
+            if !unique
+                .iter()
+                .any(|r| r.parents == rule.parents && r.target == rule.target)
+            {
+                unique.push(rule);
+            }
+        }
+
+        Self { rules: unique }
+    }
+
+    /// npm nests the rules below the parents, `.` is the parent itself
+    fn parse_npm(value: &Value, parents: &[Parent], rules: &mut Vec<OverrideRule>) {
+        let Some(overrides) = value.as_object() else {
+            return;
+        };
+
+        for (selector, value) in overrides {
+            match value {
+                Value::String(specifier) if selector == "." => {
+                    if let Some((target, parents)) = parents.split_last() {
+                        rules.push(OverrideRule {
+                            parents: parents.to_vec(),
+                            target: target.selector.clone(),
+                            specifier: specifier.clone(),
+                        });
+                    }
+                }
+                Value::String(specifier) => rules.push(OverrideRule {
+                    parents: parents.to_vec(),
+                    target: Selector::parse(selector),
+                    specifier: specifier.clone(),
+                }),
+                Value::Object(_) => {
+                    let mut nested = parents.to_vec();
+                    nested.push(Parent::new(Selector::parse(selector), false));
+                    Self::parse_npm(value, &nested, rules);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Yarn separates the packages by `/`, like `foo/**/@scope/bar`. Without
+    /// `**` between them a package is a dependency of the one before.
+    fn parse_yarn_pattern(pattern: &str) -> (Vec<Parent>, Selector) {
+        let mut parents: Vec<Parent> = vec![];
+        let mut target: Option<Selector> = None;
+        let mut direct = true;
+        let mut segments = pattern.split('/').filter(|s| !s.is_empty());
+
+        while let Some(segment) = segments.next() {
+            if segment == "**" {
+                direct = false;
+                continue;
+            }
+
+            let name = match segment.starts_with('@') {
+                true => format!("{}/{}", segment, segments.next().unwrap_or_default()),
+                false => segment.to_string(),
+            };
+            if let Some(previous) = target.replace(Selector::parse(&name)) {
+                parents.push(Parent::new(previous, direct));
+            }
+            direct = true;
+        }
+
+        (parents, target.unwrap_or_else(|| Selector::parse(pattern)))
+    }
+
+    /// `$name` refers to the specifier of a dependency of the root project
+    fn reference(manifest: &Value, target: &str, specifier: &str) -> String {
+        let Some(name) = specifier.strip_prefix('$') else {
+            return specifier.to_string();
+        };
+        let name = if name.is_empty() { target } else { name };
+
+        [
+            "dependencies",
+            "devDependencies",
+            "optionalDependencies",
+            "peerDependencies",
+        ]
+        .iter()
+        .find_map(|section| manifest.get(section)?.get(name)?.as_str())
+        .unwrap_or(specifier)
+        .to_string()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// The specifier to resolve the dependency with, `None` if an override
+    /// removes it. The rule with the most parents wins.
+    pub fn apply(&self, ancestors: &[RegistryKey], name: &str, specifier: &str) -> Option<String> {
+        let rule = self
+            .rules
+            .iter()
+            .filter(|rule| rule.matches(ancestors, name, specifier))
+            .max_by_key(|rule| (rule.parents.len(), std::cmp::Reverse(self.position(rule))));
+
+        match rule {
+            Some(rule) if rule.specifier == REMOVE => None,
+            Some(rule) => Some(rule.specifier.clone()),
+            None => Some(specifier.to_string()),
+        }
+    }
+
+    fn position(&self, rule: &OverrideRule) -> usize {
+        self.rules
+            .iter()
+            .position(|r| r == rule)
+            .unwrap_or_default()
+    }
+
+    /// The rules as the lockfile stores them
+    pub fn to_map(&self) -> HashMap<String, String> {
+        self.rules
+            .iter()
+            .map(|rule| (rule.key(), rule.specifier.clone()))
+            .collect()
+    }
+}
+
+// ─── Tests ───────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::fixtures::key;
+
+    fn pnpm() -> Overrides {
+        Overrides::from_manifest(&serde_json::json!({
+            "dependencies": {"typescript": "~5.6.0"},
+            "pnpm": {"overrides": {
+                "semver": "7.5.4",
+                "foo@1>bar": "^2",
+                "@scope/qux@<2": "2.0.0",
+                "unwanted": "-",
+                "typescript": "$typescript"
+            }}
+        }))
+    }
+
+    fn npm() -> Overrides {
+        Overrides::from_manifest(&serde_json::json!({
+            "overrides": {
+                "bar": "1.0.0",
+                "foo": {".": "3.0.0", "bar": "2.0.0"}
+            },
+            "resolutions": {"baz/**/@scope/qux": "4.0.0"}
+        }))
+    }
+
+    #[test]
+    fn test_pnpm_overrides() {
+        assert_eq!(pnpm().apply(&[], "semver", "^6.0.0").unwrap(), "7.5.4");
+        assert_eq!(pnpm().to_map()["foo@1>bar"], "^2");
+    }
+
+    #[test]
+    fn test_pnpm_parent_selector() {
+        let overrides = pnpm();
+
+        assert_eq!(
+            overrides
+                .apply(&[key("foo", "1.2.0")], "bar", "^1")
+                .unwrap(),
+            "^2"
+        );
+        assert_eq!(
+            overrides
+                .apply(&[key("foo", "2.0.0")], "bar", "^1")
+                .unwrap(),
+            "^1"
+        );
+        assert_eq!(overrides.apply(&[], "bar", "^1").unwrap(), "^1");
+    }
+
+    #[test]
+    fn test_pnpm_version_selector() {
+        let overrides = pnpm();
+
+        assert_eq!(overrides.apply(&[], "@scope/qux", "^1.1").unwrap(), "2.0.0");
+        assert_eq!(overrides.apply(&[], "@scope/qux", "^3").unwrap(), "^3");
+    }
+
+    #[test]
+    fn test_pnpm_removal() {
+        assert_eq!(pnpm().apply(&[], "unwanted", "*"), None);
+    }
+
+    #[test]
+    fn test_pnpm_reference_to_a_direct_dependency() {
+        assert_eq!(pnpm().apply(&[], "typescript", "^4").unwrap(), "~5.6.0");
+    }
+
+    #[test]
+    fn test_npm_overrides() {
+        let overrides = npm();
+        let ancestors = [key("foo", "3.0.0"), key("other", "1.0.0")];
+
+        assert_eq!(overrides.apply(&[], "bar", "^0.1").unwrap(), "1.0.0");
+        assert_eq!(overrides.apply(&ancestors, "bar", "^0.1").unwrap(), "2.0.0");
+        assert_eq!(overrides.apply(&[], "foo", "^1").unwrap(), "3.0.0");
+    }
+
+    #[test]
+    fn test_yarn_resolutions() {
+        let overrides = npm();
+
+        assert_eq!(
+            overrides
+                .apply(&[key("baz", "1.0.0")], "@scope/qux", "*")
+                .unwrap(),
+            "4.0.0"
+        );
+        assert_eq!(overrides.apply(&[], "@scope/qux", "*").unwrap(), "*");
+        assert_eq!(overrides.to_map()["baz>@scope/qux"], "4.0.0");
+    }
+
+    #[test]
+    fn test_pnpm_parent_has_to_be_the_direct_dependent() {
+        let overrides = Overrides::from_manifest(&serde_json::json!({
+            "pnpm": {"overrides": {"foo>bar": "^2"}}
+        }));
+
+        let direct = [key("app", "1.0.0"), key("foo", "1.0.0")];
+        let transitive = [key("foo", "1.0.0"), key("other", "1.0.0")];
+        assert_eq!(overrides.apply(&direct, "bar", "^1").unwrap(), "^2");
+        assert_eq!(overrides.apply(&transitive, "bar", "^1").unwrap(), "^1");
+    }
+
+    #[test]
+    fn test_yarn_parent_without_globstar_is_the_direct_dependent() {
+        let overrides = Overrides::from_manifest(&serde_json::json!({
+            "resolutions": {"foo/bar": "2.0.0"}
+        }));
+
+        let direct = [key("foo", "1.0.0")];
+        let transitive = [key("foo", "1.0.0"), key("other", "1.0.0")];
+        assert_eq!(overrides.apply(&direct, "bar", "^1").unwrap(), "2.0.0");
+        assert_eq!(overrides.apply(&transitive, "bar", "^1").unwrap(), "^1");
+    }
+
+    #[test]
+    fn test_yarn_globstar_matches_any_depth() {
+        let overrides = Overrides::from_manifest(&serde_json::json!({
+            "resolutions": {"foo/**/bar": "2.0.0", "**/baz": "3.0.0"}
+        }));
+
+        let transitive = [key("foo", "1.0.0"), key("other", "1.0.0")];
+        assert_eq!(overrides.apply(&transitive, "bar", "^1").unwrap(), "2.0.0");
+        assert_eq!(overrides.apply(&[], "bar", "^1").unwrap(), "^1");
+        assert_eq!(overrides.apply(&transitive, "baz", "^1").unwrap(), "3.0.0");
+    }
+
+    #[test]
+    fn test_yarn_mixes_direct_and_any_depth_parents() {
+        let overrides = Overrides::from_manifest(&serde_json::json!({
+            "resolutions": {"foo/**/bar/baz": "2.0.0"}
+        }));
+
+        let matching = [key("foo", "1.0.0"), key("x", "1.0.0"), key("bar", "1.0.0")];
+        let not_direct = [key("foo", "1.0.0"), key("bar", "1.0.0"), key("x", "1.0.0")];
+        assert_eq!(overrides.apply(&matching, "baz", "^1").unwrap(), "2.0.0");
+        assert_eq!(overrides.apply(&not_direct, "baz", "^1").unwrap(), "^1");
+    }
+}
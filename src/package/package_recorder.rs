@@ -1,28 +1,31 @@
-use crate::cache::{RegistryKey, DEP_CACHE_FOLDER};
-use crate::fs::get_config_dir;
+use crate::cache::RegistryKey;
+use crate::lockfile::lockfile_structure::LockfileResolution;
 use crate::package::npm_package::{EnginesType, PeerDependencyMeta};
-use crate::package::BinType;
+use crate::package::{BinType, NpmPackage};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 use std::fmt::{Display, Formatter};
-use std::path::PathBuf;
 
 #[derive(Clone, Default, Debug)]
 pub struct PackageMetaRecorder {
     pub name: String,
     pub version: String,
-    pub resolution: Option<PackageResolution>,
+    pub resolution: Option<LockfileResolution>,
     pub engines: Option<EnginesType>,
     pub peer_dependencies: Option<HashMap<String, String>>,
     pub has_bin: Option<bool>,
     pub peer_dependencies_meta: Option<HashMap<String, PeerDependencyMeta>>,
     pub cpu: Option<Vec<String>>,
     pub os: Option<Vec<String>>,
+    pub libc: Option<Vec<String>>,
     pub dependencies: Option<HashMap<String, String>>,
+    pub optional_dependencies: Option<HashMap<String, String>>,
     pub resolved_dependencies: Option<HashMap<String, String>>,
     pub bin: Option<BinType>,
     pub depth_traces: Option<Vec<Vec<RegistryKey>>>,
     pub resolved_binaries: Option<Vec<ResolvedBinary>>,
+    /// The package an `npm:` alias installs under this name
+    pub alias_of: Option<String>,
 }
 
 #[derive(Clone, Default, Debug, Serialize, Deserialize)]
@@ -32,31 +35,36 @@ pub struct ResolvedBinary {
     pub package_name: String,
 }
 
-impl PackageMetaRecorder {
-    pub fn resolve_path_to_package(&self) -> PathBuf {
-        get_config_dir(DEP_CACHE_FOLDER.clone())
-            .join(format!("{}-{}", self.name, self.version))
-            .join("package")
-    }
-}
-
 impl Display for PackageMetaRecorder {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}@{}", self.name, self.version)
     }
 }
 
+impl PackageMetaRecorder {
+    /// The package that is locked, the target of an alias or otherwise the
+    /// package itself
+    pub fn target(&self) -> RegistryKey {
+        RegistryKey {
+            name: self.alias_of.clone().unwrap_or_else(|| self.name.clone()),
+            version: self.version.clone(),
+        }
+    }
+}
+
 impl From<PackageMetaRecorder> for PackageMetaHandler {
     fn from(val: PackageMetaRecorder) -> Self {
         PackageMetaHandler {
             resolution: val.resolution,
             os: val.os,
             cpu: val.cpu,
+            libc: val.libc,
             has_bin: val.has_bin,
             engines: val.engines,
             peer_dependencies: val.peer_dependencies,
             peer_dependencies_meta: val.peer_dependencies_meta,
             dependencies: val.dependencies,
+            optional_dependencies: val.optional_dependencies,
             resolved_dependencies: val.resolved_dependencies,
             bin: val.bin,
         }
@@ -67,7 +75,7 @@ impl From<PackageMetaRecorder> for PackageMetaHandler {
 #[serde(rename_all = "camelCase")]
 pub struct PackageMetaHandler {
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub resolution: Option<PackageResolution>,
+    pub resolution: Option<LockfileResolution>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub engines: Option<EnginesType>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -81,20 +89,80 @@ pub struct PackageMetaHandler {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub os: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub libc: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub dependencies: Option<HashMap<String, String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub optional_dependencies: Option<HashMap<String, String>>,
     #[serde(skip_serializing)]
     pub resolved_dependencies: Option<HashMap<String, String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub bin: Option<BinType>,
 }
 
-#[derive(Clone, Default, Debug, Deserialize, Serialize)]
-pub struct PackageResolution {
-    pub integrity: String,
-}
-
 #[derive(Clone, Default, Debug)]
 pub struct PackageRecorder {
     pub main_packages: HashMap<RegistryKey, PackageMetaRecorder>,
     pub sub_dependencies: HashMap<RegistryKey, PackageMetaRecorder>,
+    /// Names of optional packages that are not installed on this platform
+    pub skipped_optional: BTreeSet<String>,
+}
+
+impl PackageRecorder {
+    /// Records a resolved package. Packages without a parent are main packages,
+    /// all others are sub dependencies that remember every trace they were reached by.
+    pub fn record(
+        &mut self,
+        key: RegistryKey,
+        package: NpmPackage,
+        parent: &Option<Vec<RegistryKey>>,
+    ) {
+        match parent {
+            None => {
+                // This is okay as we only insert the same version
+                self.main_packages
+                    .entry(key)
+                    .or_insert_with(|| package.into());
+            }
+            // It can be that multiple dependencies have this as a sub dependency
+            Some(parents) => match self.sub_dependencies.get_mut(&key) {
+                Some(p) => p
+                    .depth_traces
+                    .get_or_insert_with(Vec::new)
+                    .push(parents.clone()),
+                None => {
+                    let mut meta: PackageMetaRecorder = package.into();
+                    meta.depth_traces = Some(vec![parents.clone()]);
+                    self.sub_dependencies.insert(key, meta);
+                }
+            },
+        }
+    }
+
+    /// Adds the packages of another recorder, e.g. of another project of the workspace
+    pub fn merge(&mut self, other: PackageRecorder) {
+        for (key, meta) in other.main_packages {
+            self.main_packages.entry(key).or_insert(meta);
+        }
+        for (key, meta) in other.sub_dependencies {
+            self.sub_dependencies.entry(key).or_insert(meta);
+        }
+        self.skipped_optional.extend(other.skipped_optional);
+    }
+
+    /// Forgets an optional package that can't be installed, together with
+    /// the packages only reachable through it
+    pub fn skip_optional(&mut self, key: &RegistryKey) {
+        self.main_packages.remove(key);
+        self.sub_dependencies.remove(key);
+        self.sub_dependencies
+            .retain(|_, meta| match &mut meta.depth_traces {
+                Some(traces) => {
+                    traces.retain(|trace| !trace.contains(key));
+                    !traces.is_empty()
+                }
+                None => true,
+            });
+        self.skipped_optional.insert(key.name.clone());
+    }
 }
@@ -2,7 +2,9 @@ use super::registry::Registry;
 use crate::actors::PackageType;
 use crate::cache::RegistryKey;
 use nodejs_semver::Range;
+use path_clean::clean;
 use std::fmt::Display;
+use std::path::{Path, PathBuf};
 // ─── Package ───────────────────────────────────────────────────────────────────
 
 #[derive(Debug, Clone)]
@@ -11,6 +13,9 @@ pub struct Package {
     pub registry: Registry,
     pub raw_version: String,
     pub package_type: PackageType,
+    /// The folder of the package.json asking for the package, relative to
+    /// the root of the project. Local paths like `file:../foo` start there.
+    pub directory: PathBuf,
 }
 
 impl From<Package> for RegistryKey {
@@ -32,21 +37,83 @@ impl Display for Package {
 
 // ─────────────────────────────────────────────────────────────────────────────
 
+/// Versions like `npm:react@^18` install another package under this name
+const ALIAS_PREFIX: &str = "npm:";
+
 impl Package {
+    /// Whether the version is in the range, a dist-tag satisfies no version
     pub(crate) fn satisfies(&self, version: &str) -> bool {
-        let range: Range = self.raw_version.parse().unwrap();
-        let version: nodejs_semver::Version = version.parse().unwrap();
-        version.satisfies(&range)
+        let (_, range) = self.target();
+        match (
+            range.parse::<Range>(),
+            version.parse::<nodejs_semver::Version>(),
+        ) {
+            (Ok(range), Ok(version)) => version.satisfies(&range),
+            _ => false,
+        }
+    }
+
+    /// The package and the range or dist-tag to look up in the registry,
+    /// the target of an alias or otherwise the package itself
+    pub fn target(&self) -> (String, String) {
+        match self.raw_version.strip_prefix(ALIAS_PREFIX) {
+            Some(target) => PackageType::Prod(target.to_string()).get_parts(),
+            None => (self.name.clone(), self.raw_version.clone()),
+        }
+    }
+
+    pub fn is_alias(&self) -> bool {
+        self.raw_version.starts_with(ALIAS_PREFIX)
     }
 
     pub fn new(package: PackageType) -> Self {
         let binding = package.get_parts();
-
         Self {
             name: binding.0,
-            registry: Registry::Npm,
+            registry: Registry::from_version(&binding.1),
             raw_version: binding.1,
             package_type: package,
+            directory: PathBuf::from("."),
+        }
+    }
+
+    pub fn with_directory(mut self, directory: &Path) -> Self {
+        self.directory = directory.to_path_buf();
+        self
+    }
+
+    /// A local path of the package.json relative to the root of the project,
+    /// e.g. `../utils` of `packages/app` becomes `packages/utils`
+    pub fn root_relative(&self, path: &str) -> String {
+        clean(self.directory.join(path))
+            .to_string_lossy()
+            .replace('\\', "/")
+    }
+
+    /// Only packages of the npm registry have semver versions, all others
+    /// are resolved from their location
+    pub fn is_npm(&self) -> bool {
+        self.registry == Registry::Npm
+    }
+
+    /// What to write to package.json for the resolved `version`. Ranges the
+    /// user asked for are kept, no range or a dist-tag become `prefix` +
+    /// version, an empty prefix saves the exact version.
+    pub fn save_specifier(&self, version: &str, prefix: &str) -> String {
+        if !self.is_npm() {
+            return self.raw_version.clone();
+        }
+
+        let (target, range) = self.target();
+        let is_range = range != "*" && range.parse::<Range>().is_ok();
+        let specifier = match is_range && !prefix.is_empty() {
+            true => range,
+            false => format!("{}{}", prefix, version),
+        };
+
+        match self.is_alias() {
+            true => format!("{}{}@{}", ALIAS_PREFIX, target, specifier),
+            false => specifier,
         }
     }
 }
@@ -68,6 +135,45 @@ impl Package {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_save_specifier() {
+        let specifier = |spec: &str, prefix: &str| {
+            Package::new(PackageType::Prod(spec.to_string())).save_specifier("4.17.21", prefix)
+        };
+
+        assert_eq!(specifier("lodash", "^"), "^4.17.21");
+        assert_eq!(specifier("lodash@latest", "~"), "~4.17.21");
+        assert_eq!(specifier("lodash@~4.17.0", "^"), "~4.17.0");
+        assert_eq!(specifier("lodash@4.17.21", "^"), "4.17.21");
+        assert_eq!(specifier("lodash@^4", ""), "4.17.21");
+        assert_eq!(
+            specifier("lodash@github:lodash/lodash#4.17.21", ""),
+            "github:lodash/lodash#4.17.21"
+        );
+        assert_eq!(specifier("_@npm:lodash@^4.17.0", "^"), "npm:lodash@^4.17.0");
+        assert_eq!(specifier("_@npm:lodash@next", "~"), "npm:lodash@~4.17.21");
+    }
+
+    #[test]
+    fn test_alias_target() {
+        let package = Package::new(PackageType::Prod("my-react@npm:react@^18".to_string()));
+        assert_eq!(package.name, "my-react");
+        assert_eq!(package.registry, Registry::Npm);
+        assert!(package.is_alias());
+        assert_eq!(package.target(), ("react".to_string(), "^18".to_string()));
+        assert!(package.satisfies("18.2.0"));
+
+        let package = Package::new(PackageType::Prod("types@npm:@types/node".to_string()));
+        assert_eq!(
+            package.target(),
+            ("@types/node".to_string(), "*".to_string())
+        );
+
+        let package = Package::new(PackageType::Prod("typescript@next".to_string()));
+        assert!(!package.is_alias());
+        assert!(!package.satisfies("5.7.0"));
+    }
+
     #[test]
     fn test_package_new() {
         let package = Package::new(PackageType::Dev("lodash@4.17.21".to_string()));
@@ -85,4 +191,32 @@ mod tests {
         assert_eq!(package.registry, Registry::Npm);
         assert_eq!(package.raw_version, "*");
     }
+
+    #[test]
+    fn test_root_relative() {
+        let package = Package::new(PackageType::Prod("utils@file:../utils".to_string()));
+        assert_eq!(package.root_relative("../utils"), "../utils");
+
+        let package = package.with_directory(Path::new("packages/app"));
+        assert_eq!(package.root_relative("../utils"), "packages/utils");
+        assert_eq!(
+            package.root_relative("vendor/a.tgz"),
+            "packages/app/vendor/a.tgz"
+        );
+        assert_eq!(package.root_relative("/opt/utils"), "/opt/utils");
+    }
+
+    #[test]
+    fn test_package_new_git() {
+        let package = Package::new(PackageType::Prod(
+            "repo@git+ssh://git@github.com/owner/repo.git#v1".to_string(),
+        ));
+        assert_eq!(package.name, "repo");
+        assert_eq!(package.registry, Registry::Git);
+        assert_eq!(
+            package.raw_version,
+            "git+ssh://git@github.com/owner/repo.git#v1"
+        );
+        assert!(!package.is_npm());
+    }
 }
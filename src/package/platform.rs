@@ -0,0 +1,141 @@
+use std::env::consts;
+
+use crate::config::NPMRC;
+use crate::package::NpmPackage;
+
+// ─── Platform ────────────────────────────────────────────────────────────────
+
+/// The os, cpu and libc packages are installed for, in the names node uses.
+/// `--os`, `--cpu` or the `os`/`cpu` settings of .npmrc install for another
+/// platform than the current one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Platform {
+    pub os: String,
+    pub cpu: String,
+    /// Only known on linux, `glibc` or `musl`
+    pub libc: Option<String>,
+}
+
+// ─────────────────────────────────────────────────────────────────────────────
+
+impl Platform {
+    pub fn current() -> Self {
+        let os = match consts::OS {
+            "macos" => "darwin",
+            "windows" => "win32",
+            "solaris" | "illumos" => "sunos",
+            os => os,
+        };
+        let cpu = match consts::ARCH {
+            "x86_64" => "x64",
+            "x86" => "ia32",
+            "aarch64" => "arm64",
+            "powerpc" => "ppc",
+            "powerpc64" => "ppc64",
+            "loongarch64" => "loong64",
+            arch => arch,
+        };
+        let libc = match (os, cfg!(target_env = "musl")) {
+            ("linux", true) => Some("musl"),
+            ("linux", false) => Some("glibc"),
+            _ => None,
+        };
+
+        Self {
+            os: os.to_string(),
+            cpu: cpu.to_string(),
+            libc: libc.map(str::to_string),
+        }
+        .with_os(NPMRC.get("os").map(str::to_string))
+        .with_cpu(NPMRC.get("cpu").map(str::to_string))
+    }
+
+    pub fn with_os(mut self, os: Option<String>) -> Self {
+        if let Some(os) = os {
+            // The libc of another os is unknown
+            if os != self.os {
+                self.libc = None;
+            }
+            self.os = os;
+        }
+        self
+    }
+
+    pub fn with_cpu(mut self, cpu: Option<String>) -> Self {
+        if let Some(cpu) = cpu {
+            self.cpu = cpu;
+        }
+        self
+    }
+
+    /// Whether the `os`, `cpu` and `libc` fields of the package allow this platform
+    pub fn supports(&self, package: &NpmPackage) -> bool {
+        Self::allows(package.os.as_deref(), Some(&self.os))
+            && Self::allows(package.cpu.as_deref(), Some(&self.cpu))
+            && Self::allows(package.libc.as_deref(), self.libc.as_deref())
+    }
+
+    /// Like npm, entries may be negated with `!` and `any` allows everything
+    fn allows(list: Option<&[String]>, value: Option<&str>) -> bool {
+        let Some(list) = list.filter(|l| !l.is_empty()) else {
+            return true;
+        };
+
+        let mut has_positive = false;
+        let mut matches = false;
+        for entry in list {
+            match entry.strip_prefix('!') {
+                Some(negated) if Some(negated) == value => return false,
+                Some(_) => {}
+                None => {
+                    has_positive = true;
+                    matches |= entry == "any" || Some(entry.as_str()) == value;
+                }
+            }
+        }
+
+        !has_positive || matches
+    }
+}
+
+// ─── Tests ───────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn package(os: &[&str], cpu: &[&str], libc: &[&str]) -> NpmPackage {
+        let list = |l: &[&str]| Some(l.iter().map(|s| s.to_string()).collect());
+        NpmPackage {
+            name: "native".to_string(),
+            version: "1.0.0".to_string(),
+            os: list(os),
+            cpu: list(cpu),
+            libc: list(libc),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_supports() {
+        let linux = Platform {
+            os: "linux".to_string(),
+            cpu: "x64".to_string(),
+            libc: Some("glibc".to_string()),
+        };
+
+        assert!(linux.supports(&package(&[], &[], &[])));
+        assert!(linux.supports(&package(&["linux"], &["x64", "arm64"], &["glibc"])));
+        assert!(!linux.supports(&package(&["linux"], &["x64"], &["musl"])));
+        assert!(!linux.supports(&package(&["darwin"], &[], &[])));
+        assert!(linux.supports(&package(&["!win32"], &["any"], &[])));
+        assert!(!linux.supports(&package(&["!linux"], &[], &[])));
+
+        let mac = linux
+            .with_os(Some("darwin".to_string()))
+            .with_cpu(Some("arm64".to_string()));
+        assert_eq!(mac.libc, None);
+        assert!(mac.supports(&package(&["darwin"], &["arm64"], &[])));
+        assert!(!mac.supports(&package(&["linux"], &["arm64"], &["glibc"])));
+    }
+}
@@ -2,15 +2,53 @@
 
 use std::fmt::Display;
 
+pub const WORKSPACE_PREFIX: &str = "workspace:";
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Registry {
     Npm,
     Git,
+    Tarball,
+    Directory,
+    Workspace,
 }
 
 // ─────────────────────────────────────────────────────────────────────────────
 
 impl Registry {
+    /// Picks the registry that can resolve the version of a dependency
+    pub fn from_version(version: &str) -> Self {
+        if version.starts_with(WORKSPACE_PREFIX) {
+            Registry::Workspace
+        } else if Self::is_tarball(version) {
+            Registry::Tarball
+        } else if Self::is_directory(version) {
+            Registry::Directory
+        } else if Self::is_git(version) {
+            Registry::Git
+        } else {
+            Registry::Npm
+        }
+    }
+
+    /// A remote tarball like `https://host/foo-1.0.0.tgz` or a local one
+    /// like `file:../foo.tgz`
+    pub fn is_tarball(version: &str) -> bool {
+        let location = version.split('#').next().unwrap_or(version);
+        let is_url = ["file:", "http://", "https://"]
+            .iter()
+            .any(|&prefix| location.starts_with(prefix));
+
+        is_url
+            && [".tgz", ".tar.gz", ".tar"]
+                .iter()
+                .any(|&extension| location.ends_with(extension))
+    }
+
+    pub fn is_directory(version: &str) -> bool {
+        version.starts_with("link:") || (version.starts_with("file:") && !Self::is_tarball(version))
+    }
+
     pub fn is_git(version: &str) -> bool {
         let git_prefixes = [
             "git+",
@@ -39,6 +77,9 @@ impl Display for Registry {
         let str = match self {
             Registry::Npm => "npm".to_string(),
             Registry::Git => "git".to_string(),
+            Registry::Tarball => "tarball".to_string(),
+            Registry::Directory => "directory".to_string(),
+            Registry::Workspace => "workspace".to_string(),
         };
         write!(f, "{}", str)
     }
@@ -74,9 +115,33 @@ mod tests {
         assert!(!Registry::is_git("> 2"));
     }
 
+    #[test]
+    fn test_from_version() {
+        assert_eq!(Registry::from_version("^1.0.0"), Registry::Npm);
+        assert_eq!(
+            Registry::from_version("github:owner/repo#v1"),
+            Registry::Git
+        );
+        assert_eq!(
+            Registry::from_version("https://github.com/owner/repo.git"),
+            Registry::Git
+        );
+        assert_eq!(
+            Registry::from_version("https://host/foo-1.0.0.tgz"),
+            Registry::Tarball
+        );
+        assert_eq!(Registry::from_version("file:../foo.tgz"), Registry::Tarball);
+        assert_eq!(Registry::from_version("file:../foo"), Registry::Directory);
+        assert_eq!(Registry::from_version("link:../foo"), Registry::Directory);
+        assert_eq!(Registry::from_version("workspace:^"), Registry::Workspace);
+    }
+
     #[test]
     fn test_to_string() {
         assert_eq!(Registry::Npm.to_string(), "npm");
         assert_eq!(Registry::Git.to_string(), "git");
+        assert_eq!(Registry::Tarball.to_string(), "tarball");
+        assert_eq!(Registry::Directory.to_string(), "directory");
+        assert_eq!(Registry::Workspace.to_string(), "workspace");
     }
 }
@@ -0,0 +1,123 @@
+use std::collections::HashMap;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::{env, fs, process};
+
+use super::{ExtractArtifacts, ExtractArtifactsMap, ResolvedItem};
+use crate::actors::PackageType;
+use crate::cache::RegistryKey;
+use crate::package::{NpmPackage, PackageRecorder};
+
+// ─── Fixtures ────────────────────────────────────────────────────────────────
+
+/// An empty folder below the temp dir, removed again when the test ends
+/// even if it panics
+pub struct TempDir {
+    path: PathBuf,
+}
+
+pub fn key(name: &str, version: &str) -> RegistryKey {
+    RegistryKey {
+        name: name.to_string(),
+        version: version.to_string(),
+    }
+}
+
+/// A production dependency below the given ancestors
+pub fn item(name: &str, version: &str, parent: &[RegistryKey]) -> ResolvedItem {
+    let package = NpmPackage {
+        name: name.to_string(),
+        version: version.to_string(),
+        ..Default::default()
+    };
+    let parent = (!parent.is_empty()).then(|| parent.to_vec());
+    ResolvedItem::new(
+        package,
+        parent,
+        version.to_string(),
+        PackageType::Prod(format!("{}@{}", name, version)),
+    )
+}
+
+/// Records the packages like the resolver does
+pub fn recorded(items: &[ResolvedItem]) -> PackageRecorder {
+    let mut recorder = PackageRecorder::default();
+    for item in items {
+        recorder.record(
+            item.package.clone().into(),
+            item.package.clone(),
+            &item.parent,
+        );
+    }
+    recorder
+}
+
+/// Pretends that the given packages were downloaded and extracted
+pub fn extracted(items: &[ResolvedItem]) -> ExtractArtifactsMap {
+    items
+        .iter()
+        .map(|item| {
+            let artifact = ExtractArtifacts::to_artifact(item.package.clone(), Default::default());
+            (item.package.to_string(), artifact)
+        })
+        .collect()
+}
+
+impl TempDir {
+    /// `name` has to be unique among the tests running at the same time
+    pub fn new(name: &str) -> Self {
+        let path = env::temp_dir().join(format!("craft-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        Self { path }
+    }
+}
+
+impl Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+/// `None` for no entries, like packages without the field
+fn map(entries: &[(&str, &str)]) -> Option<HashMap<String, String>> {
+    (!entries.is_empty()).then(|| {
+        entries
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    })
+}
+
+// ─────────────────────────────────────────────────────────────────────────────
+
+impl ResolvedItem {
+    pub fn with_type(mut self, package_type: PackageType) -> Self {
+        self.package_type = package_type;
+        self
+    }
+
+    /// The range it was asked for, the version by default
+    pub fn with_specifier(mut self, specifier: &str) -> Self {
+        self.specifier = specifier.to_string();
+        self
+    }
+
+    pub fn with_dependencies(mut self, dependencies: &[(&str, &str)]) -> Self {
+        self.package.dependencies = map(dependencies);
+        self
+    }
+
+    pub fn with_peers(mut self, peers: &[(&str, &str)]) -> Self {
+        self.package.peer_dependencies = map(peers);
+        self
+    }
+}
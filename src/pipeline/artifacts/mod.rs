@@ -1,9 +1,9 @@
 mod download_artifacts;
 mod extract_artifacts;
-mod linker_artifacts;
+#[cfg(test)]
+pub mod fixtures;
 mod resolve_artifacts;
 
 pub use download_artifacts::{DownloadArtifacts, StoredArtifact};
 pub use extract_artifacts::{ExtractArtifacts, ExtractArtifactsMap};
-pub use linker_artifacts::LinkArtifactItem;
 pub use resolve_artifacts::{ResolveArtifacts, ResolvedItem};
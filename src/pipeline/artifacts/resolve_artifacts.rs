@@ -39,6 +39,10 @@ impl ResolvedItem {
         }
     }
 
+    pub fn is_optional(&self) -> bool {
+        matches!(self.package_type, PackageType::Optional(_))
+    }
+
     #[cfg(test)]
     pub fn with_no_parent(
         package: NpmPackage,
@@ -62,11 +66,26 @@ impl ResolveArtifacts {
         self.packages.get(key)
     }
 
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut ResolvedItem> {
+        self.packages.get_mut(key)
+    }
+
     pub fn insert(&mut self, key: String, value: ResolvedItem) {
         self.packages.insert(key, value);
     }
 }
 
+impl FromIterator<ResolvedItem> for ResolveArtifacts {
+    fn from_iter<I: IntoIterator<Item = ResolvedItem>>(iter: I) -> Self {
+        Self {
+            packages: iter
+                .into_iter()
+                .map(|item| (item.package.to_string(), item))
+                .collect(),
+        }
+    }
+}
+
 // --------------------------------------------------------------------------------
 
 impl PipeArtifact<Vec<ResolvedItem>> for ResolveArtifacts {
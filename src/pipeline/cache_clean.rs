@@ -1,7 +1,7 @@
 use async_trait::async_trait;
 
 use crate::{
-    cache::{PackagesCache, RegistryCache},
+    cache::{PackagesCache, PackumentCache, RegistryCache},
     command::CacheAction,
     contracts::{PersistentCache, Pipe},
     errors::ExecutionError,
@@ -26,6 +26,7 @@ impl Pipe<()> for CacheCleanPipe {
             CacheAction::Clean => {
                 let _ = PackagesCache::default().clean().await;
                 let _ = RegistryCache::default().clean().await;
+                let _ = PackumentCache::default().clean().await;
             }
         }
 
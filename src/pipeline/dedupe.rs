@@ -0,0 +1,459 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt::Display;
+
+use nodejs_semver::{Range, Version};
+
+use super::artifacts::ResolvedItem;
+use crate::cache::RegistryKey;
+use crate::package::{Overrides, PackageRecorder};
+
+// ─── Dedupe ──────────────────────────────────────────────────────────────────
+
+/// Minimizes the versions of the resolved packages. Every dependency is
+/// resolved to the highest version of its own range, so `foo@^1.0` and
+/// `foo@^1.2` may end up as two versions by accident. Of the resolved
+/// versions the fewest that satisfy all ranges asking for a package are kept,
+/// preferring the versions of the lockfile, and the others are dropped
+/// together with everything only they depend on.
+#[derive(Debug, Default)]
+pub struct Dedupe {
+    overrides: Overrides,
+    /// `name@version` of the packages of the lockfile
+    locked: HashSet<String>,
+    /// The versions to keep of every package resolved to several versions
+    kept: HashMap<String, Vec<String>>,
+}
+
+/// A dependency that is installed in another version
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct DedupeChange {
+    pub name: String,
+    pub from: String,
+    pub to: String,
+}
+
+/// The packages below every path of packages of a resolution
+struct Graph<'a> {
+    items: HashMap<RegistryKey, &'a ResolvedItem>,
+    /// The dependencies of the last package of a path
+    children: HashMap<Vec<RegistryKey>, Vec<RegistryKey>>,
+    /// The shortest path to every package, including the package itself
+    shortest: HashMap<RegistryKey, Vec<RegistryKey>>,
+}
+
+/// A resolution without the dropped versions
+#[derive(Default)]
+struct Rebuild {
+    recorder: PackageRecorder,
+    top_level: HashMap<RegistryKey, ResolvedItem>,
+    parents: HashMap<RegistryKey, Vec<Vec<RegistryKey>>>,
+    changes: BTreeSet<DedupeChange>,
+}
+
+// ─────────────────────────────────────────────────────────────────────────────
+
+impl Dedupe {
+    pub fn new(overrides: Overrides) -> Self {
+        Self {
+            overrides,
+            ..Default::default()
+        }
+    }
+
+    /// Prefers these `name@version`s, e.g. the packages of the lockfile
+    pub fn with_locked(mut self, locked: impl IntoIterator<Item = String>) -> Self {
+        self.locked = locked.into_iter().collect();
+        self
+    }
+
+    /// Dedupes the resolved packages and the recorder in place and returns
+    /// which dependencies moved to another version
+    pub fn run(
+        &mut self,
+        resolved: &mut Vec<ResolvedItem>,
+        recorder: &mut PackageRecorder,
+    ) -> Vec<DedupeChange> {
+        self.kept.clear();
+        let graph = Graph::new(resolved, recorder);
+
+        let mut requests: BTreeMap<String, Vec<(Option<Range>, String)>> = BTreeMap::new();
+        for item in resolved.iter().filter(|item| item.parent.is_none()) {
+            requests
+                .entry(item.package.name.clone())
+                .or_default()
+                .push((item.specifier.parse().ok(), item.package.version.clone()));
+        }
+        for (path, children) in &graph.children {
+            for child in children {
+                requests
+                    .entry(child.name.clone())
+                    .or_default()
+                    .push((self.range(&graph, path, &child.name), child.version.clone()));
+            }
+        }
+
+        for (name, requests) in requests {
+            let versions = requests.iter().map(|(_, v)| v).collect::<HashSet<_>>();
+            if versions.len() > 1 {
+                let kept = self.choose(&name, &requests);
+                self.kept.insert(name, kept);
+            }
+        }
+        if self.kept.is_empty() {
+            return vec![];
+        }
+
+        let rebuild = self.rebuild(&graph, resolved, recorder);
+        if rebuild.changes.is_empty() {
+            return vec![];
+        }
+
+        *resolved = rebuild.items(&graph);
+        let changes = rebuild.changes.into_iter().collect();
+        *recorder = rebuild.recorder;
+
+        changes
+    }
+
+    /// The range a dependency is resolved with below the path, `None` if it
+    /// isn't a semver range
+    fn range(&self, graph: &Graph, path: &[RegistryKey], name: &str) -> Option<Range> {
+        let dependent = &graph.items.get(path.last()?)?.package;
+        let specifier = [
+            &dependent.dependencies,
+            &dependent.optional_dependencies,
+            &dependent.peer_dependencies,
+        ]
+        .into_iter()
+        .flatten()
+        .find_map(|dependencies| dependencies.get(name))?;
+
+        self.overrides.apply(path, name, specifier)?.parse().ok()
+    }
+
+    /// The versions to keep for the requests of a package. Requests that
+    /// aren't semver ranges keep their version, for the others the version
+    /// satisfying the most open requests is kept until all are satisfied.
+    fn choose(&self, name: &str, requests: &[(Option<Range>, String)]) -> Vec<String> {
+        let mut kept = vec![];
+        let mut open = vec![];
+        for (range, version) in requests {
+            match (range, version.parse::<Version>()) {
+                (Some(range), Ok(_)) => open.push((range, version)),
+                _ => kept.push(version.clone()),
+            }
+        }
+        let candidates = open
+            .iter()
+            .filter_map(|(_, v)| Some((v.parse::<Version>().ok()?, v.to_string())))
+            .collect::<BTreeMap<_, _>>();
+        let satisfies = |version: &str, range: &Range| {
+            version
+                .parse::<Version>()
+                .is_ok_and(|version| version.satisfies(range))
+        };
+
+        open.retain(|(range, _)| !kept.iter().any(|v| satisfies(v, range)));
+        while !open.is_empty() {
+            let best = candidates
+                .iter()
+                .filter(|(_, v)| !kept.contains(v))
+                .map(|(version, v)| {
+                    let count = open.iter().filter(|(r, _)| version.satisfies(r)).count();
+                    (count, self.is_locked(name, v), version, v)
+                })
+                .max();
+
+            match best {
+                Some((count, _, _, version)) if count > 0 => {
+                    open.retain(|(range, _)| !satisfies(version, range));
+                    kept.push(version.clone());
+                }
+                // Nothing resolved satisfies them, they keep their version
+                _ => {
+                    kept.extend(open.drain(..).map(|(_, v)| v.clone()));
+                }
+            }
+        }
+
+        kept.sort();
+        kept.dedup();
+        kept
+    }
+
+    fn is_locked(&self, name: &str, version: &str) -> bool {
+        self.locked.contains(&format!("{}@{}", name, version))
+    }
+
+    /// The version a request is installed in, the one it was resolved to if
+    /// that is kept
+    fn pick(&self, key: &RegistryKey, range: Option<&Range>) -> RegistryKey {
+        let Some(kept) = self.kept.get(&key.name) else {
+            return key.clone();
+        };
+        if kept.contains(&key.version) {
+            return key.clone();
+        }
+
+        let picked = range.and_then(|range| {
+            kept.iter()
+                .filter_map(|v| Some((v.parse::<Version>().ok()?, v)))
+                .filter(|(version, _)| version.satisfies(range))
+                .max_by_key(|(version, v)| (self.is_locked(&key.name, v), version.clone()))
+        });
+
+        match picked {
+            Some((_, version)) => RegistryKey {
+                name: key.name.clone(),
+                version: version.clone(),
+            },
+            None => key.clone(),
+        }
+    }
+
+    /// Walks the resolution from the top level packages again, every
+    /// dependency points to the version picked for it
+    fn rebuild(
+        &self,
+        graph: &Graph,
+        resolved: &[ResolvedItem],
+        recorder: &PackageRecorder,
+    ) -> Rebuild {
+        let mut rebuild = Rebuild::default();
+        rebuild.recorder.skipped_optional = recorder.skipped_optional.clone();
+
+        let mut roots = recorder.main_packages.keys().cloned().collect::<Vec<_>>();
+        roots.sort_by_key(|key| key.to_string());
+        for root in roots {
+            let top_level = resolved.iter().find(|item| {
+                item.parent.is_none() && RegistryKey::from(item.package.clone()) == root
+            });
+
+            let key = match top_level {
+                Some(item) => {
+                    let key = self.pick(&root, item.specifier.parse().ok().as_ref());
+                    let Some(&picked) = graph.items.get(&key) else {
+                        continue;
+                    };
+                    let mut picked = picked.clone();
+                    picked.parent = None;
+                    picked.specifier = item.specifier.clone();
+                    picked.package_type = item.package_type.clone();
+                    rebuild.top_level.insert(key.clone(), picked);
+                    rebuild.change(&root, &key);
+                    key
+                }
+                None => root,
+            };
+
+            if let Some(item) = graph.items.get(&key) {
+                rebuild
+                    .recorder
+                    .record(key.clone(), item.package.clone(), &None);
+            }
+            self.visit(graph, &key, vec![], &mut rebuild);
+        }
+
+        rebuild
+    }
+
+    fn visit(
+        &self,
+        graph: &Graph,
+        key: &RegistryKey,
+        mut path: Vec<RegistryKey>,
+        rebuild: &mut Rebuild,
+    ) {
+        path.push(key.clone());
+        // A package reached on a new path has the dependencies it had before
+        let children = graph.children.get(&path).or_else(|| {
+            graph
+                .shortest
+                .get(key)
+                .and_then(|shortest| graph.children.get(shortest))
+        });
+
+        for child in children.cloned().unwrap_or_default() {
+            let range = self.range(graph, &path, &child.name);
+            let picked = self.pick(&child, range.as_ref());
+            let Some(item) = graph.items.get(&picked) else {
+                continue;
+            };
+
+            rebuild.change(&child, &picked);
+            rebuild
+                .recorder
+                .record(picked.clone(), item.package.clone(), &Some(path.clone()));
+            rebuild
+                .parents
+                .entry(picked.clone())
+                .or_default()
+                .push(path.clone());
+
+            // Circular dependencies are walked once
+            if !path.contains(&picked) {
+                self.visit(graph, &picked, path.clone(), rebuild);
+            }
+        }
+    }
+}
+
+impl<'a> Graph<'a> {
+    fn new(resolved: &'a [ResolvedItem], recorder: &PackageRecorder) -> Self {
+        let items = resolved
+            .iter()
+            .map(|item| (item.package.clone().into(), item))
+            .collect::<HashMap<RegistryKey, _>>();
+
+        let mut children: HashMap<Vec<RegistryKey>, Vec<RegistryKey>> = HashMap::new();
+        let mut shortest: HashMap<RegistryKey, Vec<RegistryKey>> = recorder
+            .main_packages
+            .keys()
+            .map(|key| (key.clone(), vec![key.clone()]))
+            .collect();
+
+        for (key, meta) in &recorder.sub_dependencies {
+            for trace in meta.depth_traces.iter().flatten() {
+                children.entry(trace.clone()).or_default().push(key.clone());
+
+                let mut path = trace.clone();
+                path.push(key.clone());
+                let is_shorter = shortest
+                    .get(key)
+                    .is_none_or(|shortest| path.len() < shortest.len());
+                if is_shorter {
+                    shortest.insert(key.clone(), path);
+                }
+            }
+        }
+        for dependencies in children.values_mut() {
+            dependencies.sort_by_key(|key| key.to_string());
+        }
+
+        Self {
+            items,
+            children,
+            shortest,
+        }
+    }
+}
+
+impl Rebuild {
+    fn change(&mut self, from: &RegistryKey, to: &RegistryKey) {
+        if from != to {
+            self.changes.insert(DedupeChange {
+                name: from.name.clone(),
+                from: from.version.clone(),
+                to: to.version.clone(),
+            });
+        }
+    }
+
+    /// The resolved packages, each one stays where it was installed before
+    /// if that path still exists, otherwise it moves to the shortest path
+    fn items(&self, graph: &Graph) -> Vec<ResolvedItem> {
+        let mut items = self.top_level.values().cloned().collect::<Vec<_>>();
+
+        for (key, parents) in &self.parents {
+            if self.top_level.contains_key(key) {
+                continue;
+            }
+            let Some(&item) = graph.items.get(key) else {
+                continue;
+            };
+
+            let mut item = item.clone();
+            let kept_parent = item.parent.as_ref().filter(|p| parents.contains(p));
+            if kept_parent.is_none() {
+                item.parent = parents.iter().min_by_key(|p| p.len()).cloned();
+            }
+            items.push(item);
+        }
+
+        items
+    }
+}
+
+impl Display for DedupeChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {} → {}", self.name, self.from, self.to)
+    }
+}
+
+// ─── Tests ───────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::fixtures::{item, key, recorded};
+
+    fn resolution() -> (Vec<ResolvedItem>, PackageRecorder) {
+        let resolved = vec![
+            item("a", "1.0.0", &[]).with_dependencies(&[("foo", "^1.0.0")]),
+            item("b", "1.0.0", &[]).with_dependencies(&[("foo", "^1.2.0")]),
+            item("foo", "1.0.5", &[key("a", "1.0.0")])
+                .with_specifier("^1.0.0")
+                .with_dependencies(&[("bar", "^1.0.0")]),
+            item("foo", "1.3.0", &[key("b", "1.0.0")]).with_specifier("^1.2.0"),
+            item("bar", "1.0.0", &[key("a", "1.0.0"), key("foo", "1.0.5")])
+                .with_specifier("^1.0.0"),
+        ];
+        let recorder = recorded(&resolved);
+
+        (resolved, recorder)
+    }
+
+    #[test]
+    fn test_dedupe() {
+        let (mut resolved, mut recorder) = resolution();
+        let changes = Dedupe::default().run(&mut resolved, &mut recorder);
+
+        assert_eq!(
+            changes,
+            vec![DedupeChange {
+                name: "foo".to_string(),
+                from: "1.0.5".to_string(),
+                to: "1.3.0".to_string(),
+            }]
+        );
+        assert_eq!(changes[0].to_string(), "foo 1.0.5 → 1.3.0");
+
+        let mut installed = resolved
+            .iter()
+            .map(|item| item.package.to_string())
+            .collect::<Vec<_>>();
+        installed.sort();
+        assert_eq!(installed, vec!["a@1.0.0", "b@1.0.0", "foo@1.3.0"]);
+
+        let foo = &recorder.sub_dependencies[&key("foo", "1.3.0")];
+        assert_eq!(
+            foo.depth_traces,
+            Some(vec![vec![key("a", "1.0.0")], vec![key("b", "1.0.0")]])
+        );
+        assert_eq!(recorder.sub_dependencies.len(), 1);
+    }
+
+    #[test]
+    fn test_dedupe_keeps_ranges_that_do_not_overlap() {
+        let (mut resolved, mut recorder) = resolution();
+        resolved[0].package.dependencies = Some([("foo".into(), "~1.0.0".into())].into());
+
+        assert!(Dedupe::default()
+            .run(&mut resolved, &mut recorder)
+            .is_empty());
+        assert_eq!(resolved.len(), 5);
+    }
+
+    #[test]
+    fn test_dedupe_prefers_the_locked_version() {
+        let (mut resolved, mut recorder) = resolution();
+        resolved[1].package.dependencies = Some([("foo".into(), "^1.0.0".into())].into());
+
+        let changes = Dedupe::default()
+            .with_locked(["foo@1.0.5".to_string()])
+            .run(&mut resolved, &mut recorder);
+
+        assert_eq!(changes[0].to_string(), "foo 1.3.0 → 1.0.5");
+        assert!(recorder.sub_dependencies.contains_key(&key("bar", "1.0.0")));
+    }
+}
@@ -11,11 +11,14 @@ use super::artifacts::{DownloadArtifacts, ResolvedItem};
 use crate::contracts::Logger;
 use crate::{
     cache::PackagesCache,
+    config::NPMRC,
     contracts::{PersistentCache, Phase, Pipe, PipeArtifact, ProgressAction},
-    errors::ExecutionError,
+    errors::{ExecutionError, NetworkError},
+    lockfile::lockfile_structure::LockfileResolution,
     logger::CraftLogger,
-    network::Http,
+    network::{Http, NetworkMode, RetryPolicy},
     package::NpmPackage,
+    registry::{GitRegistry, TarballRegistry},
 };
 
 // ─── DownloaderPipe ─────────────────────────────────────────────────────────────
@@ -25,6 +28,7 @@ pub struct DownloaderPipe<C: PersistentCache<PathBuf>> {
     packages: Vec<NpmPackage>,
     cache: Arc<Mutex<C>>,
     artifacts: Arc<Mutex<DownloadArtifacts>>,
+    network_mode: NetworkMode,
     tx: Sender<ProgressAction>,
 }
 
@@ -35,18 +39,37 @@ impl DownloaderPipe<PackagesCache> {
         artifacts: &dyn PipeArtifact<Vec<ResolvedItem>>,
         tx: Sender<ProgressAction>,
     ) -> Self {
+        // Aliases are downloaded once as the package they point to
+        let mut packages = artifacts
+            .get_artifacts()
+            .into_iter()
+            .map(|item| {
+                let target = item.package.target();
+                NpmPackage {
+                    name: target.name,
+                    alias_of: None,
+                    ..item.package
+                }
+            })
+            .collect::<Vec<_>>();
+        packages.sort_by_key(|package| package.to_string());
+        packages.dedup_by_key(|package| package.to_string());
+
         Self {
-            packages: artifacts
-                .get_artifacts()
-                .iter()
-                .map(|item| item.package.clone())
-                .collect(),
+            packages,
             cache: Arc::new(Mutex::new(PackagesCache::default())),
             artifacts: Arc::new(Mutex::new(DownloadArtifacts::new())),
+            network_mode: NetworkMode::default(),
             tx,
         }
     }
 
+    /// Fails for packages that aren't cached with `--offline`
+    pub fn with_network_mode(mut self, network_mode: NetworkMode) -> Self {
+        self.network_mode = network_mode;
+        self
+    }
+
     async fn prepare_pkg_for_download(download_path: &Path) -> Result<(), std::io::Error> {
         tokio::fs::create_dir_all(download_path.parent().unwrap()).await
     }
@@ -55,9 +78,15 @@ impl DownloaderPipe<PackagesCache> {
         package: &NpmPackage,
         mut cache: PackagesCache,
         artifacts: Arc<Mutex<DownloadArtifacts>>,
+        network_mode: NetworkMode,
     ) -> Result<(), ExecutionError> {
         let pkg = package.clone();
 
+        // Local folders are linked as they are
+        if let Some(LockfileResolution::Directory(_)) = pkg.resolution {
+            return Ok(());
+        }
+
         if cache.has(&pkg.clone().into()).await {
             log::info!("{}", format!("Package already downloaded: {}", pkg));
             let cache_dir = { cache.get_cache_directory().join(pkg.to_string()) };
@@ -72,18 +101,42 @@ impl DownloaderPipe<PackagesCache> {
             return Ok(());
         }
 
+        let is_local = matches!(
+            &pkg.resolution,
+            Some(LockfileResolution::Tarball(t)) if TarballRegistry::is_local(&t.tarball)
+        );
+        if network_mode == NetworkMode::Offline && !is_local {
+            return Err(NetworkError::Offline(vec![pkg.to_string()]).into());
+        }
+
         let path = { &cache.get_cache_directory().join(pkg.to_string()) };
 
         if pkg.contains_org() {
             Self::prepare_pkg_for_download(path).await.unwrap();
         }
-        let result = Http::download_file(&pkg.dist.tarball, path, &pkg.dist.shasum).await;
-        if result.is_err() {
-            CraftLogger::warn(format!("Failed to download package: {}", pkg));
-            return Err(ExecutionError::JobExecutionFailed(
-                "Failed to download package".parse().unwrap(),
-                "Failed to download package".parse().unwrap(),
-            ));
+        let result = match &pkg.resolution {
+            Some(LockfileResolution::GitRepository(git)) => {
+                GitRegistry::new().archive(git, path).await
+            }
+            Some(LockfileResolution::Tarball(t)) if TarballRegistry::is_local(&t.tarball) => {
+                TarballRegistry::new().store(&t.tarball, path).await
+            }
+            _ => {
+                let tarball = NPMRC.tarball_url_for(&pkg.name, &pkg.dist.tarball);
+                Http::download_file(
+                    &tarball,
+                    path,
+                    &pkg.dist.shasum,
+                    pkg.dist.integrity.as_deref(),
+                    NPMRC.auth_header_for(&tarball).as_deref(),
+                    &RetryPolicy::from_config(&NPMRC),
+                )
+                .await
+            }
+        };
+        if let Err(e) = result {
+            CraftLogger::warn(format!("Failed to download package {}: {}", pkg, e));
+            return Err(e.into());
         }
 
         {
@@ -118,9 +171,10 @@ impl Pipe<DownloadArtifacts> for DownloaderPipe<PackagesCache> {
         for pkg in pkgs {
             let cache = cache.clone();
             let artifacts = self.artifacts.clone();
+            let network_mode = self.network_mode;
             let job = tokio::spawn(async move {
                 CraftLogger::verbose(format!("Downloading package: {}", pkg));
-                match Self::download_pkg(&pkg, cache, artifacts).await {
+                match Self::download_pkg(&pkg, cache, artifacts, network_mode).await {
                     Ok(_) => Ok(()),
                     Err(err) => Err(err),
                 }
@@ -129,14 +183,57 @@ impl Pipe<DownloadArtifacts> for DownloaderPipe<PackagesCache> {
         }
 
         let results: Vec<_> = future::join_all(jobs).await;
-        // Iterate over the results
+        let mut missing = vec![];
         for result in results.into_iter() {
-            let jh_handle = result.unwrap();
-            if let Err(e) = jh_handle {
-                log::error!("Error is {}", e.to_string())
+            match result.unwrap() {
+                Err(ExecutionError::Network(NetworkError::Offline(packages))) => {
+                    missing.extend(packages)
+                }
+                Err(e) => log::error!("Error is {}", e.to_string()),
+                Ok(()) => {}
             }
         }
+        if !missing.is_empty() {
+            missing.sort();
+            missing.dedup();
+            return Err(NetworkError::Offline(missing).into());
+        }
 
         Ok(self.artifacts.lock().await.clone())
     }
 }
+
+// ─── Tests ───────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::artifacts::ResolveArtifacts;
+    use crate::pipeline::fixtures::item;
+
+    #[test]
+    fn test_aliases_are_downloaded_once() {
+        let alias = |name: &str| {
+            let mut item = item(name, "18.3.1", &[]);
+            item.package.alias_of = Some("react".to_string());
+            item
+        };
+        let resolved = [
+            alias("my-react"),
+            alias("your-react"),
+            item("react", "18.3.1", &[]),
+        ]
+        .into_iter()
+        .collect::<ResolveArtifacts>();
+        let (tx, _rx) = std::sync::mpsc::channel();
+
+        let downloader = DownloaderPipe::new(&resolved, tx);
+
+        let packages = downloader
+            .packages
+            .iter()
+            .map(|p| p.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(packages, vec!["react@18.3.1"]);
+    }
+}
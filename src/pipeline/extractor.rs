@@ -1,4 +1,4 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::{mpsc::Sender, Arc};
 
 use async_trait::async_trait;
@@ -10,7 +10,7 @@ use crate::cache::DEP_CACHE_FOLDER;
 use crate::fs::get_config_dir;
 use crate::pipeline::ResolvedItem;
 use crate::{
-    contracts::{Phase, Pipe, PipeArtifact, ProgressAction},
+    contracts::{Logger, Phase, Pipe, PipeArtifact, ProgressAction},
     errors::{ExecutionError, ZipError},
     logger::CraftLogger,
     tar::Gzip,
@@ -39,38 +39,68 @@ impl ExtractorPipe {
     }
 
     // Skip because we now simlink the extracted files
-    pub async fn cleanup(vec: Vec<ResolvedItem>) -> Result<(), ExecutionError> {
-        use std::fs::metadata;
-
+    pub async fn cleanup(
+        vec: Vec<ResolvedItem>,
+        node_modules: &Path,
+    ) -> Result<(), ExecutionError> {
+        // Only packages without a parent are linked at the top level
         let mapped_str = vec
             .iter()
+            .filter(|x| x.parent.is_none())
             .map(|x| x.package.name.clone())
             .collect::<Vec<String>>();
-        let mut entries = tokio::fs::read_dir("node_modules")
-            .await
-            .map_err(|e| ExecutionError::JobExecutionFailed(e.to_string(), e.to_string()))?;
-        while let Some(entry) = entries
-            .next_entry()
-            .await
-            .map_err(|e| ExecutionError::JobExecutionFailed(e.to_string(), e.to_string()))?
-        {
-            let dir_name = entry.file_name().to_str().unwrap().to_string();
-            let meta = metadata(entry.path())
-                .map_err(|e| ExecutionError::JobExecutionFailed(e.to_string(), e.to_string()))?;
-            if !mapped_str.contains(&dir_name) && meta.is_dir() && dir_name != ".bin" {
-                fs::remove_dir_all(entry.path()).await.map_err(|e| {
-                    ExecutionError::JobExecutionFailed(e.to_string(), e.to_string())
-                })?;
-            } else if !mapped_str.contains(&dir_name) && meta.is_file() {
-                fs::remove_file(entry.path()).await.map_err(|e| {
-                    ExecutionError::JobExecutionFailed(e.to_string(), e.to_string())
-                })?;
+
+        for (name, path) in Self::read_entries(node_modules).await? {
+            // .bin and the virtual store of the isolated layout
+            if name.starts_with('.') {
+                continue;
+            }
+
+            if name.starts_with('@') && path.is_dir() {
+                // Scope folders hold the packages, e.g. @types/node
+                let mut scope_entries = Self::read_entries(&path).await?;
+                scope_entries
+                    .retain(|(inner, _)| !mapped_str.contains(&format!("{}/{}", name, inner)));
+                for (_, inner_path) in &scope_entries {
+                    Self::remove_entry(inner_path).await?;
+                }
+                if Self::read_entries(&path).await?.is_empty() {
+                    Self::remove_entry(&path).await?;
+                }
+            } else if !mapped_str.contains(&name) {
+                Self::remove_entry(&path).await?;
             }
         }
 
         Ok(())
     }
 
+    async fn read_entries(directory: &Path) -> Result<Vec<(String, PathBuf)>, ExecutionError> {
+        let to_error =
+            |e: std::io::Error| ExecutionError::JobExecutionFailed(e.to_string(), e.to_string());
+
+        let mut entries = fs::read_dir(directory).await.map_err(to_error)?;
+        let mut result = vec![];
+        while let Some(entry) = entries.next_entry().await.map_err(to_error)? {
+            result.push((
+                entry.file_name().to_string_lossy().to_string(),
+                entry.path(),
+            ));
+        }
+
+        Ok(result)
+    }
+
+    async fn remove_entry(path: &Path) -> Result<(), ExecutionError> {
+        let result = if path.is_dir() && !path.is_symlink() {
+            fs::remove_dir_all(path).await
+        } else {
+            fs::remove_file(path).await
+        };
+
+        result.map_err(|e| ExecutionError::JobExecutionFailed(e.to_string(), e.to_string()))
+    }
+
     pub async fn unzip_archive(&self, artifact: &StoredArtifact) -> Result<(), ZipError> {
         let artifact_s = artifact.clone();
 
@@ -114,7 +144,10 @@ impl Pipe<ExtractArtifacts> for ExtractorPipe {
 
         for artifact in &self.packages {
             CraftLogger::verbose(format!("Extracting artifact: {}", artifact.package));
-            self.unzip_archive(artifact).await.unwrap();
+            // Missing packages are reported when they are linked
+            if let Err(e) = self.unzip_archive(artifact).await {
+                CraftLogger::error(format!("Failed to extract {}: {}", artifact.package, e));
+            }
         }
 
         Ok(self.artifacts.lock().await.clone())
@@ -0,0 +1,258 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+use super::artifacts::ResolvedItem;
+use crate::cache::RegistryKey;
+use crate::package::PackageRecorder;
+
+// ─── Hoisting ────────────────────────────────────────────────────────────────
+
+/// A node_modules folder of the hoisted layout and the package it belongs to
+#[derive(Debug)]
+struct Node {
+    /// `None` for the node_modules of the project
+    key: Option<RegistryKey>,
+    parent: Option<usize>,
+    children: BTreeMap<String, usize>,
+    /// Whether the dependencies of the package are placed
+    done: bool,
+}
+
+/// Where every package goes in an npm-like flat node_modules
+struct Plan<'a> {
+    nodes: Vec<Node>,
+    items: HashMap<RegistryKey, &'a ResolvedItem>,
+    recorder: &'a PackageRecorder,
+}
+
+// ─────────────────────────────────────────────────────────────────────────────
+
+/// Places the packages as flat as possible like npm does: every dependency
+/// goes to the highest node_modules where it doesn't conflict with another
+/// version and doesn't hide that version from packages already relying on it.
+/// The returned items have the packages they are placed below as parents, a
+/// package may be placed more than once.
+pub fn hoist(resolved: &[ResolvedItem], recorder: &PackageRecorder) -> Vec<ResolvedItem> {
+    let mut plan = Plan {
+        nodes: vec![Node {
+            key: None,
+            parent: None,
+            children: BTreeMap::new(),
+            done: true,
+        }],
+        items: resolved
+            .iter()
+            .map(|item| (item.package.clone().into(), item))
+            .collect(),
+        recorder,
+    };
+
+    let mut direct = resolved
+        .iter()
+        .filter(|item| item.parent.is_none())
+        .map(|item| RegistryKey::from(item.package.clone()))
+        .collect::<Vec<_>>();
+    direct.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let mut queue = direct
+        .into_iter()
+        .map(|key| plan.add(0, key))
+        .collect::<VecDeque<_>>();
+
+    while let Some(node) = queue.pop_front() {
+        let Some(key) = plan.nodes[node].key.clone() else {
+            continue;
+        };
+        for dependency in plan.dependencies(&key) {
+            if dependency.name == key.name || !plan.items.contains_key(&dependency) {
+                continue;
+            }
+            if let Some(level) = plan.place(node, &dependency) {
+                queue.push_back(plan.add(level, dependency));
+            }
+        }
+        plan.nodes[node].done = true;
+    }
+
+    plan.items()
+}
+
+impl Plan<'_> {
+    fn add(&mut self, parent: usize, key: RegistryKey) -> usize {
+        let index = self.nodes.len();
+        self.nodes[parent].children.insert(key.name.clone(), index);
+        self.nodes.push(Node {
+            key: Some(key),
+            parent: Some(parent),
+            children: BTreeMap::new(),
+            done: false,
+        });
+        index
+    }
+
+    fn dependencies(&self, key: &RegistryKey) -> Vec<RegistryKey> {
+        let meta = self
+            .recorder
+            .sub_dependencies
+            .get(key)
+            .or_else(|| self.recorder.main_packages.get(key));
+
+        let mut dependencies = meta
+            .and_then(|meta| meta.resolved_dependencies.clone())
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(name, version)| RegistryKey { name, version })
+            .collect::<Vec<_>>();
+        dependencies.sort_by(|a, b| a.name.cmp(&b.name));
+        dependencies
+    }
+
+    /// The node_modules the dependency of `node` goes to, `None` if it
+    /// already finds the version
+    fn place(&self, node: usize, dependency: &RegistryKey) -> Option<usize> {
+        let mut target = node;
+        let mut level = Some(node);
+
+        while let Some(current) = level {
+            match self.nodes[current].children.get(&dependency.name) {
+                Some(&found) if self.nodes[found].key.as_ref() == Some(dependency) => return None,
+                // Another version, it has to go below
+                Some(_) => break,
+                None if self.can_place(current, dependency) => target = current,
+                None => break,
+            }
+            level = self.nodes[current].parent;
+        }
+
+        Some(target)
+    }
+
+    /// Whether no package below `level` relies on another version of the
+    /// dependency it finds above `level`
+    fn can_place(&self, level: usize, dependency: &RegistryKey) -> bool {
+        let mut stack = vec![level];
+
+        while let Some(node) = stack.pop() {
+            stack.extend(self.nodes[node].children.values());
+
+            let Some(key) = &self.nodes[node].key else {
+                continue;
+            };
+            let relies_on_other = self.nodes[node].done
+                && self
+                    .dependencies(key)
+                    .iter()
+                    .any(|d| d.name == dependency.name && d.version != dependency.version);
+            if relies_on_other && !self.is_shielded(node, level, &dependency.name) {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    /// Whether `node` finds the package below `level`
+    fn is_shielded(&self, node: usize, level: usize, name: &str) -> bool {
+        let mut current = node;
+        while current != level {
+            if self.nodes[current].children.contains_key(name) {
+                return true;
+            }
+            match self.nodes[current].parent {
+                Some(parent) => current = parent,
+                None => return false,
+            }
+        }
+        false
+    }
+
+    /// The placed packages with the packages they are placed below as parents
+    fn items(&self) -> Vec<ResolvedItem> {
+        self.nodes
+            .iter()
+            .filter_map(|node| {
+                let mut item = (*self.items.get(node.key.as_ref()?)?).clone();
+
+                let mut parents = vec![];
+                let mut parent = node.parent;
+                while let Some(index) = parent {
+                    parents.extend(self.nodes[index].key.clone());
+                    parent = self.nodes[index].parent;
+                }
+                parents.reverse();
+
+                item.parent = (!parents.is_empty()).then_some(parents);
+                Some(item)
+            })
+            .collect()
+    }
+}
+
+// ─── Tests ───────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::fixtures::{item, key, recorded};
+    use crate::pipeline::NodeLinker;
+    use std::path::Path;
+
+    #[test]
+    fn test_hoist() {
+        // The project depends on b, c@1, d@1 and e@1, b needs c@2 and e@2,
+        // c@2 relies on d@1 of the project while e@2 wants d@2
+        let packages = [
+            ("b", "1", None, vec![("c", "2"), ("e", "2")]),
+            ("c", "1", None, vec![]),
+            ("d", "1", None, vec![]),
+            ("e", "1", None, vec![]),
+            ("c", "2", Some("b"), vec![("d", "1")]),
+            ("e", "2", Some("b"), vec![("d", "2")]),
+            ("d", "2", Some("e"), vec![]),
+        ];
+
+        let resolved = packages
+            .iter()
+            .map(|(name, version, parent, _)| {
+                let parent = parent.map(|p| vec![key(p, "1")]).unwrap_or_default();
+                item(name, version, &parent)
+            })
+            .collect::<Vec<_>>();
+        let mut recorder = recorded(&resolved);
+        for (name, version, parent, dependencies) in packages {
+            let key = key(name, version);
+            let meta = match parent {
+                Some(_) => recorder.sub_dependencies.get_mut(&key),
+                None => recorder.main_packages.get_mut(&key),
+            };
+            meta.unwrap().resolved_dependencies = Some(
+                dependencies
+                    .iter()
+                    .map(|(n, v)| (n.to_string(), v.to_string()))
+                    .collect(),
+            );
+        }
+
+        let node_modules = Path::new("node_modules");
+        let mut placed = hoist(&resolved, &recorder)
+            .iter()
+            .map(|item| {
+                let path = NodeLinker::Hoisted.install_path(node_modules, item);
+                format!("{} {}", path.display(), item.package.version)
+            })
+            .collect::<Vec<_>>();
+        placed.sort();
+
+        assert_eq!(
+            placed,
+            vec![
+                "node_modules/b 1",
+                "node_modules/b/node_modules/c 2",
+                "node_modules/b/node_modules/e 2",
+                "node_modules/b/node_modules/e/node_modules/d 2",
+                "node_modules/c 1",
+                "node_modules/d 1",
+                "node_modules/e 1",
+            ]
+        );
+    }
+}
@@ -0,0 +1,474 @@
+use std::{
+    collections::{HashMap, HashSet},
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use async_trait::async_trait;
+use tokio::process::Command;
+
+use super::{artifacts::ResolvedItem, NodeLinker};
+use crate::{
+    cache::RegistryKey,
+    contracts::{Lockfile, Logger, Pipe},
+    errors::ExecutionError,
+    lockfile::{lock_file_actor::LockFileActor, lockfile_structure::LockfileResolution},
+    logger::CraftLogger,
+    package::{PackageJson, PackageRecorder},
+};
+
+const DEPENDENCY_SCRIPTS: [&str; 3] = ["preinstall", "install", "postinstall"];
+const ROOT_SCRIPTS: [&str; 1] = ["prepare"];
+
+// ─── BuildPolicy ─────────────────────────────────────────────────────────────
+
+/// Which dependencies may run their scripts, from `pnpm.onlyBuiltDependencies`
+/// and `pnpm.neverBuiltDependencies` of package.json or else the lockfile
+#[derive(Debug, Clone)]
+pub struct BuildPolicy {
+    only_built: Option<Vec<String>>,
+    never_built: Vec<String>,
+}
+
+// ─── LifecycleScriptsPipe ────────────────────────────────────────────────────
+
+/// Runs the install scripts of the linked dependencies, dependencies before
+/// their dependents, and `prepare` of the project itself
+#[derive(Debug)]
+pub struct LifecycleScriptsPipe {
+    resolved: Vec<ResolvedItem>,
+    recorder: PackageRecorder,
+    project: PathBuf,
+    policy: BuildPolicy,
+    node_linker: NodeLinker,
+}
+
+// ─────────────────────────────────────────────────────────────────────────────
+
+/// Without any settings every dependency may run its scripts, like npm does
+impl Default for BuildPolicy {
+    fn default() -> Self {
+        Self::new(None, vec![])
+    }
+}
+
+impl BuildPolicy {
+    pub fn new(only_built: Option<Vec<String>>, never_built: Vec<String>) -> Self {
+        Self {
+            only_built,
+            never_built,
+        }
+    }
+
+    /// The settings of the project at `project`, the default if it has none
+    pub fn load(project: &Path) -> Self {
+        let settings = fs::read_to_string(project.join("package.json"))
+            .ok()
+            .and_then(|c| serde_json::from_str::<PackageJson>(&c).ok())
+            .and_then(|p| p.pnpm);
+
+        if let Some(settings) = settings {
+            return Self::new(
+                settings.only_built_dependencies,
+                settings.never_built_dependencies.unwrap_or_default(),
+            );
+        }
+
+        match LockFileActor::read_lock_file(&project.join("pnpm-lock.yaml")) {
+            Ok(lockfile) => Self::new(
+                lockfile.only_built_dependencies,
+                lockfile.never_built_dependencies.unwrap_or_default(),
+            ),
+            Err(_) => Self::default(),
+        }
+    }
+
+    pub fn allows(&self, name: &str) -> bool {
+        if self.never_built.iter().any(|n| n == name) {
+            return false;
+        }
+
+        match &self.only_built {
+            Some(only_built) => only_built.iter().any(|n| n == name),
+            None => true,
+        }
+    }
+}
+
+impl LifecycleScriptsPipe {
+    pub fn new(
+        resolved: Vec<ResolvedItem>,
+        recorder: PackageRecorder,
+        project: &Path,
+        policy: BuildPolicy,
+    ) -> Self {
+        Self {
+            resolved,
+            recorder,
+            project: project.to_path_buf(),
+            policy,
+            node_linker: NodeLinker::default(),
+        }
+    }
+
+    /// Where the linker put the packages
+    pub fn with_node_linker(mut self, node_linker: NodeLinker) -> Self {
+        self.node_linker = node_linker;
+        self
+    }
+
+    fn dependencies(&self, key: &RegistryKey) -> Vec<RegistryKey> {
+        let meta = self
+            .recorder
+            .sub_dependencies
+            .get(key)
+            .or_else(|| self.recorder.main_packages.get(key));
+
+        let mut dependencies = meta
+            .and_then(|meta| meta.resolved_dependencies.clone())
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(name, version)| RegistryKey { name, version })
+            .collect::<Vec<_>>();
+        dependencies.sort_by(|a, b| a.name.cmp(&b.name));
+        dependencies
+    }
+
+    /// Adds `key` after everything it depends on, a cycle is broken at the
+    /// package that was visited first
+    fn visit(
+        &self,
+        key: &RegistryKey,
+        visited: &mut HashSet<RegistryKey>,
+        order: &mut Vec<RegistryKey>,
+    ) {
+        if !visited.insert(key.clone()) {
+            return;
+        }
+
+        for dependency in self.dependencies(key) {
+            self.visit(&dependency, visited, order);
+        }
+        order.push(key.clone());
+    }
+
+    /// The linked folders of the dependencies that may be built, every
+    /// dependency before the packages depending on it, and whether the
+    /// package is optional
+    fn build_order(&self) -> Vec<(String, PathBuf, bool)> {
+        let node_modules = self.project.join("node_modules");
+
+        // Hoisting can place the same package in several folders
+        let mut placed = HashMap::<RegistryKey, Vec<&ResolvedItem>>::new();
+        for item in &self.resolved {
+            placed
+                .entry(item.package.clone().into())
+                .or_default()
+                .push(item);
+        }
+
+        let mut keys = placed.keys().collect::<Vec<_>>();
+        keys.sort_by_key(|key| key.to_string());
+
+        let mut visited = HashSet::new();
+        let mut order = vec![];
+        for key in keys {
+            self.visit(key, &mut visited, &mut order);
+        }
+
+        let mut seen = HashSet::new();
+        order
+            .iter()
+            .filter_map(|key| placed.get(key))
+            .flatten()
+            .filter(|item| {
+                !matches!(
+                    item.package.resolution,
+                    Some(LockfileResolution::Directory(_))
+                )
+            })
+            .filter(|item| self.policy.allows(&item.package.name))
+            .map(|item| {
+                (
+                    item.package.to_string(),
+                    self.node_linker.install_path(&node_modules, item),
+                    item.is_optional(),
+                )
+            })
+            .filter(|(_, directory, _)| seen.insert(directory.clone()))
+            .collect()
+    }
+
+    fn read_scripts(directory: &Path) -> HashMap<String, String> {
+        let mut scripts = fs::read_to_string(directory.join("package.json"))
+            .ok()
+            .and_then(|c| serde_json::from_str::<PackageJson>(&c).ok())
+            .and_then(|p| p.scripts)
+            .unwrap_or_default();
+
+        // Native addons without own install script are built by node-gyp
+        if !scripts.contains_key("install")
+            && !scripts.contains_key("preinstall")
+            && directory.join("binding.gyp").exists()
+        {
+            scripts.insert("install".to_string(), "node-gyp rebuild".to_string());
+        }
+
+        scripts
+    }
+
+    /// Runs the scripts of a package that exist, stops at the first failing one
+    async fn run_scripts(
+        &self,
+        package: &str,
+        directory: &Path,
+        events: &[&str],
+    ) -> Result<(), ExecutionError> {
+        let scripts = Self::read_scripts(directory);
+
+        for event in events {
+            if let Some(script) = scripts.get(*event) {
+                CraftLogger::verbose(format!("Running {} of {}: {}", event, package, script));
+                self.run_script(package, directory, event, script).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn run_script(
+        &self,
+        package: &str,
+        directory: &Path,
+        event: &str,
+        script: &str,
+    ) -> Result<(), ExecutionError> {
+        let mut command = if cfg!(target_os = "windows") {
+            let mut command = Command::new("cmd");
+            command.args(["/C", script]);
+            command
+        } else {
+            let mut command = Command::new("sh");
+            command.args(["-c", script]);
+            command
+        };
+
+        let output = command
+            .current_dir(directory)
+            .env("PATH", path_with_bins(directory))
+            .env("npm_lifecycle_event", event)
+            .env("npm_lifecycle_script", script)
+            .output()
+            .await
+            .map_err(|e| {
+                ExecutionError::LifecycleScriptFailed(
+                    package.to_string(),
+                    event.to_string(),
+                    e.to_string(),
+                )
+            })?;
+
+        let captured = format!(
+            "{}{}",
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr)
+        );
+
+        if !output.status.success() {
+            return Err(ExecutionError::LifecycleScriptFailed(
+                package.to_string(),
+                event.to_string(),
+                captured,
+            ));
+        }
+
+        if !captured.is_empty() {
+            CraftLogger::verbose(captured);
+        }
+
+        Ok(())
+    }
+}
+
+/// The `node_modules/.bin` folders of `directory` and all of its ancestors,
+/// the closest first
+pub fn bin_paths(directory: &Path) -> Vec<PathBuf> {
+    directory
+        .ancestors()
+        .map(|a| a.join("node_modules").join(".bin"))
+        .filter(|bin| bin.is_dir())
+        .collect()
+}
+
+/// PATH with the bin folders of `directory` in front
+pub fn path_with_bins(directory: &Path) -> std::ffi::OsString {
+    let current = env::var_os("PATH").unwrap_or_default();
+    let paths = bin_paths(directory)
+        .into_iter()
+        .chain(env::split_paths(&current));
+
+    env::join_paths(paths).unwrap_or(current)
+}
+
+#[async_trait]
+impl Pipe<()> for LifecycleScriptsPipe {
+    async fn run(&mut self) -> Result<(), ExecutionError> {
+        for (package, directory, optional) in self.build_order() {
+            match self
+                .run_scripts(&package, &directory, &DEPENDENCY_SCRIPTS)
+                .await
+            {
+                // A failing optional package doesn't fail the install
+                Err(e) if optional => CraftLogger::warn(format!("Skipping {}: {}", package, e)),
+                result => result?,
+            }
+        }
+
+        let project = self.project.display().to_string();
+        self.run_scripts(&project, &self.project, &ROOT_SCRIPTS)
+            .await?;
+
+        Ok(())
+    }
+}
+
+// ─── Tests ───────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::fixtures::{item, key, recorded, TempDir};
+
+    /// Records the resolved items, `edges` are pairs of a package and its dependency
+    fn recorder(resolved: &[ResolvedItem], edges: &[(&str, &str)]) -> PackageRecorder {
+        let mut recorder = recorded(resolved);
+        for (dependent, dependency) in edges {
+            let key = key(dependent, "1.0.0");
+            let meta = match recorder.sub_dependencies.get_mut(&key) {
+                Some(meta) => meta,
+                None => recorder.main_packages.get_mut(&key).unwrap(),
+            };
+            meta.resolved_dependencies
+                .get_or_insert_with(HashMap::new)
+                .insert(dependency.to_string(), "1.0.0".to_string());
+        }
+        recorder
+    }
+
+    fn create_package(directory: &Path, scripts: &str) {
+        fs::create_dir_all(directory).unwrap();
+        fs::write(
+            directory.join("package.json"),
+            format!(r#"{{"name": "pkg", "scripts": {{{}}}}}"#, scripts),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_build_policy() {
+        let policy = BuildPolicy::new(
+            Some(vec!["esbuild".to_string(), "sharp".to_string()]),
+            vec!["sharp".to_string()],
+        );
+
+        assert!(policy.allows("esbuild"));
+        assert!(!policy.allows("sharp"));
+        assert!(!policy.allows("lodash"));
+        assert!(BuildPolicy::default().allows("lodash"));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_runs_scripts_in_dependency_order() {
+        let project = TempDir::new("lifecycle-order");
+        let log = project.join("log");
+        let append = |text: &str| format!(r#""postinstall": "echo {} >> {}""#, text, log.display());
+
+        create_package(
+            &project,
+            &format!(r#""prepare": "echo root >> {}""#, log.display()),
+        );
+        create_package(&project.join("node_modules/a"), &append("a"));
+        create_package(
+            &project.join("node_modules/a/node_modules/b"),
+            &format!(
+                r#""preinstall": "echo b-pre >> {0}", {1}"#,
+                log.display(),
+                append("b")
+            ),
+        );
+        create_package(&project.join("node_modules/c"), &append("c"));
+
+        let resolved = vec![
+            item("a", "1.0.0", &[]),
+            item("b", "1.0.0", &[key("a", "1.0.0")]),
+            item("c", "1.0.0", &[]),
+        ];
+        LifecycleScriptsPipe::new(
+            resolved.clone(),
+            recorder(&resolved, &[("a", "b")]),
+            &project,
+            BuildPolicy::new(None, vec!["c".to_string()]),
+        )
+        .run()
+        .await
+        .unwrap();
+
+        assert_eq!(fs::read_to_string(&log).unwrap(), "b-pre\nb\na\nroot\n");
+    }
+
+    #[test]
+    fn test_build_order_follows_dependencies() {
+        // The hoisted layout puts every package at the top level, a needs z
+        // and c and d depend on each other
+        let resolved = ["a", "c", "d", "z"]
+            .iter()
+            .map(|name| item(name, "1.0.0", &[]))
+            .collect::<Vec<_>>();
+        let recorder = recorder(&resolved, &[("a", "z"), ("c", "d"), ("d", "c")]);
+        let pipe = LifecycleScriptsPipe::new(
+            resolved,
+            recorder,
+            Path::new("project"),
+            BuildPolicy::default(),
+        )
+        .with_node_linker(NodeLinker::Hoisted);
+
+        let order = pipe
+            .build_order()
+            .into_iter()
+            .map(|(package, _, _)| package)
+            .collect::<Vec<_>>();
+
+        assert_eq!(order, vec!["z@1.0.0", "a@1.0.0", "d@1.0.0", "c@1.0.0"]);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_failing_script_surfaces_output() {
+        let project = TempDir::new("lifecycle-failure");
+        create_package(
+            &project.join("node_modules/broken"),
+            r#""install": "echo compiling && exit 3""#,
+        );
+
+        let resolved = vec![item("broken", "1.0.0", &[])];
+        let result = LifecycleScriptsPipe::new(
+            resolved.clone(),
+            recorded(&resolved),
+            &project,
+            BuildPolicy::default(),
+        )
+        .run()
+        .await;
+
+        match result {
+            Err(ExecutionError::LifecycleScriptFailed(package, event, output)) => {
+                assert_eq!(package, "broken@1.0.0");
+                assert_eq!(event, "install");
+                assert!(output.contains("compiling"));
+            }
+            other => panic!("Unexpected result {:?}", other),
+        }
+    }
+}
@@ -1,19 +1,28 @@
-use std::{env, fs, path::PathBuf, sync::mpsc::Sender};
+use std::{
+    collections::{HashMap, HashSet},
+    env, fs,
+    path::{Path, PathBuf},
+    sync::mpsc::Sender,
+};
 
 use async_trait::async_trait;
 use lazy_static::lazy_static;
 
-use super::artifacts::{ExtractArtifactsMap, LinkArtifactItem, ResolvedItem};
+use super::artifacts::{ExtractArtifactsMap, ResolvedItem};
 use crate::{
+    cache::RegistryKey,
+    config::NPMRC,
     contracts::{Logger, Phase, Pipe, ProgressAction},
     errors::ExecutionError,
-    fs::copy_dir,
+    fs::{import_dir, symlink_dir, ImportMethod},
     logger::CraftLogger,
 };
 use path_clean::clean;
 
-use crate::package::{BinType, PackageRecorder, ResolvedBinary};
+use crate::lockfile::lockfile_structure::LockfileResolution;
+use crate::package::{BinType, PackageMetaRecorder, PackageRecorder, ResolvedBinary};
 use crate::pipeline::binary_templates::{get_bash_script, get_cmd_script, get_pwsh_script};
+use crate::registry::DirectoryRegistry;
 // ─────────────────────────────────────────────────────────────────────────────
 
 #[derive(Debug)]
@@ -22,6 +31,22 @@ pub struct LinkerPipe {
     resolved: Vec<ResolvedItem>,
     extracted: ExtractArtifactsMap,
     recorder: PackageRecorder,
+    node_modules: PathBuf,
+    node_linker: NodeLinker,
+    import_method: ImportMethod,
+}
+
+/// How the packages are laid out in node_modules, `node-linker` of .npmrc
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum NodeLinker {
+    /// Every package below the packages depending on it
+    #[default]
+    Nested,
+    /// Every package once in the virtual store next to links to its own
+    /// dependencies, node_modules only links the direct dependencies
+    Isolated,
+    /// Like npm, every package as high up as possible as a real folder
+    Hoisted,
 }
 
 // ─────────────────────────────────────────────────────────────────────────────
@@ -30,6 +55,59 @@ lazy_static! {
     pub static ref NODE_MODULES: PathBuf = env::current_dir().unwrap().join("node_modules");
 }
 
+/// The folder of node_modules the isolated layout links every package into
+pub const VIRTUAL_STORE: &str = ".craft";
+
+// ─────────────────────────────────────────────────────────────────────────────
+
+impl NodeLinker {
+    pub fn current() -> Self {
+        match NPMRC.get("node-linker") {
+            Some("isolated") => NodeLinker::Isolated,
+            Some("hoisted") => NodeLinker::Hoisted,
+            _ => NodeLinker::Nested,
+        }
+    }
+
+    /// Where a resolved package ends up below `node_modules`. Hoisted
+    /// packages have the packages they are placed below as parents.
+    pub fn install_path(&self, node_modules: &Path, resolved: &ResolvedItem) -> PathBuf {
+        match self {
+            NodeLinker::Isolated => {
+                Self::store_path(node_modules, &resolved.package.clone().into())
+            }
+            NodeLinker::Nested | NodeLinker::Hoisted => match &resolved.parent {
+                // If it is a child
+                Some(path_vec) => {
+                    let mut path = PathBuf::new();
+
+                    for p in path_vec {
+                        path.push(&p.name);
+                        path.push("node_modules")
+                    }
+                    node_modules.join(&path).join(&resolved.package.name)
+                }
+                None => node_modules.join(&resolved.package.name),
+            },
+        }
+    }
+
+    /// The node_modules of a package in the virtual store, holding the package
+    /// and its dependencies: `.craft/<name>@<version>/node_modules`
+    pub fn store_node_modules(node_modules: &Path, key: &RegistryKey) -> PathBuf {
+        // Like pnpm, `@scope/name` becomes `@scope+name`
+        let entry = key.to_string().replace(['/', '\\', ':'], "+");
+        node_modules
+            .join(VIRTUAL_STORE)
+            .join(entry)
+            .join("node_modules")
+    }
+
+    pub fn store_path(node_modules: &Path, key: &RegistryKey) -> PathBuf {
+        Self::store_node_modules(node_modules, key).join(&key.name)
+    }
+}
+
 // ─────────────────────────────────────────────────────────────────────────────
 
 impl LinkerPipe {
@@ -44,66 +122,268 @@ impl LinkerPipe {
             resolved,
             extracted,
             recorder,
+            node_modules: NODE_MODULES.clone(),
+            node_linker: NodeLinker::default(),
+            import_method: ImportMethod::default(),
         }
     }
 
-    fn build_linker_artifacts(&mut self) -> Vec<LinkArtifactItem> {
-        let mut linker_artifacts = vec![];
+    /// Lays the packages out in another way than nested below each other
+    pub fn with_node_linker(mut self, node_linker: NodeLinker) -> Self {
+        self.node_linker = node_linker;
+        self
+    }
 
-        // So that the parents (things in our package.json come first)
-        self.resolved.reverse();
+    /// Imports the files of the packages another way than `auto`
+    pub fn with_import_method(mut self, import_method: ImportMethod) -> Self {
+        self.import_method = import_method;
+        self
+    }
+
+    /// Links into another node_modules folder, e.g. of a project of the workspace
+    pub fn with_node_modules(mut self, node_modules: PathBuf) -> Self {
+        self.node_modules = node_modules;
+        self
+    }
+
+    /// Links every package once into the virtual store with links to its
+    /// dependencies next to it, node_modules only links the direct ones
+    fn link_isolated(&self) {
+        let mut entries = HashSet::new();
 
         for resolved in &self.resolved {
-            let pkg = &resolved.package;
-            let parent = &resolved.parent;
+            let key: RegistryKey = resolved.package.clone().into();
+            let to = NodeLinker::store_path(&self.node_modules, &key);
+            entries.insert(NodeLinker::store_node_modules(&self.node_modules, &key));
+
+            // Local folders are linked directly, everything else gets its
+            // real location in the store so node resolves its dependencies
+            let result = match &resolved.package.resolution {
+                Some(LockfileResolution::Directory(d)) => {
+                    Self::replace_link(&DirectoryRegistry::path(&d.directory), &to)
+                }
+                _ if to.join("package.json").exists() => continue,
+                _ => match self.extracted.get(&resolved.package.target().to_string()) {
+                    Some(extracted) => import_dir(
+                        &Self::package_dir(&extracted.unzip_at),
+                        &to,
+                        self.import_method,
+                    ),
+                    None => continue,
+                },
+            };
+            if let Err(e) = result {
+                CraftLogger::error(format!("Failed to link {} to {}: {}", key, to.display(), e));
+            }
+        }
+
+        for resolved in &self.resolved {
+            let key: RegistryKey = resolved.package.clone().into();
+            for dependency in self.dependencies_of(resolved) {
+                let from = NodeLinker::store_path(&self.node_modules, &dependency);
+                let to =
+                    NodeLinker::store_node_modules(&self.node_modules, &key).join(&dependency.name);
+                // Packages that are left out, e.g. optional ones
+                if dependency.name == key.name || !from.exists() {
+                    continue;
+                }
+                if let Err(e) = Self::replace_link(&from, &to) {
+                    CraftLogger::error(format!("Failed to link {} to {}: {}", dependency, key, e));
+                }
+            }
+        }
 
-            if resolved.package.name == "body-parser" {
-                CraftLogger::info(format!("Parent: {:?}", parent));
+        for resolved in self.resolved.iter().filter(|r| r.parent.is_none()) {
+            let from = NodeLinker::store_path(&self.node_modules, &resolved.package.clone().into());
+            let to = self.node_modules.join(&resolved.package.name);
+            if let Err(e) = Self::replace_link(&from, &to) {
+                CraftLogger::error(format!("Failed to link {}: {}", resolved.package, e));
             }
+        }
 
-            let from = self.extracted.get(&pkg.to_string()).unwrap().clone();
+        // Packages that aren't installed anymore
+        if let Ok(store) = fs::read_dir(self.node_modules.join(VIRTUAL_STORE)) {
+            for entry in store.flatten() {
+                if !entries.contains(&entry.path().join("node_modules")) {
+                    let _ = fs::remove_dir_all(entry.path());
+                }
+            }
+        }
+    }
 
-            // If it is a child
-            let to = if let Some(path_vec) = parent {
-                let mut path = PathBuf::new();
+    /// Imports every package as a real folder to where it is placed, parents
+    /// first as replacing a package drops its node_modules
+    fn link_folders(&self) {
+        let mut resolved = self.resolved.iter().collect::<Vec<_>>();
+        resolved.sort_by_key(|r| r.parent.as_ref().map_or(0, Vec::len));
+
+        // The packages every node_modules folder should hold
+        let mut planned: HashMap<PathBuf, HashSet<String>> = HashMap::new();
+
+        for resolved in resolved {
+            let key: RegistryKey = resolved.package.clone().into();
+            let to = self.node_linker.install_path(&self.node_modules, resolved);
+            planned.entry(to.join("node_modules")).or_default();
+            if let Some(folder) = to.ancestors().nth(key.name.split('/').count()) {
+                planned
+                    .entry(folder.to_path_buf())
+                    .or_default()
+                    .insert(key.name.clone());
+            }
 
-                for p in path_vec {
-                    path.push(&p.name);
-                    path.push("node_modules")
+            let result = match &resolved.package.resolution {
+                Some(LockfileResolution::Directory(d)) => {
+                    Self::replace_link(&DirectoryRegistry::path(&d.directory), &to)
                 }
-                NODE_MODULES.join(&path).join(&pkg.name)
-            } else {
-                NODE_MODULES.join(&pkg.name)
+                _ => self.materialize(&resolved.package.target(), &to),
             };
+            if let Err(e) = result {
+                CraftLogger::error(format!("Failed to link {} to {}: {}", key, to.display(), e));
+            }
+        }
 
-            linker_artifacts.push(LinkArtifactItem::new(from.unzip_at, to));
+        // The node_modules of the project is cleaned up by the extractor
+        for (folder, names) in &planned {
+            if folder != &self.node_modules {
+                Self::prune(folder, names);
+            }
+        }
+    }
+
+    /// Puts a real copy of the extracted package at `to`, unless that version
+    /// already is there
+    fn materialize(&self, key: &RegistryKey, to: &Path) -> std::io::Result<()> {
+        let Some(extracted) = self.extracted.get(&key.to_string()) else {
+            return Ok(());
+        };
+        if !to.is_symlink() && Self::installed_version(to).as_ref() == Some(&key.version) {
+            return Ok(());
         }
 
-        linker_artifacts
+        Self::remove_entry(to)?;
+        import_dir(
+            &Self::package_dir(&extracted.unzip_at),
+            to,
+            self.import_method,
+        )
     }
 
-    async fn link(&mut self, artifacts: &Vec<LinkArtifactItem>) {
-        for artifact in artifacts {
-            if let Err(e) = fs::create_dir_all(&artifact.to) {
-                CraftLogger::error(format!(
-                    "Failed to create directory: {}",
-                    artifact.to.display()
-                ));
-                CraftLogger::error(format!("Error: {}", e));
+    fn installed_version(package: &Path) -> Option<String> {
+        let content = fs::read_to_string(package.join("package.json")).ok()?;
+        let json = serde_json::from_str::<serde_json::Value>(&content).ok()?;
+        json.get("version")?.as_str().map(str::to_string)
+    }
+
+    /// Tarballs usually hold the package in a `package` folder
+    fn package_dir(unzip_at: &Path) -> PathBuf {
+        let package = unzip_at.join("package");
+        match package.exists() {
+            true => package,
+            false => unzip_at.to_path_buf(),
+        }
+    }
+
+    /// Removes the packages of a node_modules folder that aren't planned there
+    fn prune(folder: &Path, names: &HashSet<String>) {
+        let Ok(entries) = fs::read_dir(folder) else {
+            return;
+        };
+
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let path = entry.path();
+            if name.starts_with('.') || names.contains(&name) {
+                continue;
             }
 
-            if let Err(e) = copy_dir(&artifact.from.join("package"), &artifact.to) {
-                CraftLogger::error(format!(
-                    "Failed to copy from: {} to: {}: Error: {}",
-                    artifact.from.display(),
-                    artifact.to.display(),
-                    e
-                ));
-                CraftLogger::error(format!("Error: {}", e));
+            if name.starts_with('@') && path.is_dir() && !path.is_symlink() {
+                let scope = format!("{}/", name);
+                let scoped = names
+                    .iter()
+                    .filter_map(|n| n.strip_prefix(&scope))
+                    .map(str::to_string)
+                    .collect();
+                Self::prune(&path, &scoped);
+                if fs::read_dir(&path).is_ok_and(|mut e| e.next().is_none()) {
+                    let _ = fs::remove_dir(&path);
+                }
+            } else if let Err(e) = Self::remove_entry(&path) {
+                CraftLogger::warn(format!("Failed to remove {}: {}", path.display(), e));
             }
         }
     }
 
+    fn meta(&self, key: &RegistryKey) -> Option<&PackageMetaRecorder> {
+        self.recorder
+            .sub_dependencies
+            .get(key)
+            .or_else(|| self.recorder.main_packages.get(key))
+    }
+
+    /// The dependencies of a package, and the packages providing its peers
+    /// like node would find them from where it is depended on
+    fn dependencies_of(&self, resolved: &ResolvedItem) -> Vec<RegistryKey> {
+        let key: RegistryKey = resolved.package.clone().into();
+        let dependencies = self
+            .meta(&key)
+            .and_then(|meta| meta.resolved_dependencies.clone())
+            .unwrap_or_default();
+
+        let mut peers = resolved
+            .package
+            .peer_dependencies
+            .iter()
+            .flat_map(|peers| peers.keys())
+            .filter(|name| !dependencies.contains_key(*name))
+            .filter_map(|name| {
+                let parents = resolved.parent.iter().flatten().rev();
+                let version = parents
+                    .filter_map(|parent| self.meta(parent)?.resolved_dependencies.as_ref())
+                    .find_map(|dependencies| dependencies.get(name))
+                    .cloned()
+                    .or_else(|| {
+                        self.resolved
+                            .iter()
+                            .find(|r| r.parent.is_none() && &r.package.name == name)
+                            .map(|r| r.package.version.clone())
+                    })?;
+                Some(RegistryKey {
+                    name: name.clone(),
+                    version,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        peers.extend(
+            dependencies
+                .into_iter()
+                .map(|(name, version)| RegistryKey { name, version }),
+        );
+        peers
+    }
+
+    /// Points `link` to `target`, replacing whatever was there before
+    fn replace_link(target: &Path, link: &Path) -> std::io::Result<()> {
+        if fs::read_link(link).is_ok_and(|current| current == target) {
+            return Ok(());
+        }
+        Self::remove_entry(link)?;
+        if let Some(parent) = link.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        symlink_dir(target, link)
+    }
+
+    /// Removes a folder, file or link, if there is one
+    fn remove_entry(path: &Path) -> std::io::Result<()> {
+        match fs::symlink_metadata(path) {
+            Ok(metadata) if metadata.is_dir() => fs::remove_dir_all(path),
+            Ok(_) => fs::remove_file(path),
+            Err(_) => Ok(()),
+        }
+    }
+
     fn prepare_bin_dir(bin_dir_to_create: &PathBuf, rb: &ResolvedBinary) {
         if fs::metadata(bin_dir_to_create).is_err() {
             let result = fs::create_dir(bin_dir_to_create);
@@ -183,14 +463,25 @@ impl LinkerPipe {
     }
 
     async fn link_binaries(&self) {
+        // Every package of the store has its own bin folder
+        if self.node_linker == NodeLinker::Isolated {
+            for (key, meta) in &self.recorder.sub_dependencies {
+                let path_to_bin =
+                    NodeLinker::store_node_modules(&self.node_modules, key).join(".bin");
+                for r in meta.resolved_binaries.iter().flatten() {
+                    Self::prepare_bin_dir(&path_to_bin, r);
+                }
+            }
+        }
+
         self.recorder.main_packages.iter().for_each(|p| {
             if let Some(r_opt) = &p.1.resolved_binaries {
-                let path_to_bin =
-                    p.1.resolve_path_to_package()
-                        .join("node_modules")
-                        .join(".bin");
+                let path_to_bin = match self.node_linker {
+                    NodeLinker::Isolated => NodeLinker::store_node_modules(&self.node_modules, p.0),
+                    _ => self.node_modules.join(&p.0.name).join("node_modules"),
+                }
+                .join(".bin");
                 for r in r_opt {
-                    log::info!("{:?}", p.1.resolve_path_to_package());
                     Self::prepare_bin_dir(&path_to_bin, r);
                 }
             }
@@ -198,7 +489,7 @@ impl LinkerPipe {
             if let Some(bin) = &p.1.bin {
                 match bin {
                     BinType::Bin(s) => {
-                        let path_to_bin = PathBuf::from("node_modules").join(".bin");
+                        let path_to_bin = self.node_modules.join(".bin");
                         let resolved_binary = ResolvedBinary {
                             name: s.rsplit('/').next().unwrap().replace(".js", ""),
                             path: s.clone(),
@@ -213,7 +504,7 @@ impl LinkerPipe {
                                 path: s.1.clone(),
                                 package_name: p.1.name.clone(),
                             };
-                            let path_to_bin = PathBuf::from("node_modules").join(".bin");
+                            let path_to_bin = self.node_modules.join(".bin");
                             Self::prepare_bin_dir(&path_to_bin, &resolved_binary);
                         });
                     }
@@ -230,13 +521,102 @@ impl Pipe<()> for LinkerPipe {
     async fn run(&mut self) -> Result<(), ExecutionError> {
         let _ = self.tx.send(ProgressAction::new(Phase::Linking));
 
-        let artifacts = self.build_linker_artifacts();
-
-        self.link(&artifacts).await;
+        match self.node_linker {
+            NodeLinker::Isolated => self.link_isolated(),
+            _ => self.link_folders(),
+        }
         self.link_binaries().await;
 
         Ok(())
     }
 }
 
-// ─────────────────────────────────────────────────────────────────────────────
+// ─── Tests ───────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contracts::PipeArtifact;
+    use crate::pipeline::artifacts::ExtractArtifacts;
+    use crate::pipeline::fixtures::{item, key, recorded, TempDir};
+    use std::collections::HashMap;
+
+    /// Links a, its dependency @s/b and c, the peer of b, in isolation
+    async fn link_isolated(name: &str) -> TempDir {
+        let dir = TempDir::new(name);
+        let node_modules = dir.join("node_modules");
+        fs::create_dir_all(node_modules.join(".craft/old@1.0.0")).unwrap();
+
+        let resolved = vec![
+            item("a", "1.0.0", &[]),
+            item("@s/b", "1.0.0", &[key("a", "1.0.0")]).with_peers(&[("c", "^1")]),
+            item("c", "1.0.0", &[]),
+        ];
+        let mut extracted = ExtractArtifacts::new();
+        for item in &resolved {
+            let unzip_at = dir.join("store").join(item.package.name.replace('/', "-"));
+            fs::create_dir_all(unzip_at.join("package")).unwrap();
+            fs::write(unzip_at.join("package/package.json"), "{}").unwrap();
+            extracted.add(item.package.clone(), unzip_at);
+        }
+        let mut recorder = recorded(&resolved);
+        recorder
+            .main_packages
+            .get_mut(&key("a", "1.0.0"))
+            .unwrap()
+            .resolved_dependencies = Some(HashMap::from([("@s/b".into(), "1.0.0".into())]));
+
+        let (tx, _rx) = std::sync::mpsc::channel();
+        LinkerPipe::new(tx, resolved, extracted.get_artifacts(), recorder)
+            .with_node_modules(node_modules.clone())
+            .with_node_linker(NodeLinker::Isolated)
+            .run()
+            .await
+            .unwrap();
+
+        dir
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_isolated_layout() {
+        let dir = link_isolated("isolated-layout").await;
+        let node_modules = dir.join("node_modules");
+        let store = node_modules.join(".craft");
+        let a = store.join("a@1.0.0/node_modules/a");
+        let b = store.join("@s+b@1.0.0/node_modules/@s/b");
+
+        assert!(fs::symlink_metadata(a.join("package.json"))
+            .unwrap()
+            .is_file());
+        assert_eq!(fs::read_link(node_modules.join("a")).unwrap(), a);
+        assert!(!node_modules.join("@s").exists());
+        assert_eq!(
+            fs::read_link(store.join("a@1.0.0/node_modules/@s/b")).unwrap(),
+            b
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_isolated_peers_are_linked_next_to_the_package() {
+        let dir = link_isolated("isolated-peers").await;
+        let node_modules = dir.join("node_modules");
+        let store = node_modules.join(".craft");
+
+        // The peer of b is the c of the project
+        assert_eq!(
+            fs::read_link(store.join("@s+b@1.0.0/node_modules/c")).unwrap(),
+            store.join("c@1.0.0/node_modules/c")
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_isolated_store_is_pruned() {
+        let dir = link_isolated("isolated-prune").await;
+        let node_modules = dir.join("node_modules");
+
+        assert!(!node_modules.join(".craft/old@1.0.0").exists());
+    }
+}
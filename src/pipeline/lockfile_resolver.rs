@@ -0,0 +1,885 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Sender;
+
+use async_trait::async_trait;
+
+use super::artifacts::{ExtractArtifactsMap, ResolveArtifacts, ResolvedItem};
+use crate::actors::{PackageType, PreprocessDependencyInstall};
+use crate::cache::RegistryKey;
+use crate::contracts::{Lockfile, Logger, Phase, Pipe, ProgressAction};
+use crate::errors::{ExecutionError, LockfileError};
+use crate::lockfile::constants::{
+    CURRENT_IMPORTER, DEPENDENCIES, DEV_DEPENDENCIES, OPT_DEPENDENCIES,
+};
+use crate::lockfile::lock_file_actor::LockFileActor;
+use crate::lockfile::lockfile_structure::{
+    ImporterSections, LockfileResolution, LockfileStructure, ResolvedDependencies,
+};
+use crate::logger::CraftLogger;
+use crate::package::{
+    BinType, Distribution, NpmPackage, Overrides, PackageJson, PackageMetaHandler, PackageRecorder,
+    Platform,
+};
+use crate::registry::{DirectoryRegistry, NpmRegistry};
+
+// ─── LockfileResolverPipe ────────────────────────────────────────────────────
+
+/// Resolves the dependency graph from pnpm-lock.yaml instead of the registry.
+/// Used for `--frozen-lockfile` installs, so no metadata is fetched at all.
+pub struct LockfileResolverPipe {
+    packages: Vec<PackageType>,
+    importer: String,
+    directory: PathBuf,
+    platform: Platform,
+    tx: Sender<ProgressAction>,
+}
+
+// ─────────────────────────────────────────────────────────────────────────────
+
+impl LockfileResolverPipe {
+    pub fn new(packages: Vec<PackageType>, tx: Sender<ProgressAction>) -> Self {
+        Self {
+            packages,
+            importer: CURRENT_IMPORTER.to_string(),
+            directory: PathBuf::from("."),
+            platform: Platform::current(),
+            tx,
+        }
+    }
+
+    /// Resolves the packages of another importer, e.g. a project of the workspace
+    pub fn for_importer(mut self, importer: &str, directory: &Path) -> Self {
+        self.importer = importer.to_string();
+        self.directory = directory.to_path_buf();
+        self
+    }
+
+    /// Installs for another platform, e.g. with `--os` and `--cpu`
+    pub fn with_platform(mut self, platform: Platform) -> Self {
+        self.platform = platform;
+        self
+    }
+
+    /// pnpm appends the resolved peers to versions, e.g. `1.0.0(react@18.2.0)`
+    fn strip_peer_suffix(version: &str) -> &str {
+        version.split('(').next().unwrap_or(version)
+    }
+
+    /// The key a package is installed under and the key it is locked as,
+    /// they differ for aliases, which are locked like `react@18.3.1`
+    fn locked_keys(name: &str, version: &str) -> (RegistryKey, RegistryKey) {
+        let version = Self::strip_peer_suffix(version);
+        let target = LockFileActor::alias_target(version).unwrap_or_else(|| RegistryKey {
+            name: name.to_string(),
+            version: version.to_string(),
+        });
+        let key = RegistryKey {
+            name: name.to_string(),
+            version: target.version.clone(),
+        };
+        (key, target)
+    }
+
+    fn importer_section<'a>(
+        importer: &'a ImporterSections,
+        package_type: &PackageType,
+    ) -> Option<&'a ResolvedDependencies> {
+        match package_type {
+            PackageType::Dev(_) => importer.dev_dependencies.as_ref(),
+            PackageType::Prod(_) => importer.dependencies.as_ref(),
+            PackageType::Optional(_) => importer.optional_dependencies.as_ref(),
+            PackageType::Peer(_) => importer.peer_dependencies.as_ref(),
+            PackageType::Global(_) => None,
+        }
+    }
+
+    /// Makes sure that every section of package.json matches the importer of the lockfile
+    pub fn verify_importer(
+        package_json: &PackageJson,
+        importer: &ImporterSections,
+    ) -> Result<(), LockfileError> {
+        let sections = [
+            (
+                DEPENDENCIES,
+                &package_json.dependencies,
+                &importer.dependencies,
+            ),
+            (
+                DEV_DEPENDENCIES,
+                &package_json.dev_dependencies,
+                &importer.dev_dependencies,
+            ),
+            (
+                OPT_DEPENDENCIES,
+                &package_json.optional_dependencies,
+                &importer.optional_dependencies,
+            ),
+        ];
+
+        let mut problems = vec![];
+
+        for (section, wanted, locked) in sections {
+            let wanted = wanted.clone().unwrap_or_default();
+            let locked = locked.clone().unwrap_or_default();
+
+            let mut wanted_names = wanted.keys().collect::<Vec<_>>();
+            wanted_names.sort();
+            for name in wanted_names {
+                let specifier = &wanted[name];
+                match locked.get(name) {
+                    None => problems.push(format!("{} {} is missing", section, name)),
+                    // Wildcards are recorded with the resolved version
+                    Some(l) if specifier != "*" && &l.specifier != specifier => {
+                        problems.push(format!(
+                            "{} {} is {} in package.json but {} in the lockfile",
+                            section, name, specifier, l.specifier
+                        ))
+                    }
+                    _ => {}
+                }
+            }
+
+            let mut locked_names = locked.keys().collect::<Vec<_>>();
+            locked_names.sort();
+            for name in locked_names {
+                if !wanted.contains_key(name) {
+                    problems.push(format!("{} {} is not in package.json", section, name));
+                }
+            }
+        }
+
+        if problems.is_empty() {
+            return Ok(());
+        }
+
+        Err(LockfileError::OutdatedLockfile(problems.join(", ")))
+    }
+
+    /// The lockfile was resolved with other overrides than package.json has now
+    pub fn verify_overrides(
+        lockfile: &LockfileStructure,
+        overrides: &Overrides,
+    ) -> Result<(), LockfileError> {
+        let wanted = overrides.to_map();
+        let locked = lockfile.overrides.clone().unwrap_or_default();
+
+        if wanted != locked {
+            return Err(LockfileError::OutdatedLockfile(
+                "the overrides changed".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn to_npm_package(
+        key: &RegistryKey,
+        meta: &PackageMetaHandler,
+    ) -> Result<NpmPackage, LockfileError> {
+        let dist = match &meta.resolution {
+            Some(LockfileResolution::Integrity(i)) => Distribution {
+                integrity: Some(i.integrity.clone()),
+                tarball: NpmRegistry::tarball_url(&key.name, &key.version),
+                ..Default::default()
+            },
+            Some(LockfileResolution::Tarball(t)) => Distribution {
+                integrity: t.integrity.clone(),
+                tarball: t.tarball.clone(),
+                ..Default::default()
+            },
+            Some(LockfileResolution::Directory(_)) => Distribution::default(),
+            Some(LockfileResolution::GitRepository(g)) => Distribution {
+                tarball: format!("{}#{}", g.repo, g.commit),
+                ..Default::default()
+            },
+            _ => {
+                return Err(LockfileError::InvalidStructure(format!(
+                    "{} has no resolution that can be installed",
+                    key
+                )))
+            }
+        };
+
+        Ok(NpmPackage {
+            name: key.name.clone(),
+            version: key.version.clone(),
+            peer_dependencies: meta.peer_dependencies.clone(),
+            peer_dependencies_meta: meta.peer_dependencies_meta.clone(),
+            engines: meta.engines.clone(),
+            os: meta.os.clone(),
+            cpu: meta.cpu.clone(),
+            libc: meta.libc.clone(),
+            bin: meta.bin.clone(),
+            dist,
+            resolution: meta.resolution.clone(),
+            ..Default::default()
+        })
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn resolve_pkg(
+        lockfile: &LockfileStructure,
+        snapshots: &HashMap<&str, &PackageMetaHandler>,
+        key: RegistryKey,
+        specifier: String,
+        package_type: PackageType,
+        parent: Option<Vec<RegistryKey>>,
+        artifacts: &mut ResolveArtifacts,
+        recorder: &mut PackageRecorder,
+        platform: &Platform,
+    ) -> Result<(), LockfileError> {
+        let (key, target) = Self::locked_keys(&key.name, &key.version);
+
+        // Every path to a package resolves the same subtree, so it is walked once.
+        // A direct dependency that was reached through another one first is
+        // still installed at the top level
+        if let Some(item) = artifacts.get_mut(&key.to_string()) {
+            recorder.record(key, item.package.clone(), &parent);
+            if parent.is_none() {
+                item.parent = None;
+                item.specifier = specifier;
+                item.package_type = package_type;
+            }
+            return Ok(());
+        }
+
+        let meta = lockfile
+            .packages
+            .as_ref()
+            .and_then(|p| p.get(&target.to_string()))
+            .ok_or_else(|| {
+                LockfileError::OutdatedLockfile(format!("{} is missing from packages", target))
+            })?;
+        let mut package = Self::to_npm_package(&target, meta)?;
+        if target.name != key.name {
+            package.name = key.name.clone();
+            package.alias_of = Some(target.name.clone());
+        }
+        let snapshot = snapshots.get(target.to_string().as_str());
+        package.optional_dependencies = snapshot.and_then(|s| s.optional_dependencies.clone());
+        let is_optional = matches!(package_type, PackageType::Optional(_));
+
+        if !platform.supports(&package) {
+            if is_optional {
+                CraftLogger::verbose(format!(
+                    "Skipping optional dependency {}, it does not support {}-{}",
+                    key, platform.os, platform.cpu
+                ));
+                recorder.skip_optional(&key);
+                return Ok(());
+            }
+            CraftLogger::warn(format!(
+                "{} does not support {}-{}",
+                key, platform.os, platform.cpu
+            ));
+        }
+
+        artifacts.insert(
+            key.to_string(),
+            ResolvedItem::new(package.clone(), parent.clone(), specifier, package_type),
+        );
+        recorder.record(key.clone(), package, &parent);
+
+        // Everything below an optional dependency is optional as well
+        let children = snapshot
+            .iter()
+            .flat_map(|s| {
+                let dependencies = s.dependencies.iter().flatten();
+                let optional = s.optional_dependencies.iter().flatten();
+                dependencies
+                    .map(move |d| (d, is_optional))
+                    .chain(optional.map(|d| (d, true)))
+            })
+            .map(|((name, version), optional)| (name.clone(), version.clone(), optional))
+            .collect::<Vec<_>>();
+
+        let mut trace = parent.unwrap_or_default();
+        trace.push(key);
+
+        for (name, version, optional) in children {
+            let version = Self::strip_peer_suffix(&version).to_string();
+
+            // Circular dependencies would never end
+            let (child, _) = Self::locked_keys(&name, &version);
+            if trace.contains(&child) {
+                continue;
+            }
+
+            let spec = format!("{}@{}", name, version);
+            let package_type = match optional {
+                true => PackageType::Optional(spec),
+                false => PackageType::Prod(spec),
+            };
+            Self::resolve_pkg(
+                lockfile,
+                snapshots,
+                RegistryKey {
+                    name: name.clone(),
+                    version: version.clone(),
+                },
+                version,
+                package_type,
+                Some(trace.clone()),
+                artifacts,
+                recorder,
+                platform,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    pub fn resolve(
+        &self,
+        lockfile: &LockfileStructure,
+    ) -> Result<(ResolveArtifacts, PackageRecorder), LockfileError> {
+        let importer = lockfile
+            .importers
+            .as_ref()
+            .and_then(|i| i.get(&self.importer))
+            .ok_or_else(|| {
+                LockfileError::OutdatedLockfile(format!("importer {} is missing", self.importer))
+            })?;
+
+        let snapshots = lockfile
+            .snapshots
+            .iter()
+            .flatten()
+            .map(|(k, v)| (Self::strip_peer_suffix(k), v))
+            .collect::<HashMap<_, _>>();
+
+        let mut artifacts = ResolveArtifacts::new();
+        let mut recorder = PackageRecorder::default();
+
+        for package_type in &self.packages {
+            let (name, _) = package_type.get_parts();
+            let locked = Self::importer_section(importer, package_type)
+                .and_then(|section| section.get(&name))
+                .ok_or_else(|| LockfileError::OutdatedLockfile(format!("{} is missing", name)))?;
+
+            let key = RegistryKey {
+                name,
+                version: Self::strip_peer_suffix(&locked.version).to_string(),
+            };
+
+            Self::resolve_pkg(
+                lockfile,
+                &snapshots,
+                key,
+                locked.specifier.clone(),
+                package_type.clone(),
+                None,
+                &mut artifacts,
+                &mut recorder,
+                &self.platform,
+            )?;
+        }
+
+        // Peers that were installed automatically are not in package.json
+        let mut peers = importer
+            .peer_dependencies
+            .iter()
+            .flatten()
+            .collect::<Vec<_>>();
+        peers.sort_by_key(|(name, _)| name.as_str());
+        for (name, locked) in peers {
+            let is_requested = self
+                .packages
+                .iter()
+                .any(|package_type| &package_type.get_parts().0 == name);
+            if is_requested {
+                continue;
+            }
+
+            let version = Self::strip_peer_suffix(&locked.version).to_string();
+            Self::resolve_pkg(
+                lockfile,
+                &snapshots,
+                RegistryKey {
+                    name: name.clone(),
+                    version: version.clone(),
+                },
+                locked.specifier.clone(),
+                PackageType::Peer(format!("{}@{}", name, version)),
+                None,
+                &mut artifacts,
+                &mut recorder,
+                &self.platform,
+            )?;
+        }
+
+        Ok((artifacts, recorder))
+    }
+
+    /// Lockfiles only know whether a package has binaries, the mapping itself
+    /// is read from the package.json of the extracted package.
+    pub fn attach_binaries(recorder: &mut PackageRecorder, extracted: &ExtractArtifactsMap) {
+        recorder
+            .main_packages
+            .values_mut()
+            .chain(recorder.sub_dependencies.values_mut())
+            .filter(|p| p.has_bin == Some(true) && p.bin.is_none())
+            .for_each(|p| {
+                let package_json = match &p.resolution {
+                    Some(LockfileResolution::Directory(d)) => {
+                        DirectoryRegistry::path(&d.directory).join("package.json")
+                    }
+                    _ => {
+                        let Some(item) = extracted.get(&p.target().to_string()) else {
+                            return;
+                        };
+                        item.unzip_at.join("package").join("package.json")
+                    }
+                };
+                p.bin = std::fs::read_to_string(package_json)
+                    .ok()
+                    .and_then(|c| serde_json::from_str::<serde_json::Value>(&c).ok())
+                    .and_then(|v| v.get("bin").cloned())
+                    .and_then(|b| serde_json::from_value::<BinType>(b).ok());
+            });
+    }
+}
+
+// ─── Implementations ─────────────────────────────────────────────────────────
+
+#[async_trait]
+impl Pipe<(ResolveArtifacts, PackageRecorder)> for LockfileResolverPipe {
+    async fn run(&mut self) -> Result<(ResolveArtifacts, PackageRecorder), ExecutionError> {
+        let _ = self.tx.send(ProgressAction::new(Phase::Resolving));
+
+        let lockfile = LockFileActor::read_lock_file(Path::new("pnpm-lock.yaml"))?;
+        let package_json = PreprocessDependencyInstall::read_package_json_in(&self.directory)?;
+
+        let importer = lockfile
+            .importers
+            .as_ref()
+            .and_then(|i| i.get(&self.importer))
+            .ok_or_else(|| {
+                LockfileError::OutdatedLockfile(format!("importer {} is missing", self.importer))
+            })?;
+        Self::verify_importer(&package_json, importer)?;
+        Self::verify_overrides(&lockfile, &Overrides::load(Path::new(".")))?;
+
+        Ok(self.resolve(&lockfile)?)
+    }
+}
+
+// ─── Tests ───────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contracts::PipeArtifact;
+    use crate::pipeline::fixtures::key;
+
+    const LOCKFILE: &str = r#"
+lockfileVersion: '9.0'
+
+importers:
+  .:
+    dependencies:
+      express:
+        specifier: ^4.18.0
+        version: 4.18.2
+    devDependencies:
+      '@types/node':
+        specifier: ^20.0.0
+        version: 20.1.0
+
+packages:
+
+  '@types/node@20.1.0':
+    resolution: {integrity: sha512-types}
+
+  body-parser@1.20.1:
+    resolution: {integrity: sha512-body}
+
+  express@4.18.2:
+    resolution: {integrity: sha512-express}
+    engines: {node: '>= 0.10.0'}
+
+snapshots:
+
+  '@types/node@20.1.0': {}
+
+  body-parser@1.20.1:
+    dependencies:
+      express: 4.18.2
+
+  express@4.18.2:
+    dependencies:
+      body-parser: 1.20.1
+"#;
+
+    fn lockfile() -> LockfileStructure {
+        serde_yaml_ng::from_str(LOCKFILE).unwrap()
+    }
+
+    fn package_json(content: &str) -> PackageJson {
+        serde_json::from_str(content).unwrap()
+    }
+
+    #[test]
+    fn test_verify_importer_matches() {
+        let lockfile = lockfile();
+        let package_json = package_json(
+            r#"{
+                "dependencies": {"express": "^4.18.0"},
+                "devDependencies": {"@types/node": "^20.0.0"}
+            }"#,
+        );
+
+        let importer = &lockfile.importers.as_ref().unwrap()[CURRENT_IMPORTER];
+        assert!(LockfileResolverPipe::verify_importer(&package_json, importer).is_ok());
+    }
+
+    #[test]
+    fn test_verify_importer_detects_drift() {
+        let lockfile = lockfile();
+        let package_json = package_json(
+            r#"{
+                "dependencies": {"express": "^5.0.0", "lodash": "^4.0.0"}
+            }"#,
+        );
+
+        let importer = &lockfile.importers.as_ref().unwrap()[CURRENT_IMPORTER];
+        let err = LockfileResolverPipe::verify_importer(&package_json, importer)
+            .unwrap_err()
+            .to_string();
+
+        assert!(err.contains("dependencies express is ^5.0.0 in package.json but ^4.18.0"));
+        assert!(err.contains("dependencies lodash is missing"));
+        assert!(err.contains("devDependencies @types/node is not in package.json"));
+    }
+
+    #[test]
+    fn test_resolve_from_lockfile() {
+        let (tx, _rx) = std::sync::mpsc::channel();
+        let pipe = LockfileResolverPipe::new(
+            vec![
+                PackageType::Prod("express@^4.18.0".to_string()),
+                PackageType::Dev("@types/node@^20.0.0".to_string()),
+            ],
+            tx,
+        );
+
+        let (artifacts, recorder) = pipe.resolve(&lockfile()).unwrap();
+
+        assert_eq!(artifacts.get_artifacts().len(), 3);
+        let express = artifacts.get("express@4.18.2").unwrap();
+        assert!(express.parent.is_none());
+        assert_eq!(express.specifier, "^4.18.0");
+        assert_eq!(
+            express.package.dist.tarball,
+            "https://registry.npmjs.org/express/-/express-4.18.2.tgz"
+        );
+
+        let types = artifacts.get("@types/node@20.1.0").unwrap();
+        assert_eq!(
+            types.package.dist.tarball,
+            "https://registry.npmjs.org/@types/node/-/node-20.1.0.tgz"
+        );
+
+        // The circular dependency back to express is not followed
+        let body_parser = artifacts.get("body-parser@1.20.1").unwrap();
+        assert_eq!(body_parser.parent.as_ref().unwrap().len(), 1);
+
+        assert_eq!(recorder.main_packages.len(), 2);
+        assert_eq!(recorder.sub_dependencies.len(), 1);
+    }
+
+    #[test]
+    fn test_resolve_aliases_as_their_target() {
+        let lockfile = serde_yaml_ng::from_str::<LockfileStructure>(
+            r#"
+lockfileVersion: '9.0'
+
+importers:
+  .:
+    dependencies:
+      my-react:
+        specifier: npm:react@18.3.1
+        version: react@18.3.1
+      app:
+        specifier: ^1.0.0
+        version: 1.0.0
+
+packages:
+  app@1.0.0:
+    resolution: {integrity: sha512-app}
+  react@18.3.1:
+    resolution: {integrity: sha512-react}
+
+snapshots:
+  app@1.0.0:
+    dependencies:
+      your-react: react@18.3.1
+  react@18.3.1: {}
+"#,
+        )
+        .unwrap();
+        let (tx, _rx) = std::sync::mpsc::channel();
+        let pipe = LockfileResolverPipe::new(
+            vec![
+                PackageType::Prod("my-react@npm:react@18.3.1".to_string()),
+                PackageType::Prod("app@^1.0.0".to_string()),
+            ],
+            tx,
+        );
+
+        let (artifacts, _) = pipe.resolve(&lockfile).unwrap();
+
+        let my_react = artifacts.get("my-react@18.3.1").unwrap();
+        assert!(my_react.parent.is_none());
+        assert_eq!(my_react.package.target().to_string(), "react@18.3.1");
+        assert_eq!(
+            my_react.package.dist.tarball,
+            "https://registry.npmjs.org/react/-/react-18.3.1.tgz"
+        );
+
+        let your_react = artifacts.get("your-react@18.3.1").unwrap();
+        assert_eq!(your_react.package.alias_of.as_deref(), Some("react"));
+        assert_eq!(your_react.parent, Some(vec![key("app", "1.0.0")]));
+    }
+
+    #[test]
+    fn test_resolve_installed_peers_of_the_importer() {
+        let mut lockfile = lockfile();
+        let importer = lockfile
+            .importers
+            .as_mut()
+            .unwrap()
+            .get_mut(CURRENT_IMPORTER)
+            .unwrap();
+        importer.peer_dependencies =
+            serde_yaml_ng::from_str("'@types/node': {specifier: '>=20', version: 20.1.0}").unwrap();
+        let (tx, _rx) = std::sync::mpsc::channel();
+        let pipe =
+            LockfileResolverPipe::new(vec![PackageType::Prod("express@^4.18.0".to_string())], tx);
+
+        let (artifacts, recorder) = pipe.resolve(&lockfile).unwrap();
+
+        let types = artifacts.get("@types/node@20.1.0").unwrap();
+        assert!(types.parent.is_none());
+        assert!(matches!(types.package_type, PackageType::Peer(_)));
+        assert_eq!(types.specifier, ">=20");
+        assert_eq!(recorder.main_packages.len(), 2);
+    }
+
+    #[test]
+    fn test_resolve_direct_dependency_that_is_also_transitive() {
+        let lockfile = serde_yaml_ng::from_str::<LockfileStructure>(
+            r#"
+lockfileVersion: '9.0'
+
+importers:
+  .:
+    dependencies:
+      a:
+        specifier: ^1.0.0
+        version: 1.0.0
+      b:
+        specifier: ^2.0.0
+        version: 2.0.0
+
+packages:
+  a@1.0.0:
+    resolution: {integrity: sha512-a}
+  b@2.0.0:
+    resolution: {integrity: sha512-b}
+
+snapshots:
+  a@1.0.0:
+    dependencies:
+      b: 2.0.0
+  b@2.0.0: {}
+"#,
+        )
+        .unwrap();
+        let a = PackageType::Prod("a@^1.0.0".to_string());
+        let b = PackageType::Prod("b@^2.0.0".to_string());
+
+        for packages in [vec![a.clone(), b.clone()], vec![b, a]] {
+            let (tx, _rx) = std::sync::mpsc::channel();
+            let pipe = LockfileResolverPipe::new(packages, tx);
+
+            let (artifacts, recorder) = pipe.resolve(&lockfile).unwrap();
+
+            let b = artifacts.get("b@2.0.0").unwrap();
+            assert!(b.parent.is_none());
+            assert_eq!(b.specifier, "^2.0.0");
+            assert_eq!(artifacts.get_artifacts().len(), 2);
+            assert!(recorder.main_packages.contains_key(&key("b", "2.0.0")));
+            assert_eq!(
+                recorder.sub_dependencies[&key("b", "2.0.0")].depth_traces,
+                Some(vec![vec![key("a", "1.0.0")]])
+            );
+        }
+    }
+
+    #[test]
+    fn test_resolve_missing_importer_entry() {
+        let (tx, _rx) = std::sync::mpsc::channel();
+        let pipe = LockfileResolverPipe::new(vec![PackageType::Prod("lodash@^4".to_string())], tx);
+
+        assert!(matches!(
+            pipe.resolve(&lockfile()),
+            Err(LockfileError::OutdatedLockfile(_))
+        ));
+    }
+
+    #[test]
+    fn test_resolve_tarball_and_directory() {
+        let lockfile: LockfileStructure = serde_yaml_ng::from_str(
+            r#"
+lockfileVersion: '9.0'
+
+importers:
+  .:
+    dependencies:
+      remote:
+        specifier: https://example.com/remote-1.0.0.tgz
+        version: 1.0.0+sha.abcdef1
+      vendored:
+        specifier: file:../vendored
+        version: file:../vendored
+
+packages:
+
+  remote@1.0.0+sha.abcdef1:
+    resolution: {integrity: sha512-remote, tarball: https://example.com/remote-1.0.0.tgz}
+
+  vendored@file:../vendored:
+    resolution: {directory: ../vendored, type: directory}
+
+snapshots:
+
+  remote@1.0.0+sha.abcdef1: {}
+
+  vendored@file:../vendored: {}
+"#,
+        )
+        .unwrap();
+        let (tx, _rx) = std::sync::mpsc::channel();
+        let pipe = LockfileResolverPipe::new(
+            vec![
+                PackageType::Prod("remote@https://example.com/remote-1.0.0.tgz".to_string()),
+                PackageType::Prod("vendored@file:../vendored".to_string()),
+            ],
+            tx,
+        );
+
+        let (artifacts, recorder) = pipe.resolve(&lockfile).unwrap();
+
+        let remote = artifacts.get("remote@1.0.0+sha.abcdef1").unwrap();
+        assert_eq!(
+            remote.package.dist.tarball,
+            "https://example.com/remote-1.0.0.tgz"
+        );
+        assert!(matches!(
+            remote.package.resolution,
+            Some(LockfileResolution::Tarball(_))
+        ));
+
+        let vendored = artifacts.get("vendored@file:../vendored").unwrap();
+        match &vendored.package.resolution {
+            Some(LockfileResolution::Directory(d)) => assert_eq!(d.directory, "../vendored"),
+            other => panic!("Unexpected resolution {:?}", other),
+        }
+        assert_eq!(recorder.main_packages.len(), 2);
+    }
+
+    const OPTIONAL_LOCKFILE: &str = r#"
+lockfileVersion: '9.0'
+
+importers:
+  .:
+    dependencies:
+      vite:
+        specifier: ^5.0.0
+        version: 5.0.0
+      fsevents-only:
+        specifier: ^1.0.0
+        version: 1.0.0
+
+packages:
+
+  vite@5.0.0:
+    resolution: {integrity: sha512-vite}
+
+  esbuild@0.19.0:
+    resolution: {integrity: sha512-esbuild}
+
+  '@esbuild/linux-x64@0.19.0':
+    resolution: {integrity: sha512-linux}
+    cpu: [x64]
+    os: [linux]
+
+  '@esbuild/darwin-arm64@0.19.0':
+    resolution: {integrity: sha512-darwin}
+    cpu: [arm64]
+    os: [darwin]
+
+  fsevents-only@1.0.0:
+    resolution: {integrity: sha512-fsevents}
+    os: [darwin]
+
+snapshots:
+
+  vite@5.0.0:
+    dependencies:
+      esbuild: 0.19.0
+
+  esbuild@0.19.0:
+    optionalDependencies:
+      '@esbuild/linux-x64': 0.19.0
+      '@esbuild/darwin-arm64': 0.19.0
+
+  '@esbuild/linux-x64@0.19.0': {}
+
+  '@esbuild/darwin-arm64@0.19.0': {}
+
+  fsevents-only@1.0.0: {}
+"#;
+
+    fn resolve_for_linux() -> (ResolveArtifacts, PackageRecorder) {
+        let lockfile: LockfileStructure = serde_yaml_ng::from_str(OPTIONAL_LOCKFILE).unwrap();
+        let (tx, _rx) = std::sync::mpsc::channel();
+        let pipe = LockfileResolverPipe::new(
+            vec![
+                PackageType::Prod("vite@^5.0.0".to_string()),
+                PackageType::Prod("fsevents-only@^1.0.0".to_string()),
+            ],
+            tx,
+        )
+        .with_platform(Platform {
+            os: "linux".to_string(),
+            cpu: "x64".to_string(),
+            libc: None,
+        });
+
+        pipe.resolve(&lockfile).unwrap()
+    }
+
+    #[test]
+    fn test_resolve_follows_transitive_optional_dependencies() {
+        let (artifacts, recorder) = resolve_for_linux();
+
+        let linux = artifacts.get("@esbuild/linux-x64@0.19.0").unwrap();
+        assert!(linux.is_optional());
+        assert_eq!(linux.parent.as_ref().unwrap().len(), 2);
+        assert!(!artifacts.get("esbuild@0.19.0").unwrap().is_optional());
+
+        assert!(artifacts.get("@esbuild/darwin-arm64@0.19.0").is_none());
+        assert!(recorder.skipped_optional.contains("@esbuild/darwin-arm64"));
+    }
+
+    #[test]
+    fn test_resolve_keeps_required_packages_of_other_platforms() {
+        let (artifacts, recorder) = resolve_for_linux();
+
+        assert!(artifacts.get("fsevents-only@1.0.0").is_some());
+        assert!(!recorder.skipped_optional.contains("fsevents-only"));
+    }
+}
@@ -1,16 +1,28 @@
 mod artifacts;
 pub mod binary_templates;
 mod cache_clean;
+mod dedupe;
 mod downloader;
 mod extractor;
+mod hoist;
+mod lifecycle;
 mod linker;
+mod lockfile_resolver;
+mod peers;
 mod resolver;
 
 pub use resolver::ResolverPipe;
 
+pub use dedupe::Dedupe;
 pub use downloader::DownloaderPipe;
 pub use extractor::ExtractorPipe;
-pub use linker::LinkerPipe;
+pub use hoist::hoist;
+pub use lifecycle::{path_with_bins, BuildPolicy, LifecycleScriptsPipe};
+pub use linker::{LinkerPipe, NodeLinker};
+pub use lockfile_resolver::LockfileResolverPipe;
+pub use peers::PeerCheck;
 
-pub use artifacts::ResolvedItem;
+#[cfg(test)]
+pub use artifacts::fixtures;
+pub use artifacts::{ExtractArtifactsMap, ResolvedItem};
 pub use cache_clean::CacheCleanPipe;
@@ -0,0 +1,263 @@
+use std::collections::BTreeMap;
+
+use nodejs_semver::{Range, Version};
+
+use super::artifacts::ResolvedItem;
+use crate::cache::RegistryKey;
+
+// ─── PeerCheck ───────────────────────────────────────────────────────────────
+
+/// Checks the peer dependencies of the resolved packages. A peer is provided
+/// by a package node would find from the one asking for it, i.e. one in the
+/// node_modules of the package itself or of any of its parents.
+#[derive(Debug, Default)]
+pub struct PeerCheck {
+    /// Peers nothing provides, with where they would have to be installed
+    pub missing: Vec<MissingPeer>,
+    pub issues: Vec<PeerIssue>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MissingPeer {
+    pub name: String,
+    pub range: String,
+    /// Next to the package asking for it, `None` is the top level where the
+    /// peers of direct dependencies are installed
+    pub parent: Option<Vec<RegistryKey>>,
+}
+
+/// A peer dependency that is missing or outside of the range it asks for
+#[derive(Debug, Clone, PartialEq)]
+pub struct PeerIssue {
+    /// The package asking for the peer and its parents, the outermost first
+    pub chain: Vec<RegistryKey>,
+    pub name: String,
+    pub range: String,
+    /// The installed version, `None` if the peer is missing
+    pub found: Option<String>,
+}
+
+// ─────────────────────────────────────────────────────────────────────────────
+
+impl PeerCheck {
+    pub fn run(resolved: &[ResolvedItem]) -> Self {
+        let mut check = Self::default();
+
+        for item in resolved {
+            let Some(peers) = &item.package.peer_dependencies else {
+                continue;
+            };
+
+            let mut chain = item.parent.clone().unwrap_or_default();
+            chain.push(item.package.clone().into());
+
+            let mut names = peers.keys().collect::<Vec<_>>();
+            names.sort();
+            for name in names {
+                let range = &peers[name];
+                let is_optional = item
+                    .package
+                    .peer_dependencies_meta
+                    .as_ref()
+                    .and_then(|meta| meta.get(name))
+                    .and_then(|meta| meta.optional)
+                    .unwrap_or_default();
+
+                // The closest package wins, like node looks it up
+                let provider = resolved
+                    .iter()
+                    .filter(|p| &p.package.name == name)
+                    .filter(|p| chain.starts_with(p.parent.as_deref().unwrap_or_default()))
+                    .max_by_key(|p| p.parent.as_ref().map_or(0, |p| p.len()));
+
+                match provider {
+                    Some(provider) if Self::satisfies(&provider.package.version, range) => {}
+                    Some(provider) => check.issues.push(PeerIssue {
+                        chain: chain.clone(),
+                        name: name.clone(),
+                        range: range.clone(),
+                        found: Some(provider.package.version.clone()),
+                    }),
+                    None if is_optional => {}
+                    None => {
+                        let missing = MissingPeer {
+                            name: name.clone(),
+                            range: range.clone(),
+                            parent: item.parent.clone(),
+                        };
+                        if !check.missing.contains(&missing) {
+                            check.missing.push(missing);
+                        }
+                        check.issues.push(PeerIssue {
+                            chain: chain.clone(),
+                            name: name.clone(),
+                            range: range.clone(),
+                            found: None,
+                        });
+                    }
+                }
+            }
+        }
+
+        check
+    }
+
+    /// Ranges that are no semver ranges, like `workspace:*`, are trusted
+    fn satisfies(version: &str, range: &str) -> bool {
+        match (version.parse::<Version>(), range.parse::<Range>()) {
+            (Ok(version), Ok(range)) => version.satisfies(&range),
+            _ => true,
+        }
+    }
+
+    /// The issues as a tree below the packages asking for the peers
+    pub fn report(&self) -> String {
+        let mut root = ReportNode::default();
+        for issue in &self.issues {
+            let node = issue.chain.iter().fold(&mut root, |node, key| {
+                node.children
+                    .entry(format!("{} {}", key.name, key.version))
+                    .or_default()
+            });
+            node.issues.push(match &issue.found {
+                Some(found) => format!(
+                    "✕ unmet peer {}@{}: found {}",
+                    issue.name, issue.range, found
+                ),
+                None => format!("✕ missing peer {}@{}", issue.name, issue.range),
+            });
+        }
+
+        let mut report = "Issues with peer dependencies found\n.\n".to_string();
+        root.render("", &mut report);
+        report
+    }
+}
+
+#[derive(Debug, Default)]
+struct ReportNode {
+    children: BTreeMap<String, ReportNode>,
+    issues: Vec<String>,
+}
+
+impl ReportNode {
+    fn render(&self, prefix: &str, report: &mut String) {
+        let count = self.issues.len() + self.children.len();
+        let mut index = 0;
+        let connector = |index: &mut usize| {
+            *index += 1;
+            match *index == count {
+                true => ("└─", "  "),
+                false => ("├─", "│ "),
+            }
+        };
+
+        for issue in &self.issues {
+            let (branch, _) = connector(&mut index);
+            report.push_str(&format!("{}{}─ {}\n", prefix, branch, issue));
+        }
+        for (label, child) in &self.children {
+            let (branch, indent) = connector(&mut index);
+            report.push_str(&format!("{}{}┬ {}\n", prefix, branch, label));
+            child.render(&format!("{}{}", prefix, indent), report);
+        }
+    }
+}
+
+// ─── Tests ───────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::fixtures::{item, key};
+
+    fn ui() -> ResolvedItem {
+        let mut ui = item("ui", "1.0.0", &[]).with_peers(&[("react", "^18"), ("vue", "^3")]);
+        ui.package.peer_dependencies_meta =
+            serde_json::from_value(serde_json::json!({"vue": {"optional": true}})).unwrap();
+        ui
+    }
+
+    fn hooks() -> ResolvedItem {
+        item("hooks", "1.0.0", &[key("app", "2.0.0")])
+            .with_peers(&[("react", "^17"), ("redux", "^5")])
+    }
+
+    #[test]
+    fn test_unmet_peer_is_an_issue_but_not_missing() {
+        let check = PeerCheck::run(&[item("react", "17.0.2", &[]), ui()]);
+
+        assert!(check.missing.is_empty());
+        assert_eq!(
+            check.issues,
+            vec![PeerIssue {
+                chain: vec![key("ui", "1.0.0")],
+                name: "react".to_string(),
+                range: "^18".to_string(),
+                found: Some("17.0.2".to_string()),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_optional_peer_may_be_missing() {
+        let check = PeerCheck::run(&[item("react", "18.3.1", &[]), ui()]);
+
+        assert!(check.missing.is_empty());
+        assert!(check.issues.is_empty());
+    }
+
+    #[test]
+    fn test_peer_is_provided_by_the_parents() {
+        let check = PeerCheck::run(&[
+            item("react", "17.0.2", &[]),
+            item("app", "2.0.0", &[]),
+            hooks(),
+        ]);
+
+        assert_eq!(
+            check.missing,
+            vec![MissingPeer {
+                name: "redux".to_string(),
+                range: "^5".to_string(),
+                parent: Some(vec![key("app", "2.0.0")]),
+            }]
+        );
+        assert_eq!(check.issues.len(), 1);
+    }
+
+    #[test]
+    fn test_peers_of_direct_dependencies_are_installed_at_the_top_level() {
+        let check = PeerCheck::run(&[item("ui", "1.0.0", &[]).with_peers(&[("react", "^18")])]);
+
+        assert_eq!(
+            check.missing,
+            vec![MissingPeer {
+                name: "react".to_string(),
+                range: "^18".to_string(),
+                parent: None,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_report() {
+        let check = PeerCheck::run(&[
+            item("react", "17.0.2", &[]),
+            ui(),
+            item("app", "2.0.0", &[]),
+            hooks(),
+        ]);
+
+        assert_eq!(
+            check.report(),
+            "Issues with peer dependencies found\n\
+             .\n\
+             ├─┬ app 2.0.0\n\
+             │ └─┬ hooks 1.0.0\n\
+             │   └── ✕ missing peer redux@^5\n\
+             └─┬ ui 1.0.0\n\
+             \u{20}\u{20}└── ✕ unmet peer react@^18: found 17.0.2\n"
+        );
+    }
+}
@@ -1,20 +1,31 @@
 use crate::actors::PackageType;
 use crate::cache::{RegistryCache, RegistryKey};
-use crate::contracts::{PersistentCache, Phase, Pipe, ProgressAction, Registry};
+use crate::contracts::{
+    Logger, PersistentCache, Phase, Pipe, PipeArtifact, ProgressAction, Registry,
+};
 use crate::errors::{ExecutionError, NetworkError};
+use crate::lockfile::constants::CURRENT_IMPORTER;
+use crate::lockfile::lockfile_structure::LockfileResolution;
 use crate::logger::CraftLogger;
-use crate::package::{NpmPackage, Package, PackageRecorder};
+use crate::network::NetworkMode;
+use crate::package::{
+    NpmPackage, Overrides, Package, PackageRecorder, Platform, Registry as PackageRegistry,
+};
 use crate::registry::GitRegistry;
 use crate::registry::NpmRegistry;
+use crate::registry::{DirectoryRegistry, TarballRegistry, WorkspaceRegistry};
 use async_recursion::async_recursion;
 use async_trait::async_trait;
 use futures::future;
 use futures::future::join_all;
 use futures::lock::Mutex;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
 use std::sync::mpsc::Sender;
 use std::sync::Arc;
 
 use super::artifacts::{ResolveArtifacts, ResolvedItem};
+use super::PeerCheck;
 
 // ─── ResolverPipe ────────────────────────────────────────────────────────────
 
@@ -27,6 +38,13 @@ pub struct ResolverPipe<C: PersistentCache<NpmPackage>> {
     git_registry: GitRegistry,
 
     artifacts: Arc<Mutex<ResolveArtifacts>>,
+    overrides: Arc<Overrides>,
+    platform: Arc<Platform>,
+    auto_install_peers: bool,
+    /// Shared by all resolutions, so every packument is fetched once
+    npm_registry: Arc<NpmRegistry>,
+    /// Of the importer, relative to the root of the project
+    directory: PathBuf,
 
     tx: Sender<ProgressAction>,
 }
@@ -42,10 +60,70 @@ impl ResolverPipe<RegistryCache> {
             cache: Arc::new(Mutex::new(un_arced_cache)),
             git_registry: GitRegistry::new(),
             artifacts: Arc::new(Mutex::new(un_arced_articated)),
+            overrides: Arc::new(Overrides::load(Path::new("."))),
+            platform: Arc::new(Platform::current()),
+            auto_install_peers: true,
+            npm_registry: Arc::new(NpmRegistry::new()),
+            directory: PathBuf::from(CURRENT_IMPORTER),
             tx,
         }
     }
 
+    /// Resolves the packages of another importer, e.g. a project of the workspace
+    pub fn for_importer(mut self, importer: &str) -> Self {
+        self.directory = PathBuf::from(importer);
+        self
+    }
+
+    /// Installs for another platform, e.g. with `--os` and `--cpu`
+    pub fn with_platform(mut self, platform: Platform) -> Self {
+        self.platform = Arc::new(platform);
+        self
+    }
+
+    /// Whether peers nothing provides are installed, `autoInstallPeers`
+    pub fn with_auto_install_peers(mut self, auto_install_peers: bool) -> Self {
+        self.auto_install_peers = auto_install_peers;
+        self
+    }
+
+    /// Resolves from the caches only with `--offline`
+    pub fn with_network_mode(mut self, network_mode: NetworkMode) -> Self {
+        self.npm_registry = Arc::new(NpmRegistry::new().with_network_mode(network_mode));
+        self
+    }
+
+    /// Whether the package can only be fetched from the network, npm
+    /// packages may come from cached packuments
+    fn needs_network(package: &Package) -> bool {
+        match package.registry {
+            PackageRegistry::Git => true,
+            PackageRegistry::Tarball => !TarballRegistry::is_local(&package.raw_version),
+            _ => false,
+        }
+    }
+
+    /// Merges the packages missing offline into one error, other errors are
+    /// only logged
+    fn collect_offline(results: Vec<Result<(), NetworkError>>) -> Result<(), NetworkError> {
+        let mut missing = vec![];
+        for result in results {
+            match result {
+                Err(NetworkError::Offline(packages)) => missing.extend(packages),
+                Err(e) => log::error!("Error is {}", e),
+                Ok(()) => {}
+            }
+        }
+
+        missing.sort();
+        missing.dedup();
+        match missing.is_empty() {
+            true => Ok(()),
+            false => Err(NetworkError::Offline(missing)),
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
     #[async_recursion]
     async fn resolve_pkg(
         package: &Package,
@@ -53,11 +131,23 @@ impl ResolverPipe<RegistryCache> {
         package_recorder: Arc<Mutex<PackageRecorder>>,
         cache_arc: Arc<Mutex<RegistryCache>>,
         artifacts: Arc<Mutex<ResolveArtifacts>>,
+        overrides: Arc<Overrides>,
+        platform: Arc<Platform>,
+        npm_registry: Arc<NpmRegistry>,
     ) -> Result<(), NetworkError> {
         CraftLogger::verbose(format!("Resolving package: {}", package));
+        let network_mode = npm_registry.network_mode();
         let mut cache = { cache_arc.lock().await.clone() };
 
-        let cached_pkg = cache.get(&package.clone().into()).await;
+        // Only npm versions are ranges the registry cache can answer, an
+        // alias would be cached under the name of the alias. Online, the
+        // packuments tell whether newer versions satisfy the range.
+        let is_cacheable = package.is_npm() && !package.is_alias();
+        let cached_pkg = if is_cacheable && network_mode != NetworkMode::Online {
+            cache.get(&package.clone().into()).await
+        } else {
+            None
+        };
 
         if let Some(pkg) = cached_pkg.clone() {
             if artifacts.lock().await.get(&pkg.to_string()).is_some() {
@@ -65,130 +155,154 @@ impl ResolverPipe<RegistryCache> {
             }
         }
 
-        let final_key: RegistryKey;
-        if let Some(pkg) = cached_pkg {
-            CraftLogger::verbose(format!("Package found in cache: {}", package));
-            final_key = pkg.clone().into();
-            artifacts.lock().await.insert(
-                pkg.to_string().clone(),
-                ResolvedItem::new(
-                    pkg.clone(),
-                    parent.clone(),
-                    package.raw_version.clone(),
-                    package.package_type.clone(),
-                ),
-            );
+        let is_optional = matches!(package.package_type, PackageType::Optional(_));
+        let package_spec = package;
+        let package = if let Some(pkg) = cached_pkg {
+            CraftLogger::verbose(format!("Package found in cache: {}", package_spec));
+            pkg
         } else {
-            let remote_package = NpmRegistry::new().fetch(package).await.unwrap();
-
-            let pkg_cache_key = remote_package.to_string();
-            final_key = remote_package.clone().into();
-            {
-                artifacts.lock().await.insert(
-                    pkg_cache_key.clone(),
-                    ResolvedItem::new(
-                        remote_package.clone(),
-                        parent.clone(),
-                        package.raw_version.clone(),
-                        package.package_type.clone(),
-                    ),
-                );
-            }
+            let fetched = match package_spec.registry {
+                _ if network_mode == NetworkMode::Offline && Self::needs_network(package_spec) => {
+                    Err(NetworkError::Offline(vec![package_spec.to_string()]))
+                }
+                PackageRegistry::Npm => npm_registry.fetch(package_spec).await,
+                PackageRegistry::Git => GitRegistry::new().fetch(package_spec).await,
+                PackageRegistry::Tarball => TarballRegistry::new().fetch(package_spec).await,
+                PackageRegistry::Directory => DirectoryRegistry.fetch(package_spec).await,
+                PackageRegistry::Workspace => WorkspaceRegistry::new().fetch(package_spec).await,
+            };
+            let remote_package = match fetched {
+                Ok(remote_package) => remote_package,
+                // An optional package that can't be fetched is left out
+                Err(e) if is_optional => {
+                    CraftLogger::warn(format!(
+                        "Skipping optional dependency {}: {}",
+                        package_spec, e
+                    ));
+                    return Ok(());
+                }
+                Err(e) => return Err(e),
+            };
 
-            {
+            if is_cacheable {
                 let mut cache = cache_arc.lock().await;
                 cache
                     .set(&remote_package.clone().into(), remote_package.clone())
                     .await;
+                cache.get(&remote_package.clone().into()).await.unwrap()
+            } else {
+                remote_package
             }
-        }
-
-        let mut package = {
-            let mut cache = cache_arc.lock().await;
-            cache.get(&final_key).await.clone().unwrap()
         };
+        let final_key: RegistryKey = package.clone().into();
 
-        {
-            let mut package_recorder = package_recorder.lock().await;
-            match parent {
-                None => {
-                    // This is okay as we only insert the same version
-                    if !package_recorder.main_packages.contains_key(&final_key) {
-                        package_recorder
-                            .main_packages
-                            .insert(final_key.clone(), package.clone().into());
-                    }
-
-                    // Else can be skipped because we won't update the parent as a consequence
-                }
-                Some(ref parents) => {
-                    // It can be that multiple dependencies have this as a sub dependency
-                    match package_recorder.sub_dependencies.get_mut(&final_key) {
-                        None => {
-                            package.depth_traces = Some(vec![parents.clone()]);
-
-                            package_recorder
-                                .sub_dependencies
-                                .insert(final_key.clone(), package.clone().into());
-                        }
-                        Some(p) => match p.depth_traces {
-                            None => {
-                                p.depth_traces = Some(vec![parents.clone()]);
-                            }
-                            Some(ref mut d) => {
-                                d.push(parents.clone());
-                            }
-                        },
-                    }
-                    package_recorder
-                        .sub_dependencies
-                        .insert(package.clone().into(), package.clone().into());
-                }
+        if !platform.supports(&package) {
+            if is_optional {
+                CraftLogger::verbose(format!(
+                    "Skipping optional dependency {}, it does not support {}-{}",
+                    package, platform.os, platform.cpu
+                ));
+                package_recorder.lock().await.skip_optional(&final_key);
+                return Ok(());
             }
+            CraftLogger::warn(format!(
+                "{} does not support {}-{}",
+                package, platform.os, platform.cpu
+            ));
         }
 
+        artifacts.lock().await.insert(
+            package.to_string(),
+            ResolvedItem::new(
+                package.clone(),
+                parent.clone(),
+                package_spec.raw_version.clone(),
+                package_spec.package_type.clone(),
+            ),
+        );
+
+        package_recorder
+            .lock()
+            .await
+            .record(final_key.clone(), package.clone(), &parent);
+
+        // Everything below an optional dependency is optional as well
+        let optional_dependencies = package.optional_dependencies.clone().unwrap_or_default();
+        let mut dependencies = package
+            .dependencies
+            .clone()
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|(name, _)| !optional_dependencies.contains_key(name))
+            .map(|(name, version)| (name, version, is_optional))
+            .collect::<Vec<_>>();
+        dependencies.extend(
+            optional_dependencies
+                .into_iter()
+                .map(|(name, version)| (name, version, true)),
+        );
+
+        // Local paths of a linked folder start at that folder
+        let directory = match &package.resolution {
+            Some(LockfileResolution::Directory(d)) => PathBuf::from(&d.directory),
+            _ => package_spec.directory.clone(),
+        };
+
         let mut jobs = Vec::new();
-        if let Some(deps) = package.dependencies {
-            // This is correct because sub dependencies are always only dependencies
-            for (name, version) in deps {
-                let pkg = format!("{}@{}", name, version);
-
-                let package = Package::new(PackageType::Prod(pkg));
-
-                let parent = if let Some(ref p) = parent {
-                    let mut p_cloned = p.clone();
-                    p_cloned.push(final_key.clone());
-                    Some(p_cloned)
-                } else {
-                    Some(vec![final_key.clone()])
-                };
-                let pra = package_recorder.clone();
-                let cache = cache_arc.clone();
-                let artifacts = artifacts.clone();
-                let handle = tokio::spawn(async move {
-                    Self::resolve_pkg(&package, parent, pra, cache, artifacts).await
-                });
-                jobs.push(handle);
+        for (name, version, optional) in dependencies {
+            let parent = if let Some(ref p) = parent {
+                let mut p_cloned = p.clone();
+                p_cloned.push(final_key.clone());
+                Some(p_cloned)
+            } else {
+                Some(vec![final_key.clone()])
+            };
+
+            // The root project may pin or remove transitive dependencies
+            let ancestors = parent.as_deref().unwrap_or_default();
+            let Some(version) = overrides.apply(ancestors, &name, &version) else {
+                CraftLogger::verbose(format!("Dependency {} removed by override", name));
+                continue;
+            };
+            let pkg = format!("{}@{}", name, version);
+
+            let package = match optional {
+                true => Package::new(PackageType::Optional(pkg)),
+                false => Package::new(PackageType::Prod(pkg)),
             }
+            .with_directory(&directory);
+
+            let pra = package_recorder.clone();
+            let cache = cache_arc.clone();
+            let artifacts = artifacts.clone();
+            let overrides = overrides.clone();
+            let platform = platform.clone();
+            let npm_registry = npm_registry.clone();
+            let handle = tokio::spawn(async move {
+                Self::resolve_pkg(
+                    &package,
+                    parent,
+                    pra,
+                    cache,
+                    artifacts,
+                    overrides,
+                    platform,
+                    npm_registry,
+                )
+                .await
+            });
+            jobs.push(handle);
         }
 
-        let results: Vec<_> = future::join_all(jobs).await;
-        // Iterate over the results
-        for result in results.into_iter() {
-            let jh_handle = result;
-            match jh_handle {
-                Ok(jh_handle) => {
-                    if let Err(e) = jh_handle {
-                        log::error!("Error is {}", e.to_string())
-                    }
-                }
-                Err(e) => {
-                    log::error!("{}", e)
-                }
+        let mut results = vec![];
+        for result in future::join_all(jobs).await {
+            match result {
+                Ok(result) => results.push(result),
+                Err(e) => log::error!("{}", e),
             }
         }
 
-        Ok(())
+        Self::collect_offline(results)
     }
 
     pub async fn resolve(&self) -> Result<PackageRecorder, NetworkError> {
@@ -202,24 +316,95 @@ impl ResolverPipe<RegistryCache> {
             let pra = package_recorder_arc.clone();
             let cache = self.cache.clone();
             let artifacts = self.artifacts.clone();
+            let overrides = self.overrides.clone();
+            let platform = self.platform.clone();
+            let npm_registry = self.npm_registry.clone();
+            let directory = self.directory.clone();
             let job = tokio::spawn(async move {
                 {
-                    let package = Package::new(pkg);
-                    Self::resolve_pkg(&package, None, pra, cache, artifacts).await
+                    let package = Package::new(pkg).with_directory(&directory);
+                    Self::resolve_pkg(
+                        &package,
+                        None,
+                        pra,
+                        cache,
+                        artifacts,
+                        overrides,
+                        platform,
+                        npm_registry,
+                    )
+                    .await
                 }
             });
             jobs.push(job)
         }
 
         let results = join_all(jobs).await;
-        for result in results.into_iter() {
-            let jh_handle = result.unwrap();
-            if let Err(e) = jh_handle {
-                log::error!("Error is {}", e.to_string())
-            }
+        Self::collect_offline(results.into_iter().map(|r| r.unwrap()).collect())?;
+
+        if self.auto_install_peers {
+            self.install_missing_peers(package_recorder_arc.clone())
+                .await;
         }
+
         Ok(package_recorder_arc.clone().lock().await.clone())
     }
+
+    /// Installs the peers nothing provides until the installed ones need no
+    /// further peers. Each peer is tried once per place.
+    async fn install_missing_peers(&self, package_recorder: Arc<Mutex<PackageRecorder>>) {
+        let mut attempted = HashSet::new();
+
+        loop {
+            let resolved = self.artifacts.lock().await.get_artifacts();
+            let missing = PeerCheck::run(&resolved)
+                .missing
+                .into_iter()
+                .filter(|peer| attempted.insert((peer.name.clone(), peer.parent.clone())))
+                .collect::<Vec<_>>();
+            if missing.is_empty() {
+                return;
+            }
+
+            let mut jobs = vec![];
+            for peer in missing {
+                let ancestors = peer.parent.as_deref().unwrap_or_default();
+                let Some(range) = self.overrides.apply(ancestors, &peer.name, &peer.range) else {
+                    continue;
+                };
+                CraftLogger::verbose(format!("Installing missing peer {}@{}", peer.name, range));
+
+                let package = Package::new(PackageType::Peer(format!("{}@{}", peer.name, range)));
+                let pra = package_recorder.clone();
+                let cache = self.cache.clone();
+                let artifacts = self.artifacts.clone();
+                let overrides = self.overrides.clone();
+                let platform = self.platform.clone();
+                let npm_registry = self.npm_registry.clone();
+                jobs.push(tokio::spawn(async move {
+                    Self::resolve_pkg(
+                        &package,
+                        peer.parent,
+                        pra,
+                        cache,
+                        artifacts,
+                        overrides,
+                        platform,
+                        npm_registry,
+                    )
+                    .await
+                }));
+            }
+
+            for result in join_all(jobs).await {
+                match result {
+                    Ok(Err(e)) => log::error!("Error is {}", e),
+                    Err(e) => log::error!("{}", e),
+                    Ok(Ok(())) => {}
+                }
+            }
+        }
+    }
 }
 
 #[async_trait]
@@ -234,6 +419,7 @@ impl Pipe<(ResolveArtifacts, PackageRecorder)> for ResolverPipe<RegistryCache> {
                 let artifacts = { self.artifacts.lock().await.clone() };
                 Ok((artifacts, e))
             }
+            Err(e @ NetworkError::Offline(_)) => Err(e.into()),
             Err(e) => Err(ExecutionError::JobExecutionFailed(
                 "Resolve".to_owned(),
                 e.to_string(),
@@ -241,3 +427,31 @@ impl Pipe<(ResolveArtifacts, PackageRecorder)> for ResolverPipe<RegistryCache> {
         }
     }
 }
+
+// ─── Tests ───────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_offline_lists_missing_packages() {
+        let (tx, _rx) = std::sync::mpsc::channel();
+        let packages = vec![
+            PackageType::Prod("craft-not-cached@^1.0.0".to_string()),
+            PackageType::Optional("craft-optional-not-cached@^2.0.0".to_string()),
+        ];
+
+        let result = ResolverPipe::new(packages, tx)
+            .with_network_mode(NetworkMode::Offline)
+            .run()
+            .await;
+
+        match result {
+            Err(ExecutionError::Network(NetworkError::Offline(missing))) => {
+                assert_eq!(missing, vec!["craft-not-cached@^1.0.0"])
+            }
+            other => panic!("Unexpected result {:?}", other),
+        }
+    }
+}
@@ -1,7 +1,8 @@
-use crate::actors::{ExecActor, PackageType, PreprocessDependencyInstall, RunActor};
-use crate::command::ProgramDesire;
+use crate::actors::{ExecActor, PackageType, PreprocessDependencyInstall, RemoveActor, RunActor};
+use crate::command::{Install, ProgramDesire};
 use crate::contracts::Logger;
 use crate::logger::CraftLogger;
+use crate::workspace::WORKSPACE;
 use crate::{
     actors::{CacheCleanActor, InstallActor},
     command::{Command, SubCommand},
@@ -10,6 +11,7 @@ use crate::{
     ui::UIProgress,
 };
 use std::{
+    env,
     sync::mpsc::Receiver,
     thread::{self, JoinHandle},
 };
@@ -25,23 +27,42 @@ impl Program {
         })
     }
 
+    /// Installs the dependencies of package.json, of every project in a workspace
+    async fn project_install(
+        program_desire: ProgramDesire,
+    ) -> Result<InstallActor, ExecutionError> {
+        let preprocess = PreprocessDependencyInstall::new(program_desire.clone());
+
+        if WORKSPACE.is_workspace() {
+            let importers = preprocess.run_workspace(&WORKSPACE).await?;
+            Ok(InstallActor::with_importers(importers, program_desire))
+        } else {
+            let deps_to_install = preprocess.run().await.unwrap();
+            Ok(InstallActor::new(deps_to_install, program_desire))
+        }
+    }
+
     pub async fn execute(&mut self, args: Command) -> Result<(), ExecutionError> {
         let command = args.command.clone();
 
         match command {
             SubCommand::Install(args_install) => {
                 if args.is_install_without_args() {
-                    let program_desire: ProgramDesire = args_install.into();
-                    let deps_to_install = PreprocessDependencyInstall::new(program_desire)
-                        .run()
-                        .await
-                        .unwrap();
+                    let mut install_actor = Self::project_install(args_install.into()).await?;
 
-                    let err = InstallActor::new(deps_to_install).start().await;
+                    let err = install_actor.start().await;
                     if let Err(err) = err {
                         CraftLogger::error(format!("{}", err));
+                        return Err(err);
                     }
                 } else {
+                    if args_install.frozen_lockfile {
+                        return Err(ExecutionError::JobExecutionFailed(
+                            "install".to_string(),
+                            "--frozen-lockfile can't be combined with packages".to_string(),
+                        ));
+                    }
+
                     let packages = args_install
                         .packages
                         .clone()
@@ -62,39 +83,66 @@ impl Program {
                         })
                         .collect::<Vec<PackageType>>();
 
-                    InstallActor::new(packages).start().await.unwrap();
+                    let save = !args_install.save_global;
+                    let mut install_actor = InstallActor::new(packages, args_install.into());
+                    if save {
+                        install_actor = install_actor.saving();
+                    }
+                    install_actor.start().await.unwrap();
                 }
 
                 Ok(())
             }
+            SubCommand::Dedupe => {
+                let program_desire = ProgramDesire::from(Install::default());
+                let result = Self::project_install(program_desire)
+                    .await?
+                    .deduping()
+                    .start()
+                    .await;
+                if let Err(err) = &result {
+                    CraftLogger::error(format!("{}", err));
+                }
+
+                result
+            }
+            SubCommand::Remove(args) => {
+                let result = RemoveActor::new(args.packages).start().await;
+                if let Err(err) = &result {
+                    CraftLogger::error(format!("{}", err));
+                }
+
+                result
+            }
             SubCommand::Cache(args) => {
                 CacheCleanActor::new(args).start().await;
 
                 Ok(())
             }
             SubCommand::Run(r) => {
-                let json = PreprocessDependencyInstall::get_script()?;
+                // The scripts and their environment come from the package in `dir`
+                let cwd = env::current_dir().expect("Error getting cwd");
+                let directory = match &r.directory {
+                    Some(directory) => cwd.join(directory),
+                    None => cwd,
+                };
+                let package_json = PreprocessDependencyInstall::read_package_json_in(&directory)?;
 
-                if json.is_empty() {
+                if package_json.scripts.as_ref().is_none_or(|s| s.is_empty()) {
                     return Err(ExecutionError::JobExecutionFailed(
                         "script must be exactly 1".to_string(),
                         "script must be exactly 1".to_string(),
                     ));
                 }
 
-                if let Some(script) = json.get(&r.script) {
-                    CraftLogger::info(format!("Running script: {}", script));
-                    CraftLogger::info(format!("Command: {}", script));
-                    RunActor::new(script.clone(), r.directory).start().await?;
-
-                    Ok(())
-                } else {
+                let result = RunActor::new(r.script.clone(), directory, r.args, package_json)
+                    .start()
+                    .await;
+                if let Err(ExecutionError::ScriptNotFound(_)) = &result {
                     CraftLogger::error(format!("Script {} not found", r.script));
-                    Err(ExecutionError::ScriptNotFound(format!(
-                        "Script {} not found",
-                        r.script
-                    )))
                 }
+
+                result
             }
             SubCommand::Exec(e) => {
                 CraftLogger::info(format!("Running command: {}", e.command));
@@ -0,0 +1,122 @@
+use std::env;
+use std::path::PathBuf;
+
+use async_trait::async_trait;
+
+use crate::{
+    contracts::Registry,
+    errors::NetworkError,
+    lockfile::lockfile_structure::{DirectoryResolution, LockfileResolution},
+    package::{NpmPackage, Package},
+};
+
+const PREFIXES: [&str; 2] = ["file:", "link:"];
+
+// ─── DirectoryRegistry ───────────────────────────────────────────────────────
+
+/// Resolves dependencies on local folders like `file:../foo` or
+/// `link:../foo`. Those are linked as they are, without being downloaded.
+#[derive(Debug)]
+pub struct DirectoryRegistry;
+
+// ─────────────────────────────────────────────────────────────────────────────
+
+impl DirectoryRegistry {
+    /// The folder of the dependency, relative paths belong to the root of
+    /// the project
+    pub fn path(directory: &str) -> PathBuf {
+        let directory = PREFIXES
+            .iter()
+            .find_map(|prefix| directory.strip_prefix(prefix))
+            .unwrap_or(directory);
+
+        env::current_dir().unwrap().join(directory)
+    }
+}
+
+#[async_trait]
+impl Registry for DirectoryRegistry {
+    async fn fetch(&self, package: &Package) -> Result<NpmPackage, NetworkError> {
+        log::info!("Reading directory: {}", package.to_string());
+
+        let (prefix, directory) = PREFIXES
+            .iter()
+            .find_map(|prefix| Some((*prefix, package.raw_version.strip_prefix(prefix)?)))
+            .unwrap_or(("file:", &package.raw_version));
+        let directory = package.root_relative(directory);
+
+        let manifest_path = Self::path(&directory).join("package.json");
+        let manifest = tokio::fs::read_to_string(&manifest_path)
+            .await
+            .map_err(|_| NetworkError::FailedToFetchVersion(package.to_string()))?;
+        let mut local_package = serde_json::from_str::<NpmPackage>(&manifest)
+            .map_err(|_| NetworkError::FailedToFetchVersion(package.to_string()))?;
+
+        // The location is the version, the same folder is always the same
+        // package, no matter which package.json asks for it
+        local_package.name = package.name.clone();
+        local_package.version = format!("{}{}", prefix, directory);
+        local_package.resolution = Some(LockfileResolution::Directory(DirectoryResolution {
+            directory,
+        }));
+
+        Ok(local_package)
+    }
+}
+
+// ─── Tests ───────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actors::PackageType;
+    use crate::pipeline::fixtures::TempDir;
+
+    #[tokio::test]
+    async fn test_fetch_directory() {
+        let dir = TempDir::new("directory");
+        std::fs::write(
+            dir.join("package.json"),
+            r#"{"name": "vendored", "version": "1.0.0", "dependencies": {"lodash": "^4.0.0"}}"#,
+        )
+        .unwrap();
+
+        let spec = format!("foo@link:{}", dir.display());
+        let package = DirectoryRegistry
+            .fetch(&Package::new(PackageType::Prod(spec)))
+            .await
+            .unwrap();
+
+        assert_eq!(package.name, "foo");
+        assert_eq!(package.version, format!("link:{}", dir.display()));
+        assert!(package.dependencies.unwrap().contains_key("lodash"));
+        match package.resolution {
+            Some(LockfileResolution::Directory(d)) => {
+                assert_eq!(d.directory, dir.display().to_string())
+            }
+            other => panic!("Unexpected resolution {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fetch_relative_to_the_declaring_package() {
+        let dir = TempDir::new("directory-relative");
+        std::fs::create_dir_all(dir.join("vendored")).unwrap();
+        std::fs::write(
+            dir.join("vendored/package.json"),
+            r#"{"name": "vendored", "version": "1.0.0"}"#,
+        )
+        .unwrap();
+
+        let package = Package::new(PackageType::Prod("foo@file:../vendored".to_string()))
+            .with_directory(&dir.join("app"));
+        let package = DirectoryRegistry.fetch(&package).await.unwrap();
+
+        let vendored = dir.join("vendored").display().to_string();
+        assert_eq!(package.version, format!("file:{}", vendored));
+        match package.resolution {
+            Some(LockfileResolution::Directory(d)) => assert_eq!(d.directory, vendored),
+            other => panic!("Unexpected resolution {:?}", other),
+        }
+    }
+}
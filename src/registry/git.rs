@@ -1,45 +1,380 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
 use async_trait::async_trait;
+use lazy_static::lazy_static;
+use sha1::{Digest, Sha1};
+use tokio::process::Command;
+use tokio::sync::Mutex;
 
 use crate::{
+    cache::GIT_CACHE_FOLDER,
     contracts::Registry,
     errors::NetworkError,
-    package::{FullPackage, NpmPackage, Package},
+    fs::get_config_dir,
+    lockfile::lockfile_structure::{GitRepositoryResolution, LockfileResolution},
+    package::{NpmPackage, Package},
 };
 
-#[allow(unused_variables)]
+lazy_static! {
+    /// Repositories that were already cloned or fetched by this process. The
+    /// lock also keeps two jobs from updating the same mirror at once.
+    static ref UPDATED_MIRRORS: Mutex<HashSet<PathBuf>> = Mutex::new(HashSet::new());
+}
+
+const SEMVER_PREFIX: &str = "semver:";
+
+// ─── GitRegistry ─────────────────────────────────────────────────────────────
+
+/// Resolves dependencies like `github:owner/repo#tag` by keeping a bare
+/// mirror of every repository in the git cache folder.
 #[derive(Debug)]
 pub struct GitRegistry {
-    #[allow(dead_code)]
-    http: reqwest::Client,
+    directory: PathBuf,
 }
 
-impl GitRegistry {
-    pub fn new() -> Self {
+/// A parsed git dependency, e.g. `git+https://host/repo.git#semver:^1.2`
+#[derive(Debug, PartialEq)]
+pub struct GitSpec {
+    pub repo: String,
+    pub committish: Option<String>,
+    pub semver: Option<String>,
+}
+
+// ─────────────────────────────────────────────────────────────────────────────
+
+impl GitSpec {
+    pub fn parse(spec: &str) -> Self {
+        let (location, fragment) = match spec.split_once('#') {
+            Some((location, fragment)) => (location, Some(fragment)),
+            None => (spec, None),
+        };
+
+        let hosted = [
+            ("github:", "https://github.com"),
+            ("gitlab:", "https://gitlab.com"),
+            ("bitbucket:", "https://bitbucket.org"),
+        ];
+        let repo = hosted
+            .iter()
+            .find_map(|(prefix, host)| {
+                location
+                    .strip_prefix(prefix)
+                    .map(|path| format!("{}/{}.git", host, path.trim_end_matches(".git")))
+            })
+            .unwrap_or_else(|| location.trim_start_matches("git+").to_string());
+
+        let (committish, semver) = match fragment.filter(|f| !f.is_empty()) {
+            Some(f) => match f.strip_prefix(SEMVER_PREFIX) {
+                Some(range) => (None, Some(range.to_string())),
+                None => (Some(f.to_string()), None),
+            },
+            None => (None, None),
+        };
+
         Self {
-            http: reqwest::Client::new(),
+            repo,
+            committish,
+            semver,
         }
     }
 }
 
 impl GitRegistry {
-    async fn get_archive(&self, _package: &Package) -> Result<FullPackage, NetworkError> {
-        todo!()
+    pub fn new() -> Self {
+        Self::with_directory(get_config_dir(GIT_CACHE_FOLDER.clone()))
+    }
+
+    pub fn with_directory(directory: PathBuf) -> Self {
+        Self { directory }
+    }
+
+    fn mirror_path(&self, repo: &str) -> PathBuf {
+        self.directory
+            .join(hex::encode(Sha1::digest(repo.as_bytes())))
+    }
+
+    async fn git(repo: &str, dir: Option<&Path>, args: &[&str]) -> Result<String, NetworkError> {
+        let mut command = Command::new("git");
+        if let Some(dir) = dir {
+            command.arg("-C").arg(dir);
+        }
+
+        let output = command
+            .args(args)
+            .env("GIT_TERMINAL_PROMPT", "0")
+            .output()
+            .await
+            .map_err(|e| NetworkError::Git(repo.to_string(), e.to_string()))?;
+
+        if !output.status.success() {
+            return Err(NetworkError::Git(
+                repo.to_string(),
+                String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            ));
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    }
+
+    /// Clones the repository or fetches new commits, once per process
+    async fn update_mirror(&self, repo: &str) -> Result<PathBuf, NetworkError> {
+        let mirror = self.mirror_path(repo);
+        let mut updated = UPDATED_MIRRORS.lock().await;
+        if updated.contains(&mirror) {
+            return Ok(mirror);
+        }
+
+        if mirror.exists() {
+            Self::git(
+                repo,
+                Some(&mirror),
+                &["fetch", "--quiet", "--prune", "origin"],
+            )
+            .await?;
+        } else {
+            tokio::fs::create_dir_all(&self.directory).await?;
+            let target = mirror.to_string_lossy().to_string();
+            Self::git(repo, None, &["clone", "--quiet", "--mirror", repo, &target]).await?;
+        }
+
+        updated.insert(mirror.clone());
+        Ok(mirror)
+    }
+
+    async fn resolve_commit(&self, spec: &GitSpec, mirror: &Path) -> Result<String, NetworkError> {
+        let committish = match &spec.semver {
+            Some(range) => Self::highest_tag(spec, mirror, range).await?,
+            None => spec.committish.clone().unwrap_or("HEAD".to_string()),
+        };
+
+        let commit = Self::git(
+            &spec.repo,
+            Some(mirror),
+            &[
+                "rev-parse",
+                "--verify",
+                &format!("{}^{{commit}}", committish),
+            ],
+        )
+        .await?;
+
+        Ok(commit.trim().to_string())
+    }
+
+    async fn highest_tag(
+        spec: &GitSpec,
+        mirror: &Path,
+        range: &str,
+    ) -> Result<String, NetworkError> {
+        let range: nodejs_semver::Range = range.parse().map_err(|_| {
+            NetworkError::FailedToFetchVersion(format!("{}#semver:{}", spec.repo, range))
+        })?;
+
+        let tags = Self::git(&spec.repo, Some(mirror), &["tag", "--list"]).await?;
+        tags.lines()
+            .filter_map(|tag| {
+                let version: nodejs_semver::Version = tag.trim_start_matches('v').parse().ok()?;
+                version.satisfies(&range).then_some((version, tag))
+            })
+            .max_by(|a, b| a.0.cmp(&b.0))
+            .map(|(_, tag)| format!("refs/tags/{}", tag))
+            .ok_or_else(|| {
+                NetworkError::FailedToFetchVersion(format!("{}#semver:{}", spec.repo, range))
+            })
+    }
+
+    /// Packs the commit like a registry tarball, with all files below
+    /// `package/`, so it can be stored in the packages cache.
+    pub async fn archive(
+        &self,
+        resolution: &GitRepositoryResolution,
+        dest: &Path,
+    ) -> Result<(), NetworkError> {
+        let mirror = self.update_mirror(&resolution.repo).await?;
+        let output = dest.to_string_lossy().to_string();
+
+        Self::git(
+            &resolution.repo,
+            Some(&mirror),
+            &[
+                "archive",
+                "--format=tar.gz",
+                "--prefix=package/",
+                "-o",
+                &output,
+                &resolution.commit,
+            ],
+        )
+        .await?;
+
+        Ok(())
     }
 }
 
 #[async_trait]
 impl Registry for GitRegistry {
     async fn fetch(&self, package: &Package) -> Result<NpmPackage, NetworkError> {
-        let pkg = self.get_archive(package).await?;
+        log::info!("Fetching git package: {}", package.to_string());
+
+        let spec = GitSpec::parse(&package.raw_version);
+        let mirror = self.update_mirror(&spec.repo).await?;
+        let commit = self.resolve_commit(&spec, &mirror).await?;
+
+        let manifest = Self::git(
+            &spec.repo,
+            Some(&mirror),
+            &["show", &format!("{}:package.json", commit)],
+        )
+        .await?;
+        let mut remote_package = serde_json::from_str::<NpmPackage>(&manifest)
+            .map_err(|e| NetworkError::Git(spec.repo.clone(), e.to_string()))?;
+
+        // The commit is part of the version, so each commit gets its own
+        // entry in the caches and the lockfile
+        remote_package.name = package.name.clone();
+        remote_package.version = format!("{}+git.{}", remote_package.version, &commit[..7]);
+        remote_package.dist.tarball = format!("{}#{}", spec.repo, commit);
+        remote_package.resolution =
+            Some(LockfileResolution::GitRepository(GitRepositoryResolution {
+                repo: spec.repo,
+                commit,
+                path: None,
+            }));
+
+        Ok(remote_package)
+    }
+}
+
+// ─── Tests ───────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actors::PackageType;
+    use crate::pipeline::fixtures::TempDir;
+    use crate::tar::Gzip;
+    use std::process::Command;
 
-        if let Some((_, remote_package)) = pkg.versions.iter().next() {
-            return Ok(remote_package.clone());
+    fn git(dir: &Path, args: &[&str]) -> String {
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(dir)
+            .args([
+                "-c",
+                "user.name=craft",
+                "-c",
+                "user.email=craft@example.com",
+            ])
+            .args(["-c", "commit.gpgsign=false", "-c", "tag.gpgsign=false"])
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{:?}", output);
+        String::from_utf8_lossy(&output.stdout).trim().to_string()
+    }
+
+    /// A bare repository with the tags v1.0.0, v1.2.0 and v2.0.0 on HEAD
+    fn create_repository(name: &str) -> TempDir {
+        let root = TempDir::new(&format!("git-{}", name));
+        let work = root.join("work");
+        std::fs::create_dir_all(&work).unwrap();
+
+        git(&work, &["init", "--quiet"]);
+        for version in ["1.0.0", "1.2.0", "2.0.0"] {
+            std::fs::write(
+                work.join("package.json"),
+                format!(r#"{{"name": "origin-name", "version": "{}"}}"#, version),
+            )
+            .unwrap();
+            git(&work, &["add", "."]);
+            git(&work, &["commit", "--quiet", "-m", version]);
+            git(&work, &["tag", &format!("v{}", version)]);
+        }
+        git(&root, &["clone", "--quiet", "--bare", "work", "repo.git"]);
+
+        root
+    }
+
+    async fn fetch(registry: &GitRegistry, root: &Path, fragment: &str) -> NpmPackage {
+        let spec = format!(
+            "dep@git+file://{}{}",
+            root.join("repo.git").display(),
+            fragment
+        );
+        registry
+            .fetch(&Package::new(PackageType::Prod(spec)))
+            .await
+            .unwrap()
+    }
+
+    #[test]
+    fn test_parse_spec() {
+        assert_eq!(
+            GitSpec::parse("github:owner/repo#v1.0.0"),
+            GitSpec {
+                repo: "https://github.com/owner/repo.git".to_string(),
+                committish: Some("v1.0.0".to_string()),
+                semver: None,
+            }
+        );
+        assert_eq!(
+            GitSpec::parse("git+https://example.com/repo.git#semver:^1.2"),
+            GitSpec {
+                repo: "https://example.com/repo.git".to_string(),
+                committish: None,
+                semver: Some("^1.2".to_string()),
+            }
+        );
+        assert_eq!(
+            GitSpec::parse("git+ssh://git@github.com/owner/repo.git"),
+            GitSpec {
+                repo: "ssh://git@github.com/owner/repo.git".to_string(),
+                committish: None,
+                semver: None,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fetch_resolves_commits() {
+        let root = create_repository("fetch");
+        let registry = GitRegistry::with_directory(root.join("cache"));
+        let work = root.join("work");
+
+        let package = fetch(&registry, &root, "#semver:^1.0.0").await;
+        let commit = git(&work, &["rev-parse", "v1.2.0"]);
+        assert_eq!(package.name, "dep");
+        assert_eq!(package.version, format!("1.2.0+git.{}", &commit[..7]));
+        match package.resolution {
+            Some(LockfileResolution::GitRepository(resolution)) => {
+                assert_eq!(resolution.commit, commit)
+            }
+            other => panic!("Unexpected resolution {:?}", other),
         }
 
-        println!("Failed to fetch version: {}", package);
+        let package = fetch(&registry, &root, "#v1.0.0").await;
+        assert!(package.version.starts_with("1.0.0+git."));
+
+        let package = fetch(&registry, &root, "").await;
+        assert!(package.version.starts_with("2.0.0+git."));
+    }
+
+    #[tokio::test]
+    async fn test_archive() {
+        let root = create_repository("archive");
+        let registry = GitRegistry::with_directory(root.join("cache"));
+
+        let package = fetch(&registry, &root, "#v1.0.0").await;
+        let Some(LockfileResolution::GitRepository(resolution)) = package.resolution else {
+            panic!("Expected a git resolution");
+        };
+        let archive = root.join("dep.tgz");
+        registry.archive(&resolution, &archive).await.unwrap();
 
-        Err(NetworkError::FailedToFetchVersion(
-            package.raw_version.clone(),
-        ))
+        let extracted = root.join("extracted");
+        Gzip::extract(&archive, &extracted).unwrap();
+        let manifest = std::fs::read_to_string(extracted.join("package/package.json")).unwrap();
+        assert!(manifest.contains(r#""version": "1.0.0""#));
     }
 }
@@ -1,5 +1,11 @@
+mod directory;
 mod git;
 mod npm;
+mod tarball;
+mod workspace;
 
+pub use directory::DirectoryRegistry;
 pub use git::GitRegistry;
 pub use npm::NpmRegistry;
+pub use tarball::TarballRegistry;
+pub use workspace::WorkspaceRegistry;
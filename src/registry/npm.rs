@@ -1,46 +1,188 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
 use async_trait::async_trait;
+use reqwest::header::{AUTHORIZATION, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use reqwest::{RequestBuilder, StatusCode};
+use tokio::sync::{Mutex, OnceCell};
 
 use crate::{
+    cache::{CachedPackument, PackumentCache},
+    config::{Npmrc, NPMRC, NPM_REGISTRY_URL},
     contracts::Registry,
     errors::NetworkError,
+    logger::CraftLogger,
+    network::{Http, NetworkMode, RetryPolicy},
     package::{FullPackage, NpmPackage, Package},
 };
 
-const NPM_REGISTRY_URL: &str = "https://registry.npmjs.org";
-
 #[derive(Debug)]
 pub struct NpmRegistry {
     http: reqwest::Client,
+    config: Npmrc,
+    cache: PackumentCache,
+    network_mode: NetworkMode,
+    /// Packuments by url, fetched once for all resolutions of the package
+    packuments: Mutex<HashMap<String, Arc<OnceCell<Arc<FullPackage>>>>>,
 }
 
+/// Seconds a cached packument is used without asking the registry, unless
+/// `cache-max-age` of .npmrc says otherwise
+const CACHE_MAX_AGE: i64 = 300;
+
 impl NpmRegistry {
     pub fn new() -> Self {
+        Self::with_config(NPMRC.clone())
+    }
+
+    pub fn with_config(config: Npmrc) -> Self {
         Self {
-            http: reqwest::Client::new(),
+            http: Http::client().clone(),
+            config,
+            cache: PackumentCache::default(),
+            network_mode: NetworkMode::default(),
+            packuments: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Keeps the packuments somewhere else than in the craft folder
+    #[cfg(test)]
+    pub fn with_cache(mut self, cache: PackumentCache) -> Self {
+        self.cache = cache;
+        self
+    }
+
+    /// Uses cached packuments however old they are unless online
+    pub fn with_network_mode(mut self, network_mode: NetworkMode) -> Self {
+        self.network_mode = network_mode;
+        self
+    }
+
+    pub fn network_mode(&self) -> NetworkMode {
+        self.network_mode
+    }
+
+    /// Asks for the packument, unless it changed since it was cached
+    fn request(&self, url: &str, cached: Option<&CachedPackument>) -> RequestBuilder {
+        let mut request = self
+            .http
+            .get(url)
+            .header("Accept", "application/vnd.npm.install-v1+json");
+        if let Some(auth) = self.config.auth_header_for(url) {
+            request = request.header(AUTHORIZATION, auth);
         }
+        if let Some(etag) = cached.and_then(|c| c.etag.as_ref()) {
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+        if let Some(modified) = cached.and_then(|c| c.last_modified.as_ref()) {
+            request = request.header(IF_MODIFIED_SINCE, modified);
+        }
+
+        request
+    }
+
+    fn cache_max_age(&self) -> i64 {
+        self.config
+            .get("cache-max-age")
+            .and_then(|age| age.parse().ok())
+            .unwrap_or(CACHE_MAX_AGE)
     }
 }
 
 impl NpmRegistry {
-    async fn get_full_package(&self, package: &Package) -> Result<FullPackage, NetworkError> {
-        let url = format!("{}/{}", NPM_REGISTRY_URL, package.name);
+    /// The tarball location of a package in the registry, for packages that
+    /// are only known by name and version (e.g. from the lockfile). The
+    /// downloader moves it to the configured registry.
+    pub fn tarball_url(name: &str, version: &str) -> String {
+        let file_name = name.rsplit('/').next().unwrap_or(name);
+        format!(
+            "{}/{}/-/{}-{}.tgz",
+            NPM_REGISTRY_URL, name, file_name, version
+        )
+    }
 
-        let response = self
-            .http
-            .get(&url)
-            .header("Accept", "application/vnd.npm.install-v1+json")
-            .send()
+    /// The packument of the package. Resolutions asking for it at the same
+    /// time wait for a single fetch, later ones get it from memory.
+    async fn get_full_package(&self, name: &str) -> Result<Arc<FullPackage>, NetworkError> {
+        let url = format!(
+            "{}/{}",
+            self.config.registry_for(name),
+            name.replace('/', "%2f")
+        );
+
+        let packument = {
+            let mut packuments = self.packuments.lock().await;
+            packuments.entry(url.clone()).or_default().clone()
+        };
+
+        packument
+            .get_or_try_init(|| async { self.load_full_package(name, &url).await.map(Arc::new) })
+            .await
+            .cloned()
+    }
+
+    /// The packument from the cache while it is fresh, otherwise the registry
+    /// is asked whether it changed
+    async fn load_full_package(&self, name: &str, url: &str) -> Result<FullPackage, NetworkError> {
+        let is_usable = |cached: &CachedPackument| match self.network_mode {
+            NetworkMode::Online => cached.is_fresh(self.cache_max_age()),
+            NetworkMode::PreferOffline | NetworkMode::Offline => true,
+        };
+        let cached = match self.cache.get(url).await {
+            Some(cached) if is_usable(&cached) => return Ok(cached.packument),
+            cached => cached,
+        };
+        if self.network_mode == NetworkMode::Offline {
+            return Err(NetworkError::Offline(vec![name.to_string()]));
+        }
+
+        let retry = RetryPolicy::from_config(&self.config);
+        let fetched = retry
+            .run(url, || async {
+                let response = self
+                    .request(url, cached.as_ref())
+                    .send()
+                    .await
+                    .map_err(|e| Http::request_error(url, e))?;
+                let response = Http::check_status(url, response)?;
+                if response.status() == StatusCode::NOT_MODIFIED {
+                    return Ok(None);
+                }
+
+                let header = |name| {
+                    response
+                        .headers()
+                        .get(name)
+                        .and_then(|value| value.to_str().ok())
+                        .map(str::to_string)
+                };
+                let (etag, last_modified) = (header(ETAG), header(LAST_MODIFIED));
+                let packument =
+                    response
+                        .json::<FullPackage>()
+                        .await
+                        .map_err(|e| match e.is_decode() {
+                            true => NetworkError::FailedToFetchVersion(url.to_string()),
+                            false => Http::request_error(url, e),
+                        })?;
+
+                Ok(Some((packument, etag, last_modified)))
+            })
             .await?;
 
-        let remote_package = match response.json::<FullPackage>().await {
-            Ok(pkg) => pkg,
-            Err(e) => {
-                println!("Error: {:?}", e);
-                return Err(NetworkError::FailedToFetchVersion(url));
-            }
+        let (packument, etag, last_modified) = match (fetched, cached) {
+            (Some(fetched), _) => fetched,
+            (None, Some(cached)) => (cached.packument, cached.etag, cached.last_modified),
+            // Not modified, although nothing was cached
+            (None, None) => return Err(NetworkError::FailedToFetchVersion(url.to_string())),
         };
 
-        Ok(remote_package)
+        let cached = CachedPackument::new(packument, etag, last_modified);
+        if let Err(e) = self.cache.set(url, &cached).await {
+            CraftLogger::verbose(format!("Failed to cache the packument of {}: {}", name, e));
+        }
+
+        Ok(cached.packument)
     }
 }
 
@@ -49,9 +191,24 @@ impl Registry for NpmRegistry {
     async fn fetch(&self, package: &Package) -> Result<NpmPackage, NetworkError> {
         log::info!("Fetching package: {}", package.to_string());
 
-        let pkg = self.get_full_package(package).await?;
+        let (name, range) = package.target();
+        let pkg = match self.get_full_package(&name).await {
+            Err(NetworkError::Offline(_)) => {
+                return Err(NetworkError::Offline(vec![package.to_string()]))
+            }
+            pkg => pkg?,
+        };
         let mut highest_satisfied_version: Option<NpmPackage> = None;
 
+        // A dist-tag like `latest` or `next` points to a single version
+        if range.parse::<nodejs_semver::Range>().is_err() {
+            highest_satisfied_version = pkg
+                .dist_tags
+                .get(&range)
+                .and_then(|version| pkg.versions.get(version))
+                .cloned();
+        }
+
         for (version, remote_package) in pkg.versions.iter() {
             if package.satisfies(version) {
                 match highest_satisfied_version {
@@ -70,12 +227,324 @@ impl Registry for NpmRegistry {
             }
         }
 
-        if let Some(v) = highest_satisfied_version {
-            return Ok(v.clone());
+        if let Some(mut v) = highest_satisfied_version {
+            // An alias is installed under its own name, it is locked and
+            // downloaded as the package it points to
+            if package.is_alias() {
+                v.name = package.name.clone();
+                v.alias_of = Some(name);
+            }
+            return Ok(v);
         }
 
-        println!("Failed to fetch version: {}", package);
+        CraftLogger::verbose(format!("No version of {} matches {}", name, range));
 
         Err(NetworkError::FailedToFetchVersion(package.to_string()))
     }
 }
+
+// ─── Tests ───────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actors::PackageType;
+    use crate::network::mock_server::{packument, MockResponse, MockServer};
+    use crate::pipeline::fixtures::TempDir;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// A registry caching its packuments in `cache`
+    fn fresh_registry(config: Npmrc, cache: &TempDir) -> NpmRegistry {
+        NpmRegistry::with_config(config).with_cache(PackumentCache::new(cache.to_path_buf()))
+    }
+
+    #[tokio::test]
+    async fn test_routes_packages_to_configured_registries() {
+        let server = MockServer::start(|request| {
+            let base = "http://127.0.0.1";
+            match request.path.as_str() {
+                "/private/@company%2ffoo" => {
+                    MockResponse::json(packument(base, "@company/foo", &["1.0.0", "1.2.0"]))
+                }
+                "/mirror/lodash" => MockResponse::json(packument(base, "lodash", &["4.17.21"])),
+                _ => MockResponse::new(404, "{}"),
+            }
+        })
+        .await;
+
+        let cache = TempDir::new("packuments-routes-packages-to-configured-registries");
+        let config = Npmrc::parse(&format!(
+            "registry={url}/mirror/\n@company:registry={url}/private/",
+            url = server.url()
+        ));
+        let registry = fresh_registry(config, &cache);
+
+        let scoped = registry
+            .fetch(&Package::new(PackageType::Prod(
+                "@company/foo@^1.0.0".to_string(),
+            )))
+            .await
+            .unwrap();
+        let unscoped = registry
+            .fetch(&Package::new(PackageType::Prod(
+                "lodash@^4.0.0".to_string(),
+            )))
+            .await
+            .unwrap();
+
+        assert_eq!(scoped.version, "1.2.0");
+        assert_eq!(unscoped.version, "4.17.21");
+
+        let requests = server.requests().await;
+        let paths = requests.iter().map(|r| r.path.as_str()).collect::<Vec<_>>();
+        assert_eq!(paths, vec!["/private/@company%2ffoo", "/mirror/lodash"]);
+        assert_eq!(
+            requests[0].header("Accept"),
+            Some("application/vnd.npm.install-v1+json")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_sends_auth_only_to_matching_registry() {
+        let server = MockServer::start(|request| {
+            let base = "http://127.0.0.1";
+            match request.path.as_str() {
+                "/private/@company%2ffoo" => {
+                    MockResponse::json(packument(base, "@company/foo", &["1.0.0"]))
+                }
+                _ => MockResponse::json(packument(base, "lodash", &["4.17.21"])),
+            }
+        })
+        .await;
+
+        let host = server.url().trim_start_matches("http:");
+        let cache = TempDir::new("packuments-sends-auth-only-to-matching-registry");
+        let config = Npmrc::parse_with_env(
+            &format!(
+                "@company:registry={url}/private/\n{host}/private/:_authToken=${{NPM_TOKEN}}\nregistry=https://registry.example.com",
+                url = server.url(),
+                host = host
+            ),
+            |name| (name == "NPM_TOKEN").then(|| "s3cr3t".to_string()),
+        );
+        let registry = fresh_registry(config, &cache);
+
+        registry
+            .fetch(&Package::new(PackageType::Prod(
+                "@company/foo@1".to_string(),
+            )))
+            .await
+            .unwrap();
+
+        let requests = server.requests().await;
+        assert_eq!(requests[0].header("Authorization"), Some("Bearer s3cr3t"));
+
+        let config = Npmrc::parse(&format!("registry={}/mirror", server.url()));
+        fresh_registry(config, &cache)
+            .fetch(&Package::new(PackageType::Prod("lodash@4".to_string())))
+            .await
+            .unwrap();
+
+        let requests = server.requests().await;
+        assert_eq!(requests[1].header("Authorization"), None);
+    }
+
+    #[tokio::test]
+    async fn test_resolves_dist_tags_and_aliases() {
+        let server = MockServer::start(|request| {
+            let base = "http://127.0.0.1";
+            match request.path.as_str() {
+                "/typescript" => {
+                    let packument = packument(base, "typescript", &["5.6.3", "5.8.0-dev"]);
+                    MockResponse::json(packument.replacen(
+                        r#""latest": "5.8.0-dev""#,
+                        r#""latest": "5.6.3", "next": "5.8.0-dev""#,
+                        1,
+                    ))
+                }
+                "/react" => MockResponse::json(packument(base, "react", &["17.0.2", "18.3.1"])),
+                _ => MockResponse::new(404, "{}"),
+            }
+        })
+        .await;
+
+        let cache = TempDir::new("packuments-resolves-dist-tags-and-aliases");
+        let config = Npmrc::parse(&format!("registry={}", server.url()));
+        let registry = fresh_registry(config, &cache);
+        let package = |spec: &str| Package::new(PackageType::Prod(spec.to_string()));
+
+        assert_eq!(
+            registry
+                .fetch(&package("typescript@next"))
+                .await
+                .unwrap()
+                .version,
+            "5.8.0-dev"
+        );
+        assert_eq!(
+            registry
+                .fetch(&package("typescript@latest"))
+                .await
+                .unwrap()
+                .version,
+            "5.6.3"
+        );
+        assert!(registry.fetch(&package("typescript@beta")).await.is_err());
+
+        let alias = registry
+            .fetch(&package("my-react@npm:react@^18"))
+            .await
+            .unwrap();
+        assert_eq!(alias.name, "my-react");
+        assert_eq!(alias.version, "18.3.1");
+        assert_eq!(alias.alias_of.as_deref(), Some("react"));
+        assert_eq!(alias.target().to_string(), "react@18.3.1");
+        assert!(alias.resolution.is_none());
+        assert!(alias.dist.tarball.ends_with("/react/-/react-18.3.1.tgz"));
+    }
+
+    #[tokio::test]
+    async fn test_revalidates_cached_packuments() {
+        let server = MockServer::start(|request| match request.header("If-None-Match") {
+            Some(r#""v1""#) => MockResponse::new(304, ""),
+            _ => MockResponse::json(packument("http://127.0.0.1", "lodash", &["4.17.21"]))
+                .header("ETag", r#""v1""#),
+        })
+        .await;
+
+        let directory = TempDir::new("revalidate");
+        let registry = |max_age: &str, network_mode: NetworkMode| {
+            let config = Npmrc::parse(&format!(
+                "registry={}\ncache-max-age={}",
+                server.url(),
+                max_age
+            ));
+            NpmRegistry::with_config(config)
+                .with_cache(PackumentCache::new(directory.to_path_buf()))
+                .with_network_mode(network_mode)
+        };
+        let lodash = Package::new(PackageType::Prod("lodash@^4".to_string()));
+
+        // Stale right away, so the second fetch asks whether it changed
+        for _ in 0..2 {
+            let fetched = registry("0", NetworkMode::Online).fetch(&lodash).await;
+            assert_eq!(fetched.unwrap().version, "4.17.21");
+        }
+        let requests = server.requests().await;
+        assert_eq!(requests[0].header("If-None-Match"), None);
+        assert_eq!(requests[1].header("If-None-Match"), Some(r#""v1""#));
+
+        // Fresh or offline, the registry isn't asked at all
+        registry("300", NetworkMode::Online)
+            .fetch(&lodash)
+            .await
+            .unwrap();
+        registry("0", NetworkMode::PreferOffline)
+            .fetch(&lodash)
+            .await
+            .unwrap();
+        assert_eq!(server.requests().await.len(), 2);
+
+        let react = Package::new(PackageType::Prod("react@^18".to_string()));
+        assert!(matches!(
+            registry("0", NetworkMode::Offline).fetch(&react).await,
+            Err(NetworkError::Offline(missing)) if missing == vec!["react@^18"]
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_retries_flaky_registries() {
+        let attempts = std::sync::Arc::new(AtomicUsize::new(0));
+        let counter = attempts.clone();
+        let server = MockServer::start(move |request| match request.path.as_str() {
+            "/lodash" => match counter.fetch_add(1, Ordering::SeqCst) {
+                0 => MockResponse::new(503, ""),
+                1 => MockResponse::new(429, "").header("Retry-After", "0"),
+                _ => MockResponse::json(packument("http://127.0.0.1", "lodash", &["4.17.21"])),
+            },
+            "/private" => MockResponse::new(401, ""),
+            _ => MockResponse::new(404, ""),
+        })
+        .await;
+
+        let cache = TempDir::new("packuments-retries-flaky-registries");
+        let config = Npmrc::parse(&format!(
+            "registry={}\nfetch-retries=2\nfetch-retry-mintimeout=1",
+            server.url()
+        ));
+        let registry = fresh_registry(config, &cache);
+        let package = |spec: &str| Package::new(PackageType::Prod(spec.to_string()));
+
+        let lodash = registry.fetch(&package("lodash@^4")).await.unwrap();
+        assert_eq!(lodash.version, "4.17.21");
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+
+        // Retrying doesn't help with auth problems or missing packages
+        assert!(matches!(
+            registry.fetch(&package("private@1")).await,
+            Err(NetworkError::Unauthorized(_, 401))
+        ));
+        assert!(matches!(
+            registry.fetch(&package("missing@1")).await,
+            Err(NetworkError::NotFound(_))
+        ));
+        assert_eq!(server.requests().await.len(), 5);
+    }
+
+    #[tokio::test]
+    async fn test_accepts_compressed_packuments() {
+        use flate2::{write::GzEncoder, Compression};
+        use std::io::Write;
+
+        let server = MockServer::start(|request| {
+            let body = packument("http://127.0.0.1", "lodash", &["4.17.21"]);
+            let accepts = request.header("Accept-Encoding").unwrap_or_default();
+            if !accepts.contains("gzip") || !accepts.contains("br") {
+                return MockResponse::json(body);
+            }
+
+            let mut encoder = GzEncoder::new(vec![], Compression::default());
+            encoder.write_all(body.as_bytes()).unwrap();
+            MockResponse::json(encoder.finish().unwrap()).header("Content-Encoding", "gzip")
+        })
+        .await;
+
+        let cache = TempDir::new("packuments-accepts-compressed-packuments");
+        let config = Npmrc::parse(&format!("registry={}", server.url()));
+        let lodash = fresh_registry(config, &cache)
+            .fetch(&Package::new(PackageType::Prod("lodash@^4".to_string())))
+            .await
+            .unwrap();
+
+        assert_eq!(lodash.version, "4.17.21");
+    }
+
+    #[tokio::test]
+    async fn test_fetches_each_packument_once() {
+        let server = MockServer::start(|request| match request.path.as_str() {
+            "/debug" => MockResponse::json(packument(
+                "http://127.0.0.1",
+                "debug",
+                &["2.6.9", "3.2.7", "4.3.7"],
+            )),
+            _ => MockResponse::new(404, ""),
+        })
+        .await;
+
+        // Stale right away, so only memory saves the requests
+        let cache = TempDir::new("packuments-fetches-each-packument-once");
+        let config = Npmrc::parse(&format!("registry={}\ncache-max-age=0", server.url()));
+        let registry = fresh_registry(config, &cache);
+        let package = |spec: &str| Package::new(PackageType::Prod(spec.to_string()));
+
+        let packages = vec![package("debug@^4"); 20];
+        let fetched = futures::future::join_all(packages.iter().map(|p| registry.fetch(p))).await;
+        assert!(fetched
+            .iter()
+            .all(|f| f.as_ref().unwrap().version == "4.3.7"));
+
+        let older = registry.fetch(&package("debug@^2")).await.unwrap();
+        assert_eq!(older.version, "2.6.9");
+        assert_eq!(server.requests().await.len(), 1);
+    }
+}
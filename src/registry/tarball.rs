@@ -0,0 +1,240 @@
+use std::env;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use sha2::{Digest, Sha512};
+
+use crate::{
+    cache::PackagesCache,
+    config::NPMRC,
+    contracts::Registry,
+    errors::NetworkError,
+    lockfile::lockfile_structure::{LockfileResolution, TarballResolution},
+    network::{Http, RetryPolicy},
+    package::{NpmPackage, Package},
+    tar::Gzip,
+};
+
+const FILE_PREFIX: &str = "file:";
+
+/// Keeps concurrent downloads from sharing a temporary file
+static DOWNLOADS: AtomicUsize = AtomicUsize::new(0);
+
+// ─── TarballRegistry ─────────────────────────────────────────────────────────
+
+/// Resolves dependencies that point to a tarball, either remote like
+/// `https://host/foo-1.0.0.tgz` or local like `file:../foo.tgz`. The tarball
+/// is stored in the packages cache while its manifest is read.
+#[derive(Debug)]
+pub struct TarballRegistry {
+    directory: PathBuf,
+}
+
+// ─────────────────────────────────────────────────────────────────────────────
+
+impl TarballRegistry {
+    pub fn new() -> Self {
+        Self::with_directory(PackagesCache::default().get_cache_directory().clone())
+    }
+
+    pub fn with_directory(directory: PathBuf) -> Self {
+        Self { directory }
+    }
+
+    pub fn is_local(tarball: &str) -> bool {
+        tarball.starts_with(FILE_PREFIX)
+    }
+
+    /// The path of a local tarball, relative paths belong to the root of the
+    /// project
+    fn local_path(tarball: &str) -> Option<PathBuf> {
+        tarball
+            .strip_prefix(FILE_PREFIX)
+            .map(|path| env::current_dir().unwrap().join(path))
+    }
+
+    /// Copies or downloads the tarball to `dest`
+    pub async fn store(&self, tarball: &str, dest: &Path) -> Result<(), NetworkError> {
+        match Self::local_path(tarball) {
+            Some(path) => {
+                tokio::fs::copy(&path, dest).await?;
+            }
+            None => {
+                Http::download_file(
+                    tarball,
+                    &dest.to_path_buf(),
+                    "",
+                    None,
+                    NPMRC.auth_header_for(tarball).as_deref(),
+                    &RetryPolicy::from_config(&NPMRC),
+                )
+                .await?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Registry for TarballRegistry {
+    async fn fetch(&self, package: &Package) -> Result<NpmPackage, NetworkError> {
+        log::info!("Fetching tarball: {}", package.to_string());
+
+        let tarball = match package.raw_version.strip_prefix(FILE_PREFIX) {
+            Some(path) => format!("{}{}", FILE_PREFIX, package.root_relative(path)),
+            None => package.raw_version.clone(),
+        };
+        tokio::fs::create_dir_all(&self.directory).await?;
+        let download_path = self.directory.join(format!(
+            ".{}-{}-{}.tgz",
+            package.name.replace('/', "+"),
+            std::process::id(),
+            DOWNLOADS.fetch_add(1, Ordering::Relaxed)
+        ));
+        self.store(&tarball, &download_path).await?;
+
+        let digest = Sha512::digest(tokio::fs::read(&download_path).await?);
+        let integrity = format!("sha512-{}", STANDARD.encode(digest));
+
+        let manifest = Gzip::read_file(&download_path, "package.json")
+            .ok()
+            .flatten()
+            .ok_or_else(|| NetworkError::FailedToFetchVersion(package.to_string()))?;
+        let mut remote_package = serde_json::from_str::<NpmPackage>(&manifest)
+            .map_err(|_| NetworkError::FailedToFetchVersion(package.to_string()))?;
+
+        // The hash is part of the version, so different tarballs with the
+        // same version don't share an entry in the packages cache
+        remote_package.name = package.name.clone();
+        remote_package.version = format!(
+            "{}+sha.{}",
+            remote_package.version,
+            &hex::encode(digest)[..7]
+        );
+        remote_package.dist.tarball = tarball.clone();
+        remote_package.dist.integrity = Some(integrity.clone());
+        remote_package.resolution = Some(LockfileResolution::Tarball(TarballResolution {
+            tarball,
+            integrity: Some(integrity),
+        }));
+
+        let cache_path = self.directory.join(remote_package.to_string());
+        if let Some(parent) = cache_path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        tokio::fs::rename(&download_path, &cache_path).await?;
+
+        Ok(remote_package)
+    }
+}
+
+// ─── Tests ───────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actors::PackageType;
+    use crate::network::mock_server::{MockResponse, MockServer};
+    use crate::pipeline::fixtures::TempDir;
+    use flate2::{write::GzEncoder, Compression};
+    use std::io::Write;
+
+    fn create_tar(version: &str) -> Vec<u8> {
+        let manifest = format!(
+            r#"{{"name": "vendored", "version": "{}", "dependencies": {{"lodash": "^4.0.0"}}}}"#,
+            version
+        );
+        let mut header = tar::Header::new_gnu();
+        header.set_size(manifest.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+
+        let mut builder = tar::Builder::new(vec![]);
+        builder
+            .append_data(&mut header, "package/package.json", manifest.as_bytes())
+            .unwrap();
+        builder.into_inner().unwrap()
+    }
+
+    fn create_tarball(version: &str) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(vec![], Compression::default());
+        encoder.write_all(&create_tar(version)).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_fetch_local_tarball() {
+        let dir = TempDir::new("tarball-local");
+        std::fs::write(dir.join("vendored-1.0.0.tgz"), create_tarball("1.0.0")).unwrap();
+        let registry = TarballRegistry::with_directory(dir.join("cache"));
+
+        let spec = format!("foo@file:{}", dir.join("vendored-1.0.0.tgz").display());
+        let package = registry
+            .fetch(&Package::new(PackageType::Prod(spec)))
+            .await
+            .unwrap();
+
+        assert_eq!(package.name, "foo");
+        assert!(package.version.starts_with("1.0.0+sha."));
+        assert!(package.dependencies.unwrap().contains_key("lodash"));
+        assert!(dir
+            .join("cache")
+            .join(format!("foo@{}", package.version))
+            .exists());
+        match package.resolution {
+            Some(LockfileResolution::Tarball(t)) => {
+                assert!(t.tarball.starts_with("file:"));
+                assert!(t.integrity.unwrap().starts_with("sha512-"));
+            }
+            other => panic!("Unexpected resolution {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fetch_remote_tarball() {
+        let server = MockServer::start(|request| match request.path.as_str() {
+            "/vendored-2.0.0.tgz" => MockResponse::new(200, create_tarball("2.0.0")),
+            _ => MockResponse::new(404, ""),
+        })
+        .await;
+        let dir = TempDir::new("tarball-remote");
+        let registry = TarballRegistry::with_directory(dir.to_path_buf());
+
+        let spec = format!("@company/foo@{}/vendored-2.0.0.tgz", server.url());
+        let package = registry
+            .fetch(&Package::new(PackageType::Prod(spec)))
+            .await
+            .unwrap();
+
+        assert_eq!(package.name, "@company/foo");
+        assert!(package.version.starts_with("2.0.0+sha."));
+        assert!(dir
+            .join(format!("@company/foo@{}", package.version))
+            .exists());
+    }
+
+    #[tokio::test]
+    async fn test_fetch_uncompressed_tarball() {
+        let dir = TempDir::new("tarball-uncompressed");
+        std::fs::write(dir.join("vendored-1.0.0.tar"), create_tar("1.0.0")).unwrap();
+        let registry = TarballRegistry::with_directory(dir.join("cache"));
+
+        let spec = format!("foo@file:{}", dir.join("vendored-1.0.0.tar").display());
+        let package = registry
+            .fetch(&Package::new(PackageType::Prod(spec)))
+            .await
+            .unwrap();
+
+        assert!(package.version.starts_with("1.0.0+sha."));
+        let extracted = dir.join("extracted");
+        Gzip::extract(
+            &dir.join("cache").join(format!("foo@{}", package.version)),
+            &extracted,
+        )
+        .unwrap();
+        assert!(extracted.join("package/package.json").exists());
+    }
+}
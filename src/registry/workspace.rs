@@ -0,0 +1,202 @@
+use std::path::{Component, Path};
+
+use async_trait::async_trait;
+use nodejs_semver::{Range, Version};
+use path_clean::clean;
+
+use crate::{
+    contracts::Registry,
+    errors::NetworkError,
+    lockfile::lockfile_structure::{DirectoryResolution, LockfileResolution},
+    package::{NpmPackage, Package, WORKSPACE_PREFIX},
+    workspace::{Workspace, WorkspaceProject, WORKSPACE},
+};
+
+// ─── WorkspaceRegistry ───────────────────────────────────────────────────────
+
+/// Resolves `workspace:` specifiers to the project of the workspace with the
+/// same name. The project is linked, its own dependencies are installed when
+/// the project itself is installed.
+#[derive(Debug)]
+pub struct WorkspaceRegistry<'a> {
+    workspace: &'a Workspace,
+}
+
+// ─────────────────────────────────────────────────────────────────────────────
+
+impl WorkspaceRegistry<'static> {
+    pub fn new() -> Self {
+        Self::with_workspace(&WORKSPACE)
+    }
+}
+
+impl<'a> WorkspaceRegistry<'a> {
+    pub fn with_workspace(workspace: &'a Workspace) -> Self {
+        Self { workspace }
+    }
+
+    /// `workspace:*`, `workspace:^` and `workspace:~` accept every version,
+    /// everything else is a range the project has to satisfy
+    fn satisfies(range: &str, version: &str) -> bool {
+        if matches!(range, "*" | "^" | "~") {
+            return true;
+        }
+
+        match (range.parse::<Range>(), version.parse::<Version>()) {
+            (Ok(range), Ok(version)) => version.satisfies(&range),
+            _ => false,
+        }
+    }
+
+    fn project(&self, package: &Package) -> Result<&WorkspaceProject, NetworkError> {
+        let range = package
+            .raw_version
+            .strip_prefix(WORKSPACE_PREFIX)
+            .unwrap_or(&package.raw_version);
+
+        self.workspace
+            .find(&package.name)
+            .filter(|p| Self::satisfies(range, p.manifest.version.as_deref().unwrap_or("0.0.0")))
+            .ok_or_else(|| NetworkError::FailedToFetchVersion(package.to_string()))
+    }
+
+    /// The path from one folder to another, both relative to the root of the
+    /// workspace, like pnpm writes `link:../utils` for `packages/app`
+    fn relative_path(from: &Path, to: &Path) -> String {
+        let from = clean(from);
+        let to = clean(to);
+        let from = from.components().filter(|c| *c != Component::CurDir);
+        let to = to
+            .components()
+            .filter(|c| *c != Component::CurDir)
+            .collect::<Vec<_>>();
+
+        let mut common = 0;
+        let mut ups = 0;
+        for component in from {
+            match to.get(common) {
+                Some(c) if *c == component && ups == 0 => common += 1,
+                _ => ups += 1,
+            }
+        }
+
+        let path = std::iter::repeat_n("..".to_string(), ups)
+            .chain(
+                to[common..]
+                    .iter()
+                    .map(|c| c.as_os_str().to_string_lossy().to_string()),
+            )
+            .collect::<Vec<_>>();
+        match path.is_empty() {
+            true => ".".to_string(),
+            false => path.join("/"),
+        }
+    }
+}
+
+#[async_trait]
+impl Registry for WorkspaceRegistry<'_> {
+    async fn fetch(&self, package: &Package) -> Result<NpmPackage, NetworkError> {
+        let project = self.project(package)?;
+        let link = Self::relative_path(&package.directory, Path::new(&project.id));
+
+        Ok(NpmPackage {
+            name: package.name.clone(),
+            version: format!("link:{}", link),
+            resolution: Some(LockfileResolution::Directory(DirectoryResolution {
+                directory: project.id.clone(),
+            })),
+            ..Default::default()
+        })
+    }
+}
+
+// ─── Tests ───────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actors::PackageType;
+    use crate::package::PackageJson;
+    use std::path::PathBuf;
+
+    fn workspace() -> Workspace {
+        Workspace {
+            projects: vec![WorkspaceProject {
+                id: "packages/utils".to_string(),
+                directory: PathBuf::from("/repo/packages/utils"),
+                manifest: serde_json::from_str::<PackageJson>(
+                    r#"{"name": "@repo/utils", "version": "1.2.0"}"#,
+                )
+                .unwrap(),
+            }],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fetch_workspace_package() {
+        let workspace = workspace();
+        let registry = WorkspaceRegistry::with_workspace(&workspace);
+
+        for spec in ["workspace:*", "workspace:^", "workspace:^1.0.0"] {
+            let package = registry
+                .fetch(&Package::new(PackageType::Prod(format!(
+                    "@repo/utils@{}",
+                    spec
+                ))))
+                .await
+                .unwrap();
+
+            assert_eq!(package.version, "link:packages/utils");
+            match package.resolution {
+                Some(LockfileResolution::Directory(d)) => {
+                    assert_eq!(d.directory, "packages/utils")
+                }
+                other => panic!("Unexpected resolution {:?}", other),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fetch_unknown_or_unsatisfied() {
+        let workspace = workspace();
+        let registry = WorkspaceRegistry::with_workspace(&workspace);
+
+        for spec in ["@repo/other@workspace:*", "@repo/utils@workspace:^2.0.0"] {
+            assert!(registry
+                .fetch(&Package::new(PackageType::Prod(spec.to_string())))
+                .await
+                .is_err());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_link_is_relative_to_the_importer() {
+        let workspace = workspace();
+        let registry = WorkspaceRegistry::with_workspace(&workspace);
+
+        let package = Package::new(PackageType::Prod("@repo/utils@workspace:*".to_string()))
+            .with_directory(Path::new("packages/app"));
+        let package = registry.fetch(&package).await.unwrap();
+
+        assert_eq!(package.version, "link:../utils");
+        match package.resolution {
+            Some(LockfileResolution::Directory(d)) => assert_eq!(d.directory, "packages/utils"),
+            other => panic!("Unexpected resolution {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_relative_path() {
+        let relative =
+            |from: &str, to: &str| WorkspaceRegistry::relative_path(Path::new(from), Path::new(to));
+
+        assert_eq!(relative(".", "packages/utils"), "packages/utils");
+        assert_eq!(relative("packages/app", "packages/utils"), "../utils");
+        assert_eq!(
+            relative("apps/web/nested", "packages/utils"),
+            "../../../packages/utils"
+        );
+        assert_eq!(relative("packages/utils", "packages/utils"), ".");
+    }
+}
@@ -1,5 +1,6 @@
 use std::{
     fs::File,
+    io::{Read, Seek, SeekFrom},
     path::{Path, PathBuf},
 };
 
@@ -10,12 +11,25 @@ use crate::errors::ZipError;
 
 pub struct Gzip;
 
+/// The first bytes of every gzip stream
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
 impl Gzip {
-    pub fn extract(source: &Path, dest: &PathBuf) -> Result<(), ZipError> {
-        let file = File::open(source)?;
+    /// Tarballs are usually gzipped, plain `.tar` files are read as they are
+    fn open(source: &Path) -> Result<Box<dyn Read>, ZipError> {
+        let mut file = File::open(source)?;
+        let mut magic = [0u8; 2];
+        let is_gzip = file.read_exact(&mut magic).is_ok() && magic == GZIP_MAGIC;
+        file.seek(SeekFrom::Start(0))?;
+
+        Ok(match is_gzip {
+            true => Box::new(GzDecoder::new(file)),
+            false => Box::new(file),
+        })
+    }
 
-        let tar = GzDecoder::new(file);
-        let mut archive = Archive::new(tar);
+    pub fn extract(source: &Path, dest: &PathBuf) -> Result<(), ZipError> {
+        let mut archive = Archive::new(Self::open(source)?);
 
         match archive.unpack(dest) {
             Ok(_) => {}
@@ -27,4 +41,33 @@ impl Gzip {
 
         Ok(())
     }
+
+    /// Reads a file of the top level folder of the archive, e.g. the
+    /// `package.json` below `package/`, without extracting the archive
+    pub fn read_file(source: &Path, file_name: &str) -> Result<Option<String>, ZipError> {
+        let mut archive = Archive::new(Self::open(source)?);
+
+        let entries = archive
+            .entries()
+            .map_err(|e| ZipError::FailedToUnzip(e.to_string()))?;
+        for entry in entries {
+            let mut entry = entry.map_err(|e| ZipError::FailedToUnzip(e.to_string()))?;
+            let path = entry
+                .path()
+                .map_err(|e| ZipError::FailedToUnzip(e.to_string()))?
+                .to_path_buf();
+
+            let mut components = path.components();
+            let is_top_level = components.next().is_some()
+                && components.next().map(|c| c.as_os_str()) == Some(file_name.as_ref())
+                && components.next().is_none();
+            if is_top_level {
+                let mut content = String::new();
+                entry.read_to_string(&mut content)?;
+                return Ok(Some(content));
+            }
+        }
+
+        Ok(None)
+    }
 }
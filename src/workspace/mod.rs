@@ -0,0 +1,5 @@
+mod projects;
+
+pub use projects::Workspace;
+pub use projects::WorkspaceProject;
+pub use projects::WORKSPACE;
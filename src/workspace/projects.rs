@@ -0,0 +1,208 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use glob::Pattern;
+use lazy_static::lazy_static;
+use serde::Deserialize;
+
+use crate::lockfile::constants::CURRENT_IMPORTER;
+use crate::package::PackageJson;
+
+const WORKSPACE_FILE: &str = "pnpm-workspace.yaml";
+const PACKAGE_JSON: &str = "package.json";
+
+lazy_static! {
+    pub static ref WORKSPACE: Workspace = Workspace::discover(&env::current_dir().unwrap());
+}
+
+// ─── Workspace ───────────────────────────────────────────────────────────────
+
+/// All projects below the root that match the globs of pnpm-workspace.yaml
+/// or the `workspaces` field of the root package.json. The root itself is
+/// always the first project.
+#[derive(Debug, Clone, Default)]
+pub struct Workspace {
+    pub projects: Vec<WorkspaceProject>,
+}
+
+#[derive(Debug, Clone)]
+pub struct WorkspaceProject {
+    /// The importer id of the lockfile, the path relative to the root
+    pub id: String,
+    pub directory: PathBuf,
+    pub manifest: PackageJson,
+}
+
+#[derive(Debug, Deserialize)]
+struct WorkspaceFile {
+    packages: Option<Vec<String>>,
+}
+
+// ─────────────────────────────────────────────────────────────────────────────
+
+impl WorkspaceProject {
+    fn read(root: &Path, directory: &Path) -> Option<Self> {
+        let content = fs::read_to_string(directory.join(PACKAGE_JSON)).ok()?;
+        let manifest = serde_json::from_str::<PackageJson>(&content).ok()?;
+
+        let relative = directory.strip_prefix(root).ok()?;
+        let id = if relative.as_os_str().is_empty() {
+            CURRENT_IMPORTER.to_string()
+        } else {
+            relative
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/")
+        };
+
+        Some(Self {
+            id,
+            directory: directory.to_path_buf(),
+            manifest,
+        })
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.manifest.name.as_deref()
+    }
+}
+
+impl Workspace {
+    pub fn discover(root: &Path) -> Self {
+        let Some(root_project) = WorkspaceProject::read(root, root) else {
+            return Self::default();
+        };
+
+        let globs = Self::globs(root, &root_project.manifest);
+        let (excludes, includes): (Vec<_>, Vec<_>) =
+            globs.iter().partition(|glob| glob.starts_with('!'));
+        let excludes = excludes
+            .iter()
+            .filter_map(|glob| Pattern::new(&glob[1..]).ok())
+            .collect::<Vec<_>>();
+
+        let mut projects = includes
+            .iter()
+            .filter_map(|glob| {
+                glob::glob(&root.join(glob.trim_end_matches('/')).to_string_lossy()).ok()
+            })
+            .flatten()
+            .flatten()
+            .filter(|path| path.is_dir() && path.as_path() != root)
+            .filter_map(|path| WorkspaceProject::read(root, &path))
+            .filter(|project| !project.id.split('/').any(|c| c == "node_modules"))
+            .filter(|project| !excludes.iter().any(|e| e.matches(&project.id)))
+            .collect::<Vec<_>>();
+        projects.sort_by(|a, b| a.id.cmp(&b.id));
+        projects.dedup_by(|a, b| a.id == b.id);
+        projects.insert(0, root_project);
+
+        Self { projects }
+    }
+
+    /// pnpm-workspace.yaml wins over the `workspaces` field of package.json
+    fn globs(root: &Path, manifest: &PackageJson) -> Vec<String> {
+        let workspace_file = fs::read_to_string(root.join(WORKSPACE_FILE))
+            .ok()
+            .and_then(|c| serde_yaml_ng::from_str::<WorkspaceFile>(&c).ok());
+
+        match workspace_file {
+            Some(file) => file.packages.unwrap_or_default(),
+            None => manifest
+                .workspaces
+                .as_ref()
+                .map(|w| w.globs().to_vec())
+                .unwrap_or_default(),
+        }
+    }
+
+    pub fn is_workspace(&self) -> bool {
+        self.projects.len() > 1
+    }
+
+    pub fn find(&self, name: &str) -> Option<&WorkspaceProject> {
+        self.projects.iter().find(|p| p.name() == Some(name))
+    }
+}
+
+// ─── Tests ───────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::fixtures::TempDir;
+
+    fn create_project(root: &Path, dir: &str, name: &str) {
+        let dir = root.join(dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join(PACKAGE_JSON),
+            format!(r#"{{"name": "{}", "version": "1.0.0"}}"#, name),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_discover_pnpm_workspace() {
+        let root = TempDir::new("workspace-pnpm");
+        fs::write(
+            root.join(PACKAGE_JSON),
+            r#"{"name": "root", "workspaces": ["ignored/*"]}"#,
+        )
+        .unwrap();
+        fs::write(
+            root.join(WORKSPACE_FILE),
+            "packages:\n  - 'packages/*'\n  - 'apps/web'\n  - '!packages/private'\n",
+        )
+        .unwrap();
+        create_project(&root, "packages/a", "a");
+        create_project(&root, "packages/b", "b");
+        create_project(&root, "packages/private", "private");
+        create_project(&root, "packages/a/node_modules/dep", "dep");
+        create_project(&root, "apps/web", "web");
+        create_project(&root, "ignored/c", "c");
+
+        let workspace = Workspace::discover(&root);
+        let ids = workspace
+            .projects
+            .iter()
+            .map(|p| p.id.as_str())
+            .collect::<Vec<_>>();
+
+        assert_eq!(ids, vec![".", "apps/web", "packages/a", "packages/b"]);
+        assert!(workspace.is_workspace());
+        assert_eq!(
+            workspace.find("b").unwrap().directory,
+            root.join("packages/b")
+        );
+    }
+
+    #[test]
+    fn test_discover_package_json_workspaces() {
+        let root = TempDir::new("workspace-yarn");
+        fs::write(
+            root.join(PACKAGE_JSON),
+            r#"{"name": "root", "workspaces": {"packages": ["libs/*"]}}"#,
+        )
+        .unwrap();
+        create_project(&root, "libs/utils", "utils");
+
+        let workspace = Workspace::discover(&root);
+
+        assert_eq!(workspace.projects.len(), 2);
+        assert_eq!(workspace.projects[1].id, "libs/utils");
+    }
+
+    #[test]
+    fn test_discover_without_workspace() {
+        let root = TempDir::new("workspace-single");
+        fs::write(root.join(PACKAGE_JSON), r#"{"name": "root"}"#).unwrap();
+
+        let workspace = Workspace::discover(&root);
+
+        assert_eq!(workspace.projects.len(), 1);
+        assert!(!workspace.is_workspace());
+    }
+}